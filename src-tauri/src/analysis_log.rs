@@ -0,0 +1,274 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Days, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::config_store::{ConfigFile, ConfigStore};
+use crate::models::{AnalyzedBy, AnalyzedNotification, UrgencyLevel};
+use crate::storage::{file_size, Maintainable, StoreLimit};
+
+/// Default size limit for `analysis_log.jsonl`.
+const ANALYSIS_LOG_MAX_BYTES: u64 = 5 * 1024 * 1024;
+
+/// One line of `analysis_log.jsonl`. Notification text is left out, so the
+/// log can be shared like a redacted diagnostic bundle.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisLogEntry {
+    pub logged_at: i64,
+    pub id: i64,
+    pub bundle_id: String,
+    pub urgency: UrgencyLevel,
+    pub analyzed_by: AnalyzedBy,
+    pub confidence: f64,
+    pub low_confidence: bool,
+}
+
+impl AnalysisLogEntry {
+    pub fn new(item: &AnalyzedNotification, logged_at: i64) -> Self {
+        Self {
+            logged_at,
+            id: item.id,
+            bundle_id: item.bundle_id.clone(),
+            urgency: item.urgency,
+            analyzed_by: item.analyzed_by,
+            confidence: item.confidence,
+            low_confidence: item.low_confidence,
+        }
+    }
+}
+
+/// Append-only JSONL log of analysis results, off unless enabled. Batches
+/// are analyzed concurrently from several threads; every append and prune
+/// holds `lock`, so lines never interleave. Writes go through the config
+/// store, so safe mode leaves the real log alone.
+pub struct AnalysisLog {
+    store: ConfigStore,
+    enabled: AtomicBool,
+    lock: Mutex<()>,
+}
+
+impl AnalysisLog {
+    pub fn new(store: &ConfigStore, enabled: bool) -> Self {
+        Self {
+            store: store.clone(),
+            enabled: AtomicBool::new(enabled),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Appends one line per item when enabled, in a single write.
+    pub fn append(&self, items: &[AnalyzedNotification], now: i64) -> Result<()> {
+        if !self.enabled() || items.is_empty() {
+            return Ok(());
+        }
+        let mut lines = Vec::new();
+        for item in items {
+            serde_json::to_writer(&mut lines, &AnalysisLogEntry::new(item, now))?;
+            lines.push(b'\n');
+        }
+
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|err| anyhow!("analysis log lock error: {err}"))?;
+        self.store.append(ConfigFile::AnalysisLog, &lines)
+    }
+}
+
+impl Maintainable for AnalysisLog {
+    fn store_name(&self) -> &'static str {
+        "analysis_log"
+    }
+
+    fn default_limit(&self) -> StoreLimit {
+        StoreLimit {
+            max_age_days: None,
+            max_bytes: Some(ANALYSIS_LOG_MAX_BYTES),
+        }
+    }
+
+    fn size_bytes(&self) -> u64 {
+        file_size(&self.store.path(ConfigFile::AnalysisLog))
+    }
+
+    /// Drops lines logged before `limit.max_age_days`, then the oldest ones
+    /// until the file fits in `limit.max_bytes`.
+    fn prune(&self, limit: StoreLimit, today: NaiveDate) -> Result<usize> {
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|err| anyhow!("analysis log lock error: {err}"))?;
+        let Some(content) = self.store.read(ConfigFile::AnalysisLog) else {
+            return Ok(0);
+        };
+        let mut lines: Vec<&str> = content.lines().collect();
+        let before = lines.len();
+
+        if let Some(cutoff) = limit
+            .max_age_days
+            .and_then(|age| today.checked_sub_days(Days::new(age)))
+        {
+            lines.retain(|line| {
+                serde_json::from_str::<AnalysisLogEntry>(line).is_ok_and(|entry| {
+                    DateTime::from_timestamp(entry.logged_at, 0)
+                        .is_some_and(|at| at.with_timezone(&Local).date_naive() >= cutoff)
+                })
+            });
+        }
+        if let Some(max_bytes) = limit.max_bytes {
+            let mut size: u64 = lines.iter().map(|line| line.len() as u64 + 1).sum();
+            let mut drop = 0;
+            while size > max_bytes && drop < lines.len() {
+                size -= lines[drop].len() as u64 + 1;
+                drop += 1;
+            }
+            lines.drain(..drop);
+        }
+
+        let pruned = before - lines.len();
+        if pruned > 0 {
+            let mut kept = lines.join("\n");
+            if !kept.is_empty() {
+                kept.push('\n');
+            }
+            self.store.write(ConfigFile::AnalysisLog, kept.as_bytes())?;
+        }
+        Ok(pruned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::NaiveDate;
+
+    use super::{AnalysisLog, AnalysisLogEntry};
+    use crate::config_store::{ConfigFile, ConfigStore};
+    use crate::models::{AnalyzedNotification, UrgencyLevel};
+    use crate::storage::{Maintainable, StoreLimit};
+
+    fn store(name: &str) -> ConfigStore {
+        let dir = std::env::temp_dir().join(format!(
+            "notify-analysis-log-test-{}-{name}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        ConfigStore::new(dir)
+    }
+
+    fn item(id: i64) -> AnalyzedNotification {
+        AnalyzedNotification {
+            id,
+            title: "本番DB停止".to_string(),
+            bundle_id: "com.example.app".to_string(),
+            app_name: "Example".to_string(),
+            urgency: UrgencyLevel::High,
+            confidence: 0.9,
+            ..Default::default()
+        }
+    }
+
+    fn entries(store: &ConfigStore) -> Vec<AnalysisLogEntry> {
+        store
+            .read(ConfigFile::AnalysisLog)
+            .unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("every line is whole JSON"))
+            .collect()
+    }
+
+    #[test]
+    fn concurrent_appends_keep_every_line_whole() {
+        let store = store("concurrent");
+        let log = Arc::new(AnalysisLog::new(&store, true));
+        let threads: Vec<_> = (0..8)
+            .map(|thread| {
+                let log = Arc::clone(&log);
+                std::thread::spawn(move || {
+                    for batch in 0..50 {
+                        let base = thread * 1000 + batch * 10;
+                        let items: Vec<_> = (base..base + 3).map(item).collect();
+                        log.append(&items, 1_700_000_000).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let logged = entries(&store);
+        assert_eq!(logged.len(), 8 * 50 * 3);
+        assert!(logged.iter().all(|entry| entry.confidence == 0.9));
+    }
+
+    #[test]
+    fn disabled_log_writes_nothing() {
+        let store = store("disabled");
+        let log = AnalysisLog::new(&store, false);
+        log.append(&[item(1)], 1_700_000_000).unwrap();
+        assert!(store.read(ConfigFile::AnalysisLog).is_none());
+
+        log.set_enabled(true);
+        log.append(&[item(1)], 1_700_000_000).unwrap();
+        assert_eq!(entries(&store).len(), 1);
+    }
+
+    #[test]
+    fn detached_log_leaves_the_real_file_alone() {
+        let store = store("detached");
+        AnalysisLog::new(&store, true)
+            .append(&[item(1)], 1_700_000_000)
+            .unwrap();
+
+        let log = AnalysisLog::new(&ConfigStore::detached(store.dir()), true);
+        log.append(&[item(2)], 1_700_000_000).unwrap();
+        let limit = StoreLimit {
+            max_age_days: None,
+            max_bytes: Some(0),
+        };
+        let today = NaiveDate::from_ymd_opt(2030, 1, 1).unwrap();
+        assert_eq!(log.prune(limit, today).unwrap(), 0);
+
+        let ids: Vec<i64> = entries(&store).iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, vec![1]);
+    }
+
+    #[test]
+    fn prune_drops_oldest_lines_first() {
+        let store = store("prune");
+        let log = AnalysisLog::new(&store, true);
+        for id in 0..10 {
+            log.append(&[item(id)], 1_700_000_000 + id * 86_400)
+                .unwrap();
+        }
+        let line_len = log.size_bytes() / 10;
+        let today = NaiveDate::from_ymd_opt(2030, 1, 1).unwrap();
+
+        let pruned = log
+            .prune(
+                StoreLimit {
+                    max_age_days: None,
+                    max_bytes: Some(line_len * 4),
+                },
+                today,
+            )
+            .unwrap();
+
+        assert_eq!(pruned, 6);
+        let ids: Vec<i64> = entries(&store).iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, vec![6, 7, 8, 9]);
+    }
+}
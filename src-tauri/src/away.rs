@@ -0,0 +1,243 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+
+/// Idle time that counts as away when `AwaySettings::idle_secs` is unset.
+pub const DEFAULT_AWAY_IDLE_SECS: u64 = 30 * 60;
+/// Shortest accepted away threshold, so a coffee-length lock never counts.
+pub const MIN_AWAY_IDLE_SECS: u64 = 5 * 60;
+const QUIET_HOURS_FORMAT: &str = "%H:%M";
+
+/// The "what did I miss" catch-up run on return from a long idle or lock.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AwaySettings {
+    pub enabled: bool,
+    /// Idle (or locked) seconds that count as away. Unset uses
+    /// `DEFAULT_AWAY_IDLE_SECS`.
+    pub idle_secs: Option<u64>,
+    /// Local `HH:MM` range in which returning shows nothing. Wraps past
+    /// midnight when the end is before the start. Unset has no quiet hours.
+    pub quiet_hours: Option<QuietHours>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+impl AwaySettings {
+    pub fn threshold_secs(&self) -> u64 {
+        self.idle_secs.unwrap_or(DEFAULT_AWAY_IDLE_SECS)
+    }
+
+    /// Rejects short thresholds and quiet hours that are not `HH:MM`, and
+    /// normalizes the times (`9:00` → `09:00`).
+    pub fn validated(mut self) -> Result<Self> {
+        if let Some(secs) = self.idle_secs {
+            if secs < MIN_AWAY_IDLE_SECS {
+                return Err(anyhow!(
+                    "away threshold must be at least {MIN_AWAY_IDLE_SECS} seconds"
+                ));
+            }
+        }
+        if let Some(quiet) = &mut self.quiet_hours {
+            quiet.start = format_quiet_time(parse_quiet_time(&quiet.start)?);
+            quiet.end = format_quiet_time(parse_quiet_time(&quiet.end)?);
+        }
+        Ok(self)
+    }
+
+    /// Whether `time` falls in the quiet hours. Unparseable hours (from a
+    /// hand-edited file) count as none.
+    pub fn is_quiet(&self, time: NaiveTime) -> bool {
+        let Some(quiet) = &self.quiet_hours else {
+            return false;
+        };
+        let (Ok(start), Ok(end)) = (parse_quiet_time(&quiet.start), parse_quiet_time(&quiet.end))
+        else {
+            return false;
+        };
+        if start <= end {
+            start <= time && time < end
+        } else {
+            time >= start || time < end
+        }
+    }
+}
+
+fn parse_quiet_time(value: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), QUIET_HOURS_FORMAT)
+        .map_err(|_| anyhow!("quiet hours must be HH:MM, got `{value}`"))
+}
+
+fn format_quiet_time(time: NaiveTime) -> String {
+    time.format(QUIET_HOURS_FORMAT).to_string()
+}
+
+/// Unix times the user was away, from the start of the idle stretch to the
+/// first input after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AwayInterval {
+    pub since: i64,
+    pub until: i64,
+}
+
+/// Turns idle readings into away intervals. Arms once idle time reaches the
+/// threshold and fires on the first reading below it, then waits for another
+/// full threshold of idle before it can fire again. Locking the screen keeps
+/// the idle counter running, so a lock counts as idle.
+#[derive(Debug, Default)]
+pub struct AwayDetector {
+    away_since: Option<i64>,
+}
+
+impl AwayDetector {
+    /// Feeds the idle reading taken at `now`. A reading that could not be
+    /// taken changes nothing.
+    pub fn observe(
+        &mut self,
+        idle_secs: Option<u64>,
+        threshold_secs: u64,
+        now: i64,
+    ) -> Option<AwayInterval> {
+        let idle = idle_secs?;
+        let idle_start = now - idle as i64;
+        if idle >= threshold_secs {
+            // The earliest start seen, in case a reading was cut short.
+            self.away_since = Some(self.away_since.map_or(idle_start, |s| s.min(idle_start)));
+            return None;
+        }
+        let since = self.away_since.take()?;
+        Some(AwayInterval {
+            since,
+            until: idle_start,
+        })
+    }
+
+    /// Forgets a stretch in progress, e.g. when the mode is turned off.
+    pub fn reset(&mut self) {
+        self.away_since = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveTime;
+
+    use super::{AwayDetector, AwayInterval, AwaySettings, QuietHours};
+    use crate::idle::IdleSource;
+
+    struct MockIdle(Option<u64>);
+
+    impl IdleSource for MockIdle {
+        fn idle_secs(&self) -> Option<u64> {
+            self.0
+        }
+    }
+
+    /// Polls `detector` with one reading per `(now, idle)` step and returns
+    /// what each poll fired.
+    fn run(detector: &mut AwayDetector, steps: &[(i64, Option<u64>)]) -> Vec<Option<AwayInterval>> {
+        steps
+            .iter()
+            .map(|&(now, idle)| detector.observe(MockIdle(idle).idle_secs(), 600, now))
+            .collect()
+    }
+
+    #[test]
+    fn fires_once_on_return_from_a_long_idle_and_rearms() {
+        let mut detector = AwayDetector::default();
+        let fired = run(
+            &mut detector,
+            &[
+                (1_000, Some(300)),
+                (1_600, Some(900)),
+                // Unreadable idle time keeps the stretch open.
+                (2_000, None),
+                (2_400, Some(1_700)),
+                (2_410, Some(3)),
+                (2_420, Some(13)),
+            ],
+        );
+        assert_eq!(
+            fired,
+            vec![
+                None,
+                None,
+                None,
+                None,
+                Some(AwayInterval {
+                    since: 700,
+                    until: 2_407
+                }),
+                None,
+            ]
+        );
+
+        // Another full threshold is needed before the next one.
+        let fired = run(&mut detector, &[(3_000, Some(500)), (3_010, Some(1))]);
+        assert_eq!(fired, vec![None, None]);
+        let fired = run(&mut detector, &[(4_000, Some(600)), (4_010, Some(0))]);
+        assert_eq!(
+            fired,
+            vec![
+                None,
+                Some(AwayInterval {
+                    since: 3_400,
+                    until: 4_010
+                })
+            ]
+        );
+    }
+
+    #[test]
+    fn short_lock_cycles_never_fire() {
+        let mut detector = AwayDetector::default();
+        let steps: Vec<_> = (0..60)
+            .map(|i| (i * 300, Some(if i % 2 == 0 { 590 } else { 2 })))
+            .collect();
+        assert!(run(&mut detector, &steps).iter().all(Option::is_none));
+
+        detector.observe(Some(900), 600, 10_000);
+        detector.reset();
+        assert_eq!(detector.observe(Some(0), 600, 10_010), None);
+    }
+
+    #[test]
+    fn quiet_hours_wrap_midnight_and_are_validated() {
+        let at = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        let settings = AwaySettings {
+            enabled: true,
+            idle_secs: None,
+            quiet_hours: Some(QuietHours {
+                start: "22:00".to_string(),
+                end: "7:30".to_string(),
+            }),
+        }
+        .validated()
+        .unwrap();
+        assert_eq!(settings.quiet_hours.as_ref().unwrap().end, "07:30");
+        assert!(settings.is_quiet(at(23, 0)));
+        assert!(settings.is_quiet(at(7, 29)));
+        assert!(!settings.is_quiet(at(7, 30)));
+        assert!(!settings.is_quiet(at(12, 0)));
+        assert!(!AwaySettings::default().is_quiet(at(23, 0)));
+
+        let short = AwaySettings {
+            idle_secs: Some(60),
+            ..AwaySettings::default()
+        };
+        assert!(short.validated().is_err());
+        let bad = AwaySettings {
+            quiet_hours: Some(QuietHours {
+                start: "25:00".to_string(),
+                end: "07:00".to_string(),
+            }),
+            ..AwaySettings::default()
+        };
+        assert!(bad.validated().is_err());
+    }
+}
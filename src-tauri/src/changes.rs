@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde::Serialize;
+
+/// Per-notification changes kept for `get_changes_since`. A mutation touching
+/// more items than this (a large clear) leaves only a full resync possible.
+pub const CHANGE_JOURNAL_CAPACITY: usize = 2000;
+
+/// The collected state's revision, readable without the orchestrator lock
+/// because events are emitted both with and without it held.
+#[derive(Debug, Clone, Default)]
+pub struct RevisionCounter(Arc<AtomicU64>);
+
+impl RevisionCounter {
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn bump(&self) -> u64 {
+        self.0.fetch_add(1, Ordering::SeqCst) + 1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChangeKind {
+    Added,
+    Removed,
+    Updated,
+}
+
+/// Notification ids changed after the revision a client last saw.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeSet {
+    pub revision: u64,
+    /// The journal no longer covers the client's revision; refetch
+    /// everything. The id lists are empty.
+    pub resync_required: bool,
+    pub added: Vec<i64>,
+    pub removed: Vec<i64>,
+    pub updated: Vec<i64>,
+}
+
+/// Bounded journal of changes to the collected notifications. Changes are
+/// found by diffing fingerprints of the whole collection, so every mutation
+/// is covered as long as it is followed by [`ChangeJournal::record`].
+#[derive(Debug)]
+pub struct ChangeJournal {
+    revision: RevisionCounter,
+    entries: VecDeque<(u64, i64, ChangeKind)>,
+    capacity: usize,
+    /// Every change after this revision is still in `entries`.
+    floor: u64,
+    /// Fingerprint of each item as of the current revision.
+    seen: HashMap<i64, u64>,
+}
+
+impl Default for ChangeJournal {
+    fn default() -> Self {
+        Self::new(CHANGE_JOURNAL_CAPACITY)
+    }
+}
+
+impl ChangeJournal {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            revision: RevisionCounter::default(),
+            entries: VecDeque::new(),
+            capacity,
+            floor: 0,
+            seen: HashMap::new(),
+        }
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision.get()
+    }
+
+    pub fn revision_counter(&self) -> RevisionCounter {
+        self.revision.clone()
+    }
+
+    /// Compares `items` (id and fingerprint) with the state at the current
+    /// revision and, if anything differs, journals it under a new revision.
+    pub fn record(&mut self, items: impl IntoIterator<Item = (i64, u64)>) -> bool {
+        let current: HashMap<i64, u64> = items.into_iter().collect();
+        let mut changes: Vec<(i64, ChangeKind)> = current
+            .iter()
+            .filter_map(|(&id, fingerprint)| match self.seen.get(&id) {
+                None => Some((id, ChangeKind::Added)),
+                Some(seen) if seen != fingerprint => Some((id, ChangeKind::Updated)),
+                Some(_) => None,
+            })
+            .chain(
+                self.seen
+                    .keys()
+                    .filter(|id| !current.contains_key(id))
+                    .map(|&id| (id, ChangeKind::Removed)),
+            )
+            .collect();
+        if changes.is_empty() {
+            return false;
+        }
+        changes.sort_unstable_by_key(|&(id, _)| id);
+
+        let revision = self.revision.bump();
+        self.entries
+            .extend(changes.into_iter().map(|(id, kind)| (revision, id, kind)));
+        while self.entries.len() > self.capacity {
+            if let Some((evicted, _, _)) = self.entries.pop_front() {
+                self.floor = evicted;
+            }
+        }
+        self.seen = current;
+        true
+    }
+
+    /// Net changes after `revision`: an item added and removed again is left
+    /// out, and one removed and added back counts as updated.
+    pub fn changes_since(&self, revision: u64) -> ChangeSet {
+        let current = self.revision();
+        if revision > current || revision < self.floor {
+            return ChangeSet {
+                revision: current,
+                resync_required: true,
+                ..ChangeSet::default()
+            };
+        }
+
+        let mut net: BTreeMap<i64, (ChangeKind, ChangeKind)> = BTreeMap::new();
+        for &(_, id, kind) in self.entries.iter().filter(|(r, _, _)| *r > revision) {
+            net.entry(id)
+                .and_modify(|(_, last)| *last = kind)
+                .or_insert((kind, kind));
+        }
+        let mut changes = ChangeSet {
+            revision: current,
+            ..ChangeSet::default()
+        };
+        for (id, (first, last)) in net {
+            match (first, last) {
+                (ChangeKind::Added, ChangeKind::Removed) => {}
+                (ChangeKind::Added, _) => changes.added.push(id),
+                (_, ChangeKind::Removed) => changes.removed.push(id),
+                _ => changes.updated.push(id),
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChangeJournal, ChangeSet};
+
+    fn delta(revision: u64, added: &[i64], removed: &[i64], updated: &[i64]) -> ChangeSet {
+        ChangeSet {
+            revision,
+            resync_required: false,
+            added: added.to_vec(),
+            removed: removed.to_vec(),
+            updated: updated.to_vec(),
+        }
+    }
+
+    #[test]
+    fn changes_are_netted_per_item_since_the_given_revision() {
+        let mut journal = ChangeJournal::new(100);
+        assert!(journal.record([(1, 10), (2, 20)]));
+        assert!(!journal.record([(2, 20), (1, 10)]));
+        assert_eq!(journal.revision(), 1);
+
+        assert!(journal.record([(1, 11), (3, 30)]));
+        assert!(journal.record([(1, 11), (2, 20), (4, 40)]));
+        assert!(journal.record([(1, 11), (2, 20)]));
+
+        assert_eq!(journal.changes_since(4), delta(4, &[], &[], &[]));
+        assert_eq!(journal.changes_since(3), delta(4, &[], &[4], &[]));
+        // 3 and 4 came and went; 2 went and came back.
+        assert_eq!(journal.changes_since(1), delta(4, &[], &[], &[1, 2]));
+        assert_eq!(journal.changes_since(0), delta(4, &[1, 2], &[], &[]));
+    }
+
+    #[test]
+    fn revisions_the_journal_no_longer_covers_require_a_resync() {
+        let mut journal = ChangeJournal::new(3);
+        for fingerprint in 0..5 {
+            journal.record([(1, fingerprint), (2, 0)]);
+        }
+        // Revision 1 added both items and each later one updated item 1, so
+        // the ring now holds revisions 3 to 5: a client at 2 is still covered.
+        assert_eq!(journal.revision(), 5);
+        assert!(journal.changes_since(0).resync_required);
+        assert!(journal.changes_since(1).resync_required);
+        assert_eq!(journal.changes_since(2), delta(5, &[], &[], &[1]));
+        assert_eq!(journal.changes_since(4), delta(5, &[], &[], &[1]));
+
+        // A client ahead of the journal (from before a restart) resyncs too.
+        let ahead = journal.changes_since(9);
+        assert!(ahead.resync_required && ahead.revision == 5);
+        assert!(ahead.added.is_empty() && ahead.updated.is_empty());
+    }
+
+    #[test]
+    fn a_change_larger_than_the_journal_leaves_only_the_current_revision() {
+        let mut journal = ChangeJournal::new(3);
+        journal.record((1..=2).map(|id| (id, 0)));
+        journal.record(std::iter::empty());
+        journal.record((1..=5).map(|id| (id, 0)));
+
+        assert_eq!(journal.revision(), 3);
+        assert!(journal.changes_since(2).resync_required);
+        assert_eq!(journal.changes_since(3), delta(3, &[], &[], &[]));
+    }
+}
@@ -1,16 +1,65 @@
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::anyhow;
+use chrono::Local;
 use serde::Serialize;
 use tauri::{AppHandle, Manager, State};
 
-use crate::emit_notifications_updated;
-use crate::llm::SharedLlm;
-use crate::models::UiNotificationGroup;
-use crate::orchestrator::{SharedOrchestrator, MAX_DUMMY_INSERT_COUNT};
+use crate::away::AwaySettings;
+use crate::changes::ChangeSet;
+use crate::config_store::write_atomic;
+use crate::db::{BlobStats, SchemaInfo, StoreHealth};
+use crate::diagnostics::{write_bundle, DiagnosticBundle};
+use crate::dialog::MessageTemplates;
+use crate::digest::DigestFormat;
+use crate::focus::StaleAssertionReport;
+use crate::http::NetworkSettings;
+use crate::language::OutputLanguage;
+use crate::llm::{
+    AnalysisBudget, AnalysisPromptTemplate, LatencyStats, LlmMetricsSnapshot, SharedLlm,
+    OLLAMA_BASE_URL,
+};
+use crate::models::{
+    AccountScope, AppDelivery, CatchUpResult, CollectionSummary, FocusStatus, GroupActionResult,
+    GroupState, ReasonCode, RecentlyCleared, RuleInfo, SessionSummary, SummaryField, TriageStep,
+    UiAgendaItem, UiAppNode, UiNotification, UiNotificationGroups, UiNotificationInput,
+    UiTimeBucket, UnconfiguredApp, UrgencyChange, UrgencyLevel,
+};
+use crate::noise::{noise_report_markdown, NoiseReport};
+use crate::notification_center::{clear_with_system, SystemEventsUi};
+use crate::onboarding::{OnboardingState, OnboardingStep};
+use crate::orchestrator::{
+    analyze_notifications_with_budget, app_name_from_bundle, rule_precedence, ActiveThresholds,
+    ClearFilter, GroupAction, SharedOrchestrator, MAX_BLOB_STATS_ROWS, MAX_DUMMY_INSERT_COUNT,
+    POLL_INTERVAL_SECONDS,
+};
+use crate::prompt_templates::{PromptTemplate, PROMPT_TEMPLATES};
+use crate::resources::{estimate, ResourceEstimate, ResourceInputs};
+use crate::sources::SourceConfig;
+use crate::storage::{StorageSettings, StoreUsage};
+use crate::summary::{build_session_summary_prompt, enforce_summary_tiers, SummarySource};
+use crate::usage::{LlmDailyCost, TokenPrice};
+use crate::{
+    apply_analysis_enabled, emit_notifications_updated, emit_to_window, spawn_reanalysis,
+    spawn_summary_stream,
+};
 
 #[derive(Serialize)]
 pub struct AppPromptEntry {
     #[serde(rename = "bundleId")]
     pub bundle_id: String,
     pub context: String,
+    pub delivery: AppDelivery,
+}
+
+/// Items analyzed between `catch-up-progress` events.
+const CATCH_UP_PROGRESS_CHUNK: usize = 5;
+
+#[derive(Clone, Serialize)]
+pub struct CatchUpProgress {
+    pub done: usize,
+    pub total: usize,
 }
 
 #[derive(Serialize)]
@@ -18,209 +67,1561 @@ pub struct LlmSettingsResponse {
     #[serde(rename = "selectedModel")]
     pub selected_model: String,
     pub models: Vec<String>,
+    #[serde(rename = "analysisEnabled")]
+    pub analysis_enabled: bool,
+    #[serde(rename = "outputLanguage")]
+    pub output_language: OutputLanguage,
+    #[serde(rename = "modelLifecycle")]
+    pub model_lifecycle: bool,
+    #[serde(rename = "sessionKeepAliveMinutes")]
+    pub session_keep_alive_minutes: u64,
 }
 
 #[tauri::command]
 pub fn get_notification_groups(
+    reason_code: Option<ReasonCode>,
     state: State<'_, SharedOrchestrator>,
-) -> Result<Vec<UiNotificationGroup>, String> {
+) -> Result<UiNotificationGroups, String> {
     let guard = state
         .0
         .lock()
         .map_err(|err| format!("state lock error: {err}"))?;
-    Ok(guard.notification_groups())
+    Ok(UiNotificationGroups {
+        revision: guard.revision(),
+        groups: guard.notification_groups_with_reason(reason_code),
+    })
 }
 
+/// Ids added, removed and updated after `revision`, for a view that already
+/// holds the groups of that revision.
 #[tauri::command]
-pub fn clear_notification(
+pub fn get_changes_since(
+    revision: u64,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<ChangeSet, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.changes_since(revision))
+}
+
+#[tauri::command]
+pub fn get_notification_tree(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<Vec<UiAppNode>, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.notification_tree())
+}
+
+#[tauri::command]
+pub fn get_notifications_by_time_bucket(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<Vec<UiTimeBucket>, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.time_buckets(Local::now().date_naive()))
+}
+
+#[tauri::command]
+pub fn get_agenda(state: State<'_, SharedOrchestrator>) -> Result<Vec<UiAgendaItem>, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.agenda())
+}
+
+/// The model's unparsed reply for a notification; `None` unless
+/// `set_keep_raw_responses` was on when it was analyzed.
+#[tauri::command]
+pub fn get_raw_response(
     id: i64,
     state: State<'_, SharedOrchestrator>,
-    app: AppHandle,
-) -> Result<bool, String> {
+) -> Result<Option<String>, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.raw_response(id))
+}
+
+#[tauri::command]
+pub fn get_rule_precedence() -> Vec<RuleInfo> {
+    rule_precedence()
+}
+
+#[tauri::command]
+pub fn get_escalation_keywords(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<Vec<String>, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.escalation_keywords())
+}
+
+#[tauri::command]
+pub fn set_escalation_keywords(
+    keywords: Vec<String>,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
     let mut guard = state
         .0
         .lock()
         .map_err(|err| format!("state lock error: {err}"))?;
-    let cleared = guard.clear_notification(id);
-    if cleared {
-        let counts = guard.urgency_counts();
-        emit_notifications_updated(&app, counts);
-    }
-    Ok(cleared)
+    guard
+        .set_escalation_keywords(keywords)
+        .map_err(|err| format!("failed to save escalation keywords: {err}"))
 }
 
 #[tauri::command]
-pub fn clear_app_notifications(
-    bundle_id: String,
+pub fn get_store_health(state: State<'_, SharedOrchestrator>) -> Result<StoreHealth, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.store_health())
+}
+
+#[tauri::command]
+pub fn get_schema_info(state: State<'_, SharedOrchestrator>) -> Result<SchemaInfo, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.schema_info())
+}
+
+/// Debug-only: per-app blob sizes and parse rates over the newest `rows` rows.
+#[tauri::command]
+pub fn get_blob_stats(
+    rows: Option<i64>,
     state: State<'_, SharedOrchestrator>,
-    app: AppHandle,
-) -> Result<usize, String> {
+) -> Result<Vec<BlobStats>, String> {
+    let rows = rows.unwrap_or(500).clamp(1, MAX_BLOB_STATS_ROWS);
     let mut guard = state
         .0
         .lock()
         .map_err(|err| format!("state lock error: {err}"))?;
-    let cleared = guard.clear_app_notifications(&bundle_id);
-    if cleared > 0 {
-        let counts = guard.urgency_counts();
-        emit_notifications_updated(&app, counts);
-    }
-    Ok(cleared)
+    guard
+        .blob_stats(rows)
+        .map_err(|err| format!("failed to read blob stats: {err}"))
 }
 
 #[tauri::command]
-pub fn clear_all_notifications(
+pub fn get_stale_assertions(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<StaleAssertionReport, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.stale_assertions(chrono::Utc::now().timestamp()))
+}
+
+#[tauri::command]
+pub fn set_stale_assertion_policy(
+    threshold_secs: Option<i64>,
+    treat_as_inactive: bool,
     state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_stale_assertion_policy(threshold_secs, treat_as_inactive)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn get_focus_status(state: State<'_, SharedOrchestrator>) -> Result<FocusStatus, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.focus_status())
+}
+
+/// Reads and analyzes everything delivered in the last `since_minutes`
+/// without touching focus sessions. Runs off the main thread and reports
+/// `catch-up-progress` while the LLM works through the backlog.
+#[tauri::command]
+pub async fn catch_me_up(
     app: AppHandle,
-) -> Result<usize, String> {
+    since_minutes: u64,
+    add_to_collected: Option<bool>,
+    state: State<'_, SharedOrchestrator>,
+    llm: State<'_, SharedLlm>,
+) -> Result<CatchUpResult, String> {
+    let add_to_collected = add_to_collected.unwrap_or(false);
+    let routed = {
+        let mut guard = state
+            .0
+            .lock()
+            .map_err(|err| format!("state lock error: {err}"))?;
+        guard
+            .catch_up_read(since_minutes)
+            .map_err(|err| format!("failed to read notifications: {err:#}"))?
+    };
+
+    let total = routed.escalated.len() + routed.pending.len();
+    let mut analyzed = routed.escalated;
+    // The chunks are one batch for the analysis budget: a chunk that starts
+    // late gets what is left of it.
+    let budget = llm.0.analysis_budget();
+    let deadline = Instant::now() + budget.batch;
+    for chunk in routed.pending.chunks(CATCH_UP_PROGRESS_CHUNK) {
+        let remaining = AnalysisBudget {
+            batch: deadline.saturating_duration_since(Instant::now()),
+            ..budget
+        };
+        analyzed.extend(analyze_notifications_with_budget(&llm.0, chunk.to_vec(), remaining).await);
+        let progress = CatchUpProgress {
+            done: analyzed.len(),
+            total,
+        };
+        emit_to_window(&app, "catch-up-progress", progress);
+    }
+
     let mut guard = state
         .0
         .lock()
         .map_err(|err| format!("state lock error: {err}"))?;
-    let cleared = guard.clear_all();
-    if cleared > 0 {
+    let result = guard.catch_up_finish(analyzed, add_to_collected);
+    if add_to_collected {
         let counts = guard.urgency_counts();
         emit_notifications_updated(&app, counts);
     }
-    Ok(cleared)
+    Ok(result)
 }
 
+/// Runs the session summary over a client-supplied list, for iterating on the
+/// prompt and model with fixed inputs. `collected` is left alone.
 #[tauri::command]
-pub fn inject_dummy_notifications(
-    count: Option<usize>,
+pub async fn summarize_given(
+    notifications: Vec<UiNotificationInput>,
+    llm: State<'_, SharedLlm>,
+) -> Result<String, String> {
+    if notifications.is_empty() {
+        return Err("no notifications to summarize".to_string());
+    }
+    let source = SummarySource::from_inputs(&notifications);
+    let prompt = build_session_summary_prompt(&source, llm.0.output_language(), Local::now());
+    let text = llm
+        .0
+        .generate_summary(&prompt, &mut |_: &str| {})
+        .await
+        .map_err(|err| format!("failed to generate summary: {err:#}"))?;
+    Ok(enforce_summary_tiers(&text, &source))
+}
+
+#[tauri::command]
+pub fn get_digest(
+    format: Option<DigestFormat>,
     state: State<'_, SharedOrchestrator>,
-    app: AppHandle,
-) -> Result<usize, String> {
-    let insert_count = count.unwrap_or(8).clamp(1, MAX_DUMMY_INSERT_COUNT);
-    let mut guard = state
+) -> Result<String, String> {
+    let guard = state
         .0
         .lock()
         .map_err(|err| format!("state lock error: {err}"))?;
-    let inserted = guard.inject_dummy_notifications(insert_count);
-    let counts = guard.urgency_counts();
-    emit_notifications_updated(&app, counts);
-    Ok(inserted)
+    Ok(guard.digest(format.unwrap_or_default()))
 }
 
 #[tauri::command]
-pub fn get_app_prompts(
+pub fn get_last_summary(
     state: State<'_, SharedOrchestrator>,
-) -> Result<Vec<AppPromptEntry>, String> {
+) -> Result<Option<SessionSummary>, String> {
     let guard = state
         .0
         .lock()
         .map_err(|err| format!("state lock error: {err}"))?;
-    let entries = guard
-        .list_app_prompts()
-        .into_iter()
-        .map(|(bundle_id, context)| AppPromptEntry { bundle_id, context })
-        .collect();
-    Ok(entries)
+    Ok(guard.last_summary())
 }
 
 #[tauri::command]
-pub fn set_app_prompt(
+pub fn get_summary_history(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<Vec<SessionSummary>, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.summary_history())
+}
+
+#[tauri::command]
+pub fn get_session_notifications(
+    session_id: u64,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<Vec<UiNotification>, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.session_notifications(session_id))
+}
+
+/// One app's collected notifications, newest first, for a detail view that
+/// does not need the other apps' groups.
+#[tauri::command]
+pub fn get_app_notifications(
     bundle_id: String,
-    context: String,
     state: State<'_, SharedOrchestrator>,
-) -> Result<(), String> {
-    let mut guard = state
+) -> Result<Vec<UiNotification>, String> {
+    let guard = state
         .0
         .lock()
         .map_err(|err| format!("state lock error: {err}"))?;
-    guard
-        .set_app_prompt(bundle_id, context)
-        .map_err(|err| format!("failed to save app prompt: {err}"))
+    Ok(guard.app_notifications(&bundle_id))
 }
 
 #[tauri::command]
-pub fn open_app(bundle_id: String) -> Result<(), String> {
-    log::info!("open_app called with bundle_id: {bundle_id}");
-    std::process::Command::new("open")
-        .arg("-b")
-        .arg(&bundle_id)
-        .spawn()
-        .map_err(|err| format!("failed to open app {bundle_id}: {err}"))?;
-    Ok(())
+pub fn regenerate_summary(
+    app: AppHandle,
+    state: State<'_, SharedOrchestrator>,
+    llm: State<'_, SharedLlm>,
+) -> Result<SessionSummary, String> {
+    let summary = {
+        let mut guard = state
+            .0
+            .lock()
+            .map_err(|err| format!("state lock error: {err}"))?;
+        guard.regenerate_summary()
+    };
+    // The count-based summary is shown right away; the LLM version streams in.
+    spawn_summary_stream(app, state.0.clone(), llm.0.clone());
+    Ok(summary)
 }
 
 #[tauri::command]
-pub fn hide_main_window(app: AppHandle) -> Result<(), String> {
-    let window = app
-        .get_webview_window("main")
-        .ok_or_else(|| "main window not found".to_string())?;
-    window
-        .hide()
-        .map_err(|err| format!("failed to hide main window: {err}"))
+pub fn get_app_order(state: State<'_, SharedOrchestrator>) -> Result<Vec<String>, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.app_order())
 }
 
 #[tauri::command]
-pub fn delete_app_prompt(
-    bundle_id: String,
+pub fn set_app_order(
+    bundle_ids: Vec<String>,
     state: State<'_, SharedOrchestrator>,
-) -> Result<bool, String> {
+    app: AppHandle,
+) -> Result<(), String> {
     let mut guard = state
         .0
         .lock()
         .map_err(|err| format!("state lock error: {err}"))?;
     guard
-        .delete_app_prompt(&bundle_id)
-        .map_err(|err| format!("failed to delete app prompt: {err}"))
+        .set_app_order(bundle_ids)
+        .map_err(|err| format!("failed to save app order: {err}"))?;
+    let counts = guard.urgency_counts();
+    emit_notifications_updated(&app, counts);
+    Ok(())
 }
 
 #[tauri::command]
-pub fn get_ignored_apps(state: State<'_, SharedOrchestrator>) -> Result<Vec<String>, String> {
+pub fn get_collection_summary(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<CollectionSummary, String> {
     let guard = state
         .0
         .lock()
         .map_err(|err| format!("state lock error: {err}"))?;
-    Ok(guard.list_ignored_apps())
+    Ok(guard.collection_summary())
 }
 
 #[tauri::command]
-pub fn add_ignored_app(
-    bundle_id: String,
+pub fn mark_notifications_read(
+    ids: Vec<i64>,
+    read: bool,
     state: State<'_, SharedOrchestrator>,
-) -> Result<(), String> {
+    app: AppHandle,
+) -> Result<TriageStep<usize>, String> {
     let mut guard = state
         .0
         .lock()
         .map_err(|err| format!("state lock error: {err}"))?;
-    guard
-        .add_ignored_app(bundle_id)
-        .map_err(|err| format!("failed to save ignored app: {err}"))
+    let changed = guard.mark_read(&ids, read);
+    if changed > 0 {
+        let counts = guard.urgency_counts();
+        emit_notifications_updated(&app, counts);
+    }
+    Ok(TriageStep {
+        result: changed,
+        next: guard.triage_followup(ids.last().copied()),
+    })
 }
 
+/// Starts a triage pass (`after_id` omitted) or advances it.
 #[tauri::command]
-pub fn remove_ignored_app(
-    bundle_id: String,
+pub fn triage_next(
+    after_id: Option<i64>,
     state: State<'_, SharedOrchestrator>,
-) -> Result<bool, String> {
+) -> Result<Option<UiNotification>, String> {
     let mut guard = state
         .0
         .lock()
         .map_err(|err| format!("state lock error: {err}"))?;
-    guard
-        .remove_ignored_app(&bundle_id)
-        .map_err(|err| format!("failed to remove ignored app: {err}"))
+    Ok(guard.triage_next(after_id))
 }
 
 #[tauri::command]
-pub fn get_llm_settings(llm: State<'_, SharedLlm>) -> Result<LlmSettingsResponse, String> {
-    let selected_model = llm.0.current_model();
-    let models = llm
+pub fn set_notification_pinned(
+    id: i64,
+    pinned: bool,
+    state: State<'_, SharedOrchestrator>,
+    app: AppHandle,
+) -> Result<bool, String> {
+    let mut guard = state
         .0
-        .list_models()
-        .map_err(|err| format!("failed to list Ollama models: {err}"))?;
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    let changed = guard.set_pinned(id, pinned);
+    if changed {
+        let counts = guard.urgency_counts();
+        emit_notifications_updated(&app, counts);
+    }
+    Ok(changed)
+}
 
-    Ok(LlmSettingsResponse {
-        selected_model,
-        models,
+#[tauri::command]
+pub fn clear_notification(
+    id: i64,
+    state: State<'_, SharedOrchestrator>,
+    app: AppHandle,
+) -> Result<TriageStep<bool>, String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    let cleared = guard.clear_notification(id);
+    if cleared {
+        let counts = guard.urgency_counts();
+        emit_notifications_updated(&app, counts);
+    }
+    Ok(TriageStep {
+        result: cleared,
+        next: guard.triage_followup(Some(id)),
     })
 }
 
+/// With `also_clear_in_system`, the app's alerts are also dismissed in
+/// Notification Center through UI scripting, which needs accessibility
+/// permission. Async so the scripting does not block the main thread.
 #[tauri::command]
-pub fn set_llm_model(model: String, llm: State<'_, SharedLlm>) -> Result<(), String> {
-    llm.0
-        .set_model(model)
+pub async fn clear_app_notifications(
+    bundle_id: String,
+    scope: Option<AccountScope>,
+    also_clear_in_system: Option<bool>,
+    state: State<'_, SharedOrchestrator>,
+    app: AppHandle,
+) -> Result<usize, String> {
+    let clear_local = || {
+        let mut guard = state
+            .0
+            .lock()
+            .map_err(|err| anyhow!("state lock error: {err}"))?;
+        let scope = scope.clone().unwrap_or(AccountScope::All);
+        let cleared = guard.clear_app_notifications(&bundle_id, &scope);
+        if cleared > 0 {
+            let counts = guard.urgency_counts();
+            emit_notifications_updated(&app, counts);
+        }
+        Ok(cleared)
+    };
+    let cleared = if also_clear_in_system.unwrap_or(false) {
+        let app_name = app_name_from_bundle(&bundle_id);
+        clear_with_system(&SystemEventsUi, &app_name, clear_local)
+    } else {
+        clear_local()
+    };
+    cleared.map_err(|err| format!("{err:#}"))
+}
+
+#[tauri::command]
+pub fn clear_where(
+    max_urgency: Option<UrgencyLevel>,
+    older_than_secs: Option<i64>,
+    bundle_id: Option<String>,
+    state: State<'_, SharedOrchestrator>,
+    app: AppHandle,
+) -> Result<usize, String> {
+    let filter = ClearFilter {
+        max_urgency,
+        older_than_secs,
+        bundle_id,
+    };
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    let cleared = guard.clear_where(&filter);
+    if cleared > 0 {
+        let counts = guard.urgency_counts();
+        emit_notifications_updated(&app, counts);
+    }
+    Ok(cleared)
+}
+
+#[tauri::command]
+pub fn group_action(
+    bundle_id: String,
+    scope: Option<AccountScope>,
+    action: String,
+    params: Option<serde_json::Value>,
+    state: State<'_, SharedOrchestrator>,
+    app: AppHandle,
+) -> Result<GroupActionResult, String> {
+    let action = GroupAction::parse(&action, params.as_ref()).map_err(|err| err.to_string())?;
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    let result = guard
+        .group_action(&bundle_id, &scope.unwrap_or(AccountScope::All), action)
+        .map_err(|err| format!("group action failed: {err}"))?;
+    let counts = guard.urgency_counts();
+    emit_notifications_updated(&app, counts);
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn snooze_app_until_focus_end(
+    bundle_id: String,
+    scope: Option<AccountScope>,
+    current_id: Option<i64>,
+    state: State<'_, SharedOrchestrator>,
+    app: AppHandle,
+) -> Result<TriageStep<GroupState>, String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    let result = guard.snooze_app_until_focus_end(&bundle_id, &scope.unwrap_or(AccountScope::All));
+    let counts = guard.urgency_counts();
+    emit_notifications_updated(&app, counts);
+    Ok(TriageStep {
+        result,
+        next: guard.triage_followup(current_id),
+    })
+}
+
+#[tauri::command]
+pub fn clear_all_notifications(
+    state: State<'_, SharedOrchestrator>,
+    app: AppHandle,
+) -> Result<usize, String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    let cleared = guard.clear_all();
+    if cleared > 0 {
+        let counts = guard.urgency_counts();
+        emit_notifications_updated(&app, counts);
+    }
+    Ok(cleared)
+}
+
+#[tauri::command]
+pub fn undo_clear(state: State<'_, SharedOrchestrator>, app: AppHandle) -> Result<usize, String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    let restored = guard.undo_clear();
+    if restored > 0 {
+        let counts = guard.urgency_counts();
+        emit_notifications_updated(&app, counts);
+    }
+    Ok(restored)
+}
+
+/// Urgency changes from the latest `reanalyze_fallbacks` run that upgraded
+/// anything.
+#[tauri::command]
+pub fn get_recent_urgency_changes(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<Vec<UrgencyChange>, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.recent_urgency_changes())
+}
+
+/// Brings back one cleared notification. Returns false once its restore
+/// window has passed.
+#[tauri::command]
+pub fn restore_notification(
+    id: i64,
+    state: State<'_, SharedOrchestrator>,
+    app: AppHandle,
+) -> Result<bool, String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    let restored = guard.restore_notification(id);
+    if restored {
+        let counts = guard.urgency_counts();
+        emit_notifications_updated(&app, counts);
+    }
+    Ok(restored)
+}
+
+#[tauri::command]
+pub fn get_recently_cleared(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<Vec<RecentlyCleared>, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.recently_cleared())
+}
+
+#[tauri::command]
+pub fn set_undo_clear_window(
+    secs: Option<i64>,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_undo_clear_window(secs)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn set_restore_window(
+    secs: Option<i64>,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_restore_window(secs)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn get_message_templates(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<MessageTemplates, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.message_templates())
+}
+
+/// Sets the critical-dialog and focus-end banner templates. Used from the
+/// next dialog or banner on.
+#[tauri::command]
+pub fn set_message_templates(
+    templates: MessageTemplates,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_message_templates(templates)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn get_network_settings(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<NetworkSettings, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.network_settings())
+}
+
+/// Applies proxy and root certificate settings to outgoing HTTP. Invalid
+/// settings are rejected with the cause and the current client is kept.
+#[tauri::command]
+pub fn set_network_settings(
+    network: NetworkSettings,
+    state: State<'_, SharedOrchestrator>,
+    llm: State<'_, SharedLlm>,
+) -> Result<(), String> {
+    llm.0
+        .set_network(&network)
+        .map_err(|err| format!("{err:#}"))?;
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_network_settings(network)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub async fn test_llm_connection(llm: State<'_, SharedLlm>) -> Result<(), String> {
+    llm.0
+        .test_connection()
+        .await
+        .map_err(|err| format!("{err:#}"))
+}
+
+#[tauri::command]
+pub fn set_critical_min_confidence(
+    min: Option<f64>,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_critical_min_confidence(min)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+/// `time` is local `HH:MM`; `None` turns the daily rollup off.
+#[tauri::command]
+pub fn set_daily_rollup_time(
+    time: Option<String>,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_daily_rollup_time(time)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn get_away_settings(state: State<'_, SharedOrchestrator>) -> Result<AwaySettings, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.away_settings())
+}
+
+/// Turns the catch-up on return from being away on or off and sets its idle
+/// threshold and quiet hours.
+#[tauri::command]
+pub fn set_away_settings(
+    settings: AwaySettings,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_away_settings(settings)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn set_defer_analysis_idle_secs(
+    secs: Option<u64>,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_defer_analysis_idle_secs(secs)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn set_max_analysis_per_poll(
+    limit: Option<usize>,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_max_analysis_per_poll(limit)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn set_max_parse_attempts(
+    attempts: Option<u32>,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_max_parse_attempts(attempts)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn set_ignore_interruption_level(
+    ignore: bool,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_ignore_interruption_level(ignore)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn set_incremental_sort(
+    enabled: bool,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_incremental_sort(enabled)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn set_group_by_category(
+    enabled: bool,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_group_by_category(enabled)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn set_group_by_account(
+    enabled: bool,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_group_by_account(enabled)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+/// Merges title and subtitle into one line when neither is longer than
+/// `max_chars`; `None` keeps them apart.
+#[tauri::command]
+pub fn set_title_merge_max_chars(
+    max_chars: Option<usize>,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_title_merge_max_chars(max_chars)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn set_focus_start_lookback(
+    secs: Option<i64>,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_focus_start_lookback(secs)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn get_storage_usage(
+    state: State<'_, SharedOrchestrator>,
+    llm: State<'_, SharedLlm>,
+) -> Result<Vec<StoreUsage>, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.storage_usage(&llm.0))
+}
+
+#[tauri::command]
+pub fn run_maintenance_now(
+    state: State<'_, SharedOrchestrator>,
+    llm: State<'_, SharedLlm>,
+) -> Result<Vec<StoreUsage>, String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.run_maintenance(&llm.0))
+}
+
+#[tauri::command]
+pub fn set_storage_settings(
+    storage: StorageSettings,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_storage_settings(storage)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn inject_dummy_notifications(
+    count: Option<usize>,
+    state: State<'_, SharedOrchestrator>,
+    app: AppHandle,
+) -> Result<usize, String> {
+    let insert_count = count.unwrap_or(8).clamp(1, MAX_DUMMY_INSERT_COUNT);
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    let inserted = guard.inject_dummy_notifications(insert_count);
+    let counts = guard.urgency_counts();
+    emit_notifications_updated(&app, counts);
+    Ok(inserted)
+}
+
+#[tauri::command]
+pub fn get_app_prompts(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<Vec<AppPromptEntry>, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    let entries = guard
+        .list_app_prompts()
+        .into_iter()
+        .map(|(bundle_id, config)| AppPromptEntry {
+            bundle_id,
+            context: config.context,
+            delivery: config.delivery,
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// Collected apps with no prompt context that are not ignored, by
+/// notification count, to suggest which apps to describe next.
+#[tauri::command]
+pub fn get_unconfigured_apps(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<Vec<UnconfiguredApp>, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.unconfigured_apps())
+}
+
+#[tauri::command]
+pub fn set_app_prompt(
+    bundle_id: String,
+    context: String,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_app_prompt(bundle_id, context)
+        .map_err(|err| format!("failed to save app prompt: {err}"))
+}
+
+#[tauri::command]
+pub fn set_app_delivery(
+    bundle_id: String,
+    delivery: AppDelivery,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_app_delivery(bundle_id, delivery)
+        .map_err(|err| format!("failed to save app delivery: {err}"))
+}
+
+/// Sets prompt context for one app, or (without `bundle_id`) for every app,
+/// that is dropped when the focus session ends.
+#[tauri::command]
+pub fn set_session_context(
+    bundle_id: Option<String>,
+    context: String,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_session_context(bundle_id, context)
+        .map_err(|err| err.to_string())
+}
+
+#[tauri::command]
+pub fn clear_session_context(state: State<'_, SharedOrchestrator>) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard.clear_session_context();
+    Ok(())
+}
+
+/// Sets which fields the fallback summary line is built from, in priority
+/// order, for one app or (without `bundle_id`) for every app.
+#[tauri::command]
+pub fn set_summary_fields(
+    bundle_id: Option<String>,
+    fields: Option<Vec<SummaryField>>,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_summary_fields(bundle_id, fields)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn open_app(bundle_id: String) -> Result<(), String> {
+    log::info!("open_app called with bundle_id: {bundle_id}");
+    std::process::Command::new("open")
+        .arg("-b")
+        .arg(&bundle_id)
+        .spawn()
+        .map_err(|err| format!("failed to open app {bundle_id}: {err}"))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn hide_main_window(app: AppHandle) -> Result<(), String> {
+    let window = app
+        .get_webview_window("main")
+        .ok_or_else(|| "main window not found".to_string())?;
+    window
+        .hide()
+        .map_err(|err| format!("failed to hide main window: {err}"))
+}
+
+/// Built-in app contexts that `apply_prompt_templates` can set.
+#[tauri::command]
+pub fn get_prompt_templates() -> Vec<PromptTemplate> {
+    PROMPT_TEMPLATES.to_vec()
+}
+
+/// Sets the built-in context of the given apps, keeping contexts already
+/// set unless `force`. Returns the apps that were set.
+#[tauri::command]
+pub fn apply_prompt_templates(
+    bundle_ids: Vec<String>,
+    force: Option<bool>,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<Vec<String>, String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .apply_prompt_templates(&bundle_ids, force.unwrap_or(false))
+        .map_err(|err| format!("failed to apply prompt templates: {err}"))
+}
+
+#[tauri::command]
+pub fn delete_app_prompt(
+    bundle_id: String,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<bool, String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .delete_app_prompt(&bundle_id)
+        .map_err(|err| format!("failed to delete app prompt: {err}"))
+}
+
+#[tauri::command]
+pub fn get_ignored_apps(state: State<'_, SharedOrchestrator>) -> Result<Vec<String>, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.list_ignored_apps())
+}
+
+#[tauri::command]
+pub fn add_ignored_app(
+    bundle_id: String,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .add_ignored_app(bundle_id)
+        .map_err(|err| format!("failed to save ignored app: {err}"))
+}
+
+#[tauri::command]
+pub fn remove_ignored_app(
+    bundle_id: String,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<bool, String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .remove_ignored_app(&bundle_id)
+        .map_err(|err| format!("failed to remove ignored app: {err}"))
+}
+
+#[tauri::command]
+pub fn get_notification_databases(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<Vec<SourceConfig>, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.notification_databases())
+}
+
+#[tauri::command]
+pub fn add_notification_database(
+    path: String,
+    label: Option<String>,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .add_notification_database(path.trim().into(), label)
+        .map_err(|err| format!("failed to add notification database: {err}"))
+}
+
+#[tauri::command]
+pub fn remove_notification_database(
+    path: String,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<bool, String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .remove_notification_database(Path::new(&path))
+        .map_err(|err| format!("failed to remove notification database: {err}"))
+}
+
+/// Writes a diagnostic bundle for bug reports to `path`.
+#[tauri::command]
+pub fn export_diagnostics(
+    path: String,
+    state: State<'_, SharedOrchestrator>,
+    llm: State<'_, SharedLlm>,
+) -> Result<(), String> {
+    let bundle = {
+        let guard = state
+            .0
+            .lock()
+            .map_err(|err| format!("state lock error: {err}"))?;
+        DiagnosticBundle::build(&guard, &llm.0, chrono::Utc::now().timestamp())
+    };
+    write_bundle(Path::new(&path), &bundle)
+        .map_err(|err| format!("failed to write diagnostics: {err:#}"))
+}
+
+#[tauri::command]
+pub fn get_noise_report(
+    days: u32,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<NoiseReport, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.noise_report(days))
+}
+
+/// Writes the noise report to `path` as a Markdown checklist.
+#[tauri::command]
+pub fn export_noise_report(
+    days: u32,
+    path: String,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let report = {
+        let guard = state
+            .0
+            .lock()
+            .map_err(|err| format!("state lock error: {err}"))?;
+        guard.noise_report(days)
+    };
+    write_atomic(Path::new(&path), noise_report_markdown(&report).as_bytes())
+        .map_err(|err| format!("failed to write noise report: {err:#}"))
+}
+
+#[tauri::command]
+pub fn set_diagnostics_include_content(
+    include: bool,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_diagnostics_include_content(include)
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
+
+#[tauri::command]
+pub fn get_state_snapshot_enabled(state: State<'_, SharedOrchestrator>) -> Result<bool, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.state_snapshot_enabled())
+}
+
+#[tauri::command]
+pub fn set_state_snapshot_enabled(
+    enabled: bool,
+    state: State<'_, SharedOrchestrator>,
+    app: AppHandle,
+) -> Result<(), String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_state_snapshot_enabled(enabled)
+        .map_err(|err| format!("failed to save settings: {err}"))?;
+    let counts = guard.urgency_counts();
+    emit_notifications_updated(&app, counts);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_llm_settings(llm: State<'_, SharedLlm>) -> Result<LlmSettingsResponse, String> {
+    let selected_model = llm.0.current_model();
+    let models = llm
+        .0
+        .list_models()
+        .map_err(|err| format!("failed to list Ollama models: {err}"))?;
+
+    Ok(LlmSettingsResponse {
+        selected_model,
+        models,
+        analysis_enabled: llm.0.analysis_enabled(),
+        output_language: llm.0.output_language(),
+        model_lifecycle: llm.0.model_lifecycle(),
+        session_keep_alive_minutes: llm.0.session_keep_alive_minutes(),
+    })
+}
+
+#[tauri::command]
+pub fn set_analysis_enabled(app: AppHandle, enabled: bool) -> Result<(), String> {
+    apply_analysis_enabled(&app, enabled)
+        .map_err(|err| format!("failed to save LLM settings: {err}"))
+}
+
+#[tauri::command]
+pub fn reanalyze_fallbacks(
+    app: AppHandle,
+    state: State<'_, SharedOrchestrator>,
+    llm: State<'_, SharedLlm>,
+) {
+    spawn_reanalysis(app, state.0.clone(), llm.0.clone());
+}
+
+#[tauri::command]
+pub fn get_llm_metrics(llm: State<'_, SharedLlm>) -> LlmMetricsSnapshot {
+    llm.0.metrics().snapshot()
+}
+
+#[tauri::command]
+pub fn get_backend_latency_stats(llm: State<'_, SharedLlm>) -> LatencyStats {
+    llm.0.metrics().latency_stats()
+}
+
+/// Rates the battery and CPU impact of the polling interval, backend, model
+/// size and recent notification volume, with the measured cost per cycle.
+#[tauri::command]
+pub fn get_resource_estimate(
+    state: State<'_, SharedOrchestrator>,
+    llm: State<'_, SharedLlm>,
+) -> Result<ResourceEstimate, String> {
+    let cycles = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?
+        .cycle_average();
+    let model = llm.0.current_model();
+    Ok(estimate(&ResourceInputs {
+        poll_interval_secs: POLL_INTERVAL_SECONDS,
+        base_url: OLLAMA_BASE_URL,
+        model: &model,
+        analysis_enabled: llm.0.analysis_enabled(),
+        cycles,
+    }))
+}
+
+#[tauri::command]
+pub fn get_llm_costs(days: u32, llm: State<'_, SharedLlm>) -> Result<Vec<LlmDailyCost>, String> {
+    llm.0
+        .costs(days)
+        .map_err(|err| format!("failed to read LLM usage: {err}"))
+}
+
+#[tauri::command]
+pub fn set_llm_token_price(
+    provider: String,
+    price: TokenPrice,
+    llm: State<'_, SharedLlm>,
+) -> Result<(), String> {
+    llm.0
+        .set_token_price(provider, price)
+        .map_err(|err| format!("failed to save LLM settings: {err}"))
+}
+
+#[tauri::command]
+pub fn set_output_language(
+    language: OutputLanguage,
+    llm: State<'_, SharedLlm>,
+) -> Result<(), String> {
+    llm.0
+        .set_output_language(language)
+        .map_err(|err| format!("failed to save LLM settings: {err}"))
+}
+
+/// Warm the model up when focus starts and release it after the session
+/// summary, keeping it loaded for `keep_alive_minutes` between requests.
+#[tauri::command]
+pub fn set_model_lifecycle(
+    enabled: bool,
+    keep_alive_minutes: u64,
+    llm: State<'_, SharedLlm>,
+) -> Result<(), String> {
+    llm.0
+        .set_model_lifecycle(enabled, keep_alive_minutes)
+        .map_err(|err| format!("failed to save LLM settings: {err}"))
+}
+
+#[tauri::command]
+pub fn set_keep_raw_responses(enabled: bool, llm: State<'_, SharedLlm>) -> Result<(), String> {
+    llm.0
+        .set_keep_raw_responses(enabled)
+        .map_err(|err| format!("failed to save LLM settings: {err}"))
+}
+
+#[tauri::command]
+pub fn set_analysis_log(enabled: bool, llm: State<'_, SharedLlm>) -> Result<(), String> {
+    llm.0
+        .set_analysis_log_enabled(enabled)
+        .map_err(|err| format!("failed to save LLM settings: {err}"))
+}
+
+#[tauri::command]
+pub fn get_analysis_prompt_template(llm: State<'_, SharedLlm>) -> AnalysisPromptTemplate {
+    llm.0.analysis_prompt_template()
+}
+
+/// The urgency criteria and interruption thresholds in effect, for showing
+/// what analysis currently goes by. Read-only.
+#[tauri::command]
+pub fn get_active_thresholds(
+    state: State<'_, SharedOrchestrator>,
+    llm: State<'_, SharedLlm>,
+) -> Result<ActiveThresholds, String> {
+    let rubric = llm.0.analysis_prompt_template();
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.active_thresholds(rubric))
+}
+
+/// Deletes the custom template so analysis goes back to the built-in one.
+/// Returns the template now in use.
+#[tauri::command]
+pub fn reset_analysis_prompt_template(
+    llm: State<'_, SharedLlm>,
+) -> Result<AnalysisPromptTemplate, String> {
+    llm.0
+        .reset_analysis_prompt_template()
+        .map_err(|err| format!("failed to delete analysis prompt template: {err}"))?;
+    Ok(llm.0.analysis_prompt_template())
+}
+
+#[tauri::command]
+pub fn set_llm_model(model: String, llm: State<'_, SharedLlm>) -> Result<(), String> {
+    llm.0
+        .set_model(model)
         .map_err(|err| format!("failed to save LLM model: {err}"))
 }
+
+#[tauri::command]
+pub fn get_onboarding_state(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<OnboardingState, String> {
+    let guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    Ok(guard.onboarding_state())
+}
+
+fn complete_onboarding_step(
+    state: &SharedOrchestrator,
+    step: OnboardingStep,
+) -> Result<OnboardingState, String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .complete_onboarding_step(step)
+        .map_err(|err| format!("{err:#}"))
+}
+
+/// Completes the Full Disk Access step once the notification store is readable.
+#[tauri::command]
+pub fn verify_full_disk_access(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<OnboardingState, String> {
+    complete_onboarding_step(&state, OnboardingStep::FullDiskAccess)
+}
+
+#[tauri::command]
+pub fn choose_onboarding_model(
+    model: String,
+    state: State<'_, SharedOrchestrator>,
+    llm: State<'_, SharedLlm>,
+) -> Result<OnboardingState, String> {
+    llm.0
+        .set_model(model)
+        .map_err(|err| format!("failed to save LLM model: {err}"))?;
+    complete_onboarding_step(&state, OnboardingStep::ChooseModel)
+}
+
+/// Same check as `test_llm_connection`; the step stays open if it fails.
+#[tauri::command]
+pub async fn confirm_ollama(
+    state: State<'_, SharedOrchestrator>,
+    llm: State<'_, SharedLlm>,
+) -> Result<OnboardingState, String> {
+    llm.0
+        .test_connection()
+        .await
+        .map_err(|err| format!("{err:#}"))?;
+    complete_onboarding_step(&state, OnboardingStep::ConfirmOllama)
+}
+
+/// Sets when the panel opens by itself (`None` for never) and completes
+/// the threshold step.
+#[tauri::command]
+pub fn choose_interruption_threshold(
+    min: Option<UrgencyLevel>,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<OnboardingState, String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .set_interruption_threshold(min)
+        .map_err(|err| format!("failed to save settings: {err}"))?;
+    guard
+        .complete_onboarding_step(OnboardingStep::InterruptionThreshold)
+        .map_err(|err| format!("{err:#}"))
+}
+
+#[tauri::command]
+pub fn send_test_notification(
+    state: State<'_, SharedOrchestrator>,
+) -> Result<OnboardingState, String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard.send_test_notification();
+    guard
+        .complete_onboarding_step(OnboardingStep::TestNotification)
+        .map_err(|err| format!("{err:#}"))
+}
+
+#[tauri::command]
+pub fn skip_onboarding_step(
+    step: OnboardingStep,
+    state: State<'_, SharedOrchestrator>,
+) -> Result<OnboardingState, String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .skip_onboarding_step(step)
+        .map_err(|err| format!("{err:#}"))
+}
+
+#[tauri::command]
+pub fn reset_onboarding(state: State<'_, SharedOrchestrator>) -> Result<OnboardingState, String> {
+    let mut guard = state
+        .0
+        .lock()
+        .map_err(|err| format!("state lock error: {err}"))?;
+    guard
+        .reset_onboarding()
+        .map_err(|err| format!("failed to save settings: {err}"))
+}
@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+
+/// Files under the config directory that are persisted through [`ConfigStore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConfigFile {
+    AppPrompts,
+    IgnoredApps,
+    Settings,
+    LlmSettings,
+    LastSummary,
+    SummaryHistory,
+    SessionIds,
+    SchemaCache,
+    LlmUsage,
+    AppStats,
+    LaunchState,
+    /// Plain text edited by the user; read by `LlmClient`, never saved.
+    AnalysisPrompt,
+    /// Appended line by line by `AnalysisLog`, never saved whole.
+    AnalysisLog,
+    ReadState,
+    WebhookOutbox,
+}
+
+impl ConfigFile {
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ConfigFile::AppPrompts => "app_prompts.json",
+            ConfigFile::IgnoredApps => "ignored_apps.json",
+            ConfigFile::Settings => "settings.json",
+            ConfigFile::LlmSettings => "llm_settings.json",
+            ConfigFile::LastSummary => "last_summary.json",
+            ConfigFile::SummaryHistory => "summary_history.json",
+            ConfigFile::SessionIds => "session_ids.json",
+            ConfigFile::SchemaCache => "schema_cache.json",
+            ConfigFile::LlmUsage => "llm_usage.json",
+            ConfigFile::AppStats => "app_stats.json",
+            ConfigFile::LaunchState => "launch_state.json",
+            ConfigFile::AnalysisPrompt => "analysis_prompt.txt",
+            ConfigFile::AnalysisLog => "analysis_log.jsonl",
+            ConfigFile::ReadState => "read_state.json",
+            ConfigFile::WebhookOutbox => "webhook_outbox.json",
+        }
+    }
+}
+
+/// Owns the config directory and serializes every write to it. Clones share one
+/// lock (independent of the orchestrator's), so saves from command threads and
+/// the poll thread never interleave.
+#[derive(Debug, Clone)]
+pub struct ConfigStore {
+    dir: PathBuf,
+    pending: Arc<Mutex<BTreeMap<ConfigFile, Vec<u8>>>>,
+    detached: bool,
+    first_launch: Arc<OnceLock<bool>>,
+}
+
+impl ConfigStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            pending: Arc::new(Mutex::new(BTreeMap::new())),
+            detached: false,
+            first_launch: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// A store over `dir` that reads every file as missing and drops every
+    /// write, so safe mode runs on defaults without touching the real files.
+    pub fn detached(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            detached: true,
+            ..Self::new(dir)
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, file: ConfigFile) -> PathBuf {
+        self.dir.join(file.file_name())
+    }
+
+    /// Current contents of `file`, or `None` if it does not exist or cannot be read.
+    pub fn read(&self, file: ConfigFile) -> Option<String> {
+        if self.detached {
+            return None;
+        }
+        fs::read_to_string(self.path(file)).ok()
+    }
+
+    /// Queues `value` for `file`, replacing contents staged earlier but not yet flushed.
+    pub fn stage<T: Serialize>(&self, file: ConfigFile, value: &T) -> Result<()> {
+        let mut pending = self.lock()?;
+        pending.insert(file, serde_json::to_vec_pretty(value)?);
+        Ok(())
+    }
+
+    /// Writes every staged file, including ones staged by other threads.
+    pub fn flush(&self) -> Result<()> {
+        let mut pending = self.lock()?;
+        if self.detached {
+            pending.clear();
+            return Ok(());
+        }
+        while let Some((file, contents)) = pending.pop_first() {
+            if let Err(err) = write_atomic(&self.path(file), &contents) {
+                pending.insert(file, contents);
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Nothing but the launch counter had been written when this was first
+    /// asked; clones keep that answer, so it holds after the launch starts
+    /// writing files. Never true for a detached store.
+    pub fn is_first_launch(&self) -> bool {
+        let launch_state = ConfigFile::LaunchState.file_name();
+        !self.detached
+            && *self.first_launch.get_or_init(|| {
+                fs::read_dir(&self.dir).map_or(true, |entries| {
+                    entries
+                        .flatten()
+                        .all(|entry| entry.file_name() == launch_state)
+                })
+            })
+    }
+
+    /// Deletes `file` and anything staged for it. Returns whether it existed.
+    pub fn remove(&self, file: ConfigFile) -> Result<bool> {
+        let mut pending = self.lock()?;
+        pending.remove(&file);
+        if self.detached {
+            return Ok(false);
+        }
+        match fs::remove_file(self.path(file)) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Stages and flushes `value` in one step.
+    pub fn save<T: Serialize>(&self, file: ConfigFile, value: &T) -> Result<()> {
+        self.stage(file, value)?;
+        self.flush()
+    }
+
+    /// Appends raw `contents` to `file`, for line logs that are never staged.
+    pub fn append(&self, file: ConfigFile, contents: &[u8]) -> Result<()> {
+        if self.detached {
+            return Ok(());
+        }
+        let path = self.path(file);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut handle| handle.write_all(contents))
+            .with_context(|| format!("cannot append to {}", path.display()))
+    }
+
+    /// Replaces `file` with raw `contents` right away, dropping anything
+    /// staged for it.
+    pub fn write(&self, file: ConfigFile, contents: &[u8]) -> Result<()> {
+        let mut pending = self.lock()?;
+        pending.remove(&file);
+        if self.detached {
+            return Ok(());
+        }
+        write_atomic(&self.path(file), contents)
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, BTreeMap<ConfigFile, Vec<u8>>>> {
+        self.pending
+            .lock()
+            .map_err(|err| anyhow!("config store lock error: {err}"))
+    }
+}
+
+/// Writes to a sibling temp file and renames it over the target, so readers
+/// never observe a partial file.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use serde_json::{json, Value};
+
+    use super::{ConfigFile, ConfigStore};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "notify-config-store-test-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn read_json(store: &ConfigStore, file: ConfigFile) -> Value {
+        serde_json::from_str(&store.read(file).expect("file exists")).expect("valid JSON")
+    }
+
+    #[test]
+    fn staged_files_are_written_in_one_flush() {
+        let store = ConfigStore::new(temp_dir("coalesce"));
+        store
+            .stage(ConfigFile::IgnoredApps, &vec!["a", "b"])
+            .unwrap();
+        store.stage(ConfigFile::IgnoredApps, &vec!["c"]).unwrap();
+        store
+            .stage(ConfigFile::Settings, &json!({ "state_snapshot": true }))
+            .unwrap();
+        assert!(store.read(ConfigFile::IgnoredApps).is_none());
+
+        store.flush().unwrap();
+
+        assert_eq!(read_json(&store, ConfigFile::IgnoredApps), json!(["c"]));
+        assert_eq!(
+            read_json(&store, ConfigFile::Settings),
+            json!({ "state_snapshot": true })
+        );
+    }
+
+    #[test]
+    fn concurrent_saves_leave_valid_json_with_the_last_state() {
+        let store = ConfigStore::new(temp_dir("hammer"));
+        store.save(ConfigFile::AppPrompts, &json!({})).unwrap();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let store = store.clone();
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    let content = store.read(ConfigFile::AppPrompts).expect("file exists");
+                    serde_json::from_str::<Value>(&content).expect("never a partial file");
+                }
+            })
+        };
+
+        let writers: Vec<_> = (0..8)
+            .map(|writer| {
+                let store = store.clone();
+                thread::spawn(move || {
+                    for seq in 0..50 {
+                        let value = json!({ "writer": writer, "seq": seq, "pad": "x".repeat(512) });
+                        store.save(ConfigFile::AppPrompts, &value).unwrap();
+                        store.save(ConfigFile::IgnoredApps, &vec![writer]).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        store
+            .save(ConfigFile::AppPrompts, &json!({ "writer": "final" }))
+            .unwrap();
+        stop.store(true, Ordering::SeqCst);
+        reader.join().unwrap();
+
+        assert_eq!(
+            read_json(&store, ConfigFile::AppPrompts),
+            json!({ "writer": "final" })
+        );
+        let ignored = read_json(&store, ConfigFile::IgnoredApps);
+        assert!(ignored.as_array().is_some_and(|v| v.len() == 1));
+    }
+
+    #[test]
+    fn detached_store_neither_reads_nor_writes_the_directory() {
+        let dir = temp_dir("detached");
+        let store = ConfigStore::new(&dir);
+        store.save(ConfigFile::IgnoredApps, &vec!["a"]).unwrap();
+
+        let detached = ConfigStore::detached(&dir);
+        assert_eq!(detached.read(ConfigFile::IgnoredApps), None);
+        detached.save(ConfigFile::IgnoredApps, &vec!["b"]).unwrap();
+        detached.save(ConfigFile::Settings, &json!({})).unwrap();
+        detached.append(ConfigFile::AnalysisLog, b"{}\n").unwrap();
+        detached.write(ConfigFile::IgnoredApps, b"[]").unwrap();
+
+        assert_eq!(read_json(&store, ConfigFile::IgnoredApps), json!(["a"]));
+        assert_eq!(store.read(ConfigFile::Settings), None);
+        assert_eq!(store.read(ConfigFile::AnalysisLog), None);
+    }
+}
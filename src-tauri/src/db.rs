@@ -1,145 +1,855 @@
+use std::collections::{BTreeMap, VecDeque};
 use std::env;
-use std::io::Cursor;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{Cursor, ErrorKind};
 use std::path::PathBuf;
 use std::process::Command;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::{bail, Context, Result};
-use log::warn;
+use log::{info, warn};
 use plist::Value as PlistValue;
 use rusqlite::{params, Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
 
-use crate::models::{Notification, ParsedPlist};
+use crate::config_store::{ConfigFile, ConfigStore};
+use crate::models::{InterruptionLevel, Notification, ParsedPlist};
+use crate::sources::NotificationSource;
 
-const SCHEMA_QUERY_Z: &str = "SELECT rec.Z_PK, rec.ZDATA, app.ZBUNDLEID \
+/// Seconds between the Unix epoch and the Core Data reference date (2001-01-01 UTC).
+pub(crate) const CORE_DATA_EPOCH_OFFSET: i64 = 978_307_200;
+/// With the `fixture-db` feature, a SQLite file read instead of the macOS
+/// store, on any platform and without the macOS version check.
+#[cfg(feature = "fixture-db")]
+const FIXTURE_DB_ENV: &str = "NOTIFY_FIXTURE_DB";
+/// With the `fixture-db` feature, the schema (`Z` or `record`) the store is
+/// read with instead of probing, to stand in for a given macOS release.
+#[cfg(feature = "fixture-db")]
+const FIXTURE_SCHEMA_ENV: &str = "NOTIFY_FIXTURE_SCHEMA";
+
+pub(crate) fn core_data_to_unix(value: f64) -> i64 {
+    value as i64 + CORE_DATA_EPOCH_OFFSET
+}
+
+/// Query layout for one known notification DB schema.
+#[derive(Debug)]
+struct NotificationSchema {
+    /// Short name reported by `get_schema_info`.
+    name: &'static str,
+    /// Tables the queries read, whose columns go into the fingerprint.
+    tables: &'static [&'static str],
+    /// Selects `(rowid, plist data, bundle id, delivery date)` for rows after `?`.
+    query: &'static str,
+    /// The same columns for the rowids in the JSON array `?`.
+    rows_query: &'static str,
+    max_rowid_query: &'static str,
+    min_rowid_query: &'static str,
+    /// Converts the delivery date column to Unix seconds.
+    date_to_unix: fn(f64) -> i64,
+}
+
+static SCHEMA_Z: NotificationSchema = NotificationSchema {
+    name: "Z",
+    tables: &["ZNOTIFICATIONENTRY", "ZNOTIFICATIONAPPENTRY"],
+    query: "SELECT rec.Z_PK, rec.ZDATA, app.ZBUNDLEID, rec.ZDELIVEREDDATE \
 FROM ZNOTIFICATIONENTRY rec \
 JOIN ZNOTIFICATIONAPPENTRY app ON rec.ZAPP = app.Z_PK \
 WHERE rec.Z_PK > ? \
-ORDER BY rec.Z_PK";
+ORDER BY rec.Z_PK",
+    rows_query: "SELECT rec.Z_PK, rec.ZDATA, app.ZBUNDLEID, rec.ZDELIVEREDDATE \
+FROM ZNOTIFICATIONENTRY rec \
+JOIN ZNOTIFICATIONAPPENTRY app ON rec.ZAPP = app.Z_PK \
+WHERE rec.Z_PK IN (SELECT value FROM json_each(?)) \
+ORDER BY rec.Z_PK",
+    max_rowid_query: "SELECT MAX(Z_PK) FROM ZNOTIFICATIONENTRY",
+    min_rowid_query: "SELECT MIN(Z_PK) FROM ZNOTIFICATIONENTRY",
+    date_to_unix: core_data_to_unix,
+};
 
-const SCHEMA_QUERY_RECORD: &str = "SELECT rec.rec_id, rec.data, app.identifier \
+static SCHEMA_RECORD: NotificationSchema = NotificationSchema {
+    name: "record",
+    tables: &["record", "app"],
+    query: "SELECT rec.rec_id, rec.data, app.identifier, rec.delivered_date \
 FROM record rec \
 JOIN app ON rec.app_id = app.app_id \
 WHERE rec.rec_id > ? \
-ORDER BY rec.rec_id";
+ORDER BY rec.rec_id",
+    rows_query: "SELECT rec.rec_id, rec.data, app.identifier, rec.delivered_date \
+FROM record rec \
+JOIN app ON rec.app_id = app.app_id \
+WHERE rec.rec_id IN (SELECT value FROM json_each(?)) \
+ORDER BY rec.rec_id",
+    max_rowid_query: "SELECT MAX(rec_id) FROM record",
+    min_rowid_query: "SELECT MIN(rec_id) FROM record",
+    date_to_unix: core_data_to_unix,
+};
+
+/// Schemas in the order they are probed.
+static KNOWN_SCHEMAS: [&NotificationSchema; 2] = [&SCHEMA_Z, &SCHEMA_RECORD];
+
+pub fn known_schema_names() -> Vec<&'static str> {
+    KNOWN_SCHEMAS.iter().map(|schema| schema.name).collect()
+}
+
+/// The store layout a schema was detected against, cached in
+/// `schema_cache.json` so later launches can skip probing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaFingerprint {
+    pub schema: String,
+    /// Every table in the store, sorted.
+    pub tables: Vec<String>,
+    /// `table.column` for the tables the schema reads, sorted.
+    pub columns: Vec<String>,
+    pub macos_version: String,
+}
+
+impl SchemaFingerprint {
+    fn capture(
+        conn: &Connection,
+        schema: &NotificationSchema,
+        macos_version: &str,
+    ) -> Result<Self> {
+        let mut statement =
+            conn.prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")?;
+        let tables = statement
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut columns = Vec::new();
+        let mut statement = conn.prepare("SELECT name FROM pragma_table_info(?)")?;
+        for table in schema.tables {
+            for column in statement.query_map(params![table], |row| row.get::<_, String>(0))? {
+                columns.push(format!("{table}.{}", column?));
+            }
+        }
+        columns.sort();
+
+        Ok(Self {
+            schema: schema.name.to_string(),
+            tables,
+            columns,
+            macos_version: macos_version.to_string(),
+        })
+    }
+
+    /// The schema this fingerprint names, or `None` if it was taken on another
+    /// macOS version or names a schema this build no longer knows.
+    fn schema_for(&self, macos_version: &str) -> Option<&'static NotificationSchema> {
+        if self.macos_version != macos_version {
+            return None;
+        }
+        KNOWN_SCHEMAS
+            .iter()
+            .copied()
+            .find(|schema| schema.name == self.schema)
+    }
+}
+
+const STORE_RETRY_MIN: Duration = Duration::from_secs(5);
+const STORE_RETRY_MAX: Duration = Duration::from_secs(60);
+/// Rowids `read_new` remembers returning, so one surfacing again (e.g. from
+/// a WAL checkpoint between reads) is not processed twice.
+const SEEN_ROWIDS_CAPACITY: usize = 1000;
+/// A drop in the latest rowid at least this large is logged as a store
+/// reset rather than the newest rows being dismissed.
+const ROWID_RESET_MIN_DROP: i64 = 50;
+/// How far back a catch-up read, or the first read of a recreated store,
+/// may look, in DB rows.
+pub(crate) const MAX_CATCH_UP_ROWS: i64 = 500;
+/// Reads of a row whose blob fails to parse before the row is skipped, when
+/// `max_parse_attempts` is unset.
+pub const DEFAULT_MAX_PARSE_ATTEMPTS: u32 = 3;
+/// Rows with parse failures remembered at once; the lowest rowids go first.
+const PARSE_FAILURE_CAPACITY: usize = 1000;
+
+/// Whether the notification DB file is currently present.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreHealth {
+    Available,
+    /// The file is absent (e.g. `usernoted` restarting); polls retry with backoff.
+    WaitingForStore,
+}
 
-const SCHEMA_MAX_ROWID_Z: &str = "SELECT MAX(Z_PK) FROM ZNOTIFICATIONENTRY";
-const SCHEMA_MAX_ROWID_RECORD: &str = "SELECT MAX(rec_id) FROM record";
+/// Which notification DB schemas the reader knows and which one it is using.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaInfo {
+    pub known_schemas: Vec<&'static str>,
+    /// `None` until a read has succeeded against the current store.
+    pub active_schema: Option<&'static str>,
+    pub macos_major: u32,
+}
+
+/// Rows whose blob failed to parse, for diagnostics.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParseFailureStats {
+    pub max_attempts: u32,
+    /// Failed rows that are still parsed again when re-read.
+    pub retrying: usize,
+    /// Failed rows left out of reads for good.
+    pub skipped: usize,
+    /// Parse failures since launch.
+    pub failures: u64,
+}
+
+/// Plist blob sizes for one app over the rows `blob_stats` looked at.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlobStats {
+    pub bundle_id: String,
+    pub rows: usize,
+    pub min_bytes: usize,
+    pub max_bytes: usize,
+    pub avg_bytes: f64,
+    /// Share of blobs that parsed as a plist, from 0.0 to 1.0.
+    pub parse_success_rate: f64,
+    /// Parsed blobs that yielded neither a title nor a body.
+    pub blank: usize,
+}
 
 pub struct NotificationDb {
     db_path: PathBuf,
-    query: Option<&'static str>,
+    schema: Option<&'static NotificationSchema>,
+    /// `schema` was chosen by hand and is never probed again.
+    schema_pinned: bool,
+    fingerprint: Option<SchemaFingerprint>,
+    /// Where fingerprints are persisted; `None` keeps detection in memory only.
+    schema_cache: Option<ConfigStore>,
+    macos_version: String,
+    health: StoreHealth,
+    retry_delay: Duration,
+    next_retry: Option<Instant>,
+    /// Rows macOS pruned before `poll_new` got to them, not yet taken.
+    expired_rows: i64,
+    /// The last rowid read through [`NotificationSource::poll`].
+    cursor: Option<i64>,
+    /// Newest rowids returned by `read_new`, oldest first.
+    seen_rowids: VecDeque<i64>,
+    max_parse_attempts: u32,
+    /// Failed parse attempts per rowid; rows at `max_parse_attempts` are
+    /// skipped.
+    parse_failures: BTreeMap<i64, u32>,
+    parse_failure_count: u64,
 }
 
 impl NotificationDb {
     pub fn new(db_path: PathBuf) -> Self {
-        Self {
+        let mut db = Self {
             db_path,
-            query: None,
+            schema: None,
+            schema_pinned: false,
+            fingerprint: None,
+            schema_cache: None,
+            macos_version: macos_product_version().to_string(),
+            health: StoreHealth::Available,
+            retry_delay: STORE_RETRY_MIN,
+            next_retry: None,
+            expired_rows: 0,
+            cursor: None,
+            seen_rowids: VecDeque::new(),
+            max_parse_attempts: DEFAULT_MAX_PARSE_ATTEMPTS,
+            parse_failures: BTreeMap::new(),
+            parse_failure_count: 0,
+        };
+        if db.is_missing() {
+            db.health = StoreHealth::WaitingForStore;
+        }
+        db
+    }
+
+    /// Loads the schema fingerprint saved by an earlier launch and persists
+    /// newly detected ones. A fingerprint from another macOS version is ignored.
+    pub fn with_schema_cache(mut self, config: ConfigStore) -> Self {
+        let cached = config
+            .read(ConfigFile::SchemaCache)
+            .and_then(|content| serde_json::from_str::<SchemaFingerprint>(&content).ok());
+        if let Some(fingerprint) = cached {
+            match fingerprint.schema_for(&self.macos_version) {
+                Some(schema) => {
+                    info!("using cached notification DB schema: {}", schema.name);
+                    self.schema = Some(schema);
+                    self.fingerprint = Some(fingerprint);
+                }
+                None => info!(
+                    "ignoring schema cache from macOS {:?}",
+                    fingerprint.macos_version
+                ),
+            }
+        }
+        self.schema_cache = Some(config);
+        self
+    }
+
+    /// Reads with the named schema instead of probing, even when queries fail.
+    #[cfg(feature = "fixture-db")]
+    pub fn with_schema(mut self, name: &str) -> Result<Self> {
+        let Some(schema) = KNOWN_SCHEMAS.iter().copied().find(|s| s.name == name) else {
+            bail!(
+                "unknown notification DB schema `{name}` (known: {})",
+                known_schema_names().join(", ")
+            )
+        };
+        self.schema = Some(schema);
+        self.schema_pinned = true;
+        Ok(self)
+    }
+
+    /// Pins the schema named by `NOTIFY_FIXTURE_SCHEMA`, if set.
+    #[cfg(feature = "fixture-db")]
+    pub fn with_fixture_schema(self) -> Result<Self> {
+        match env::var(FIXTURE_SCHEMA_ENV) {
+            Ok(name) => {
+                info!("using fixture notification DB schema: {name}");
+                self.with_schema(&name)
+            }
+            Err(_) => Ok(self),
+        }
+    }
+
+    pub fn health(&self) -> StoreHealth {
+        self.health
+    }
+
+    /// Name of the schema detected on the last successful read, if any.
+    pub fn active_schema(&self) -> Option<&'static str> {
+        self.schema.map(|schema| schema.name)
+    }
+
+    pub fn schema_info(&self) -> SchemaInfo {
+        SchemaInfo {
+            known_schemas: known_schema_names(),
+            active_schema: self.active_schema(),
+            macos_major: macos_major_version(),
+        }
+    }
+
+    /// Only a definite "not found" counts as missing, so permission errors
+    /// still surface through the normal open path.
+    fn is_missing(&self) -> bool {
+        matches!(fs::metadata(&self.db_path), Err(err) if err.kind() == ErrorKind::NotFound)
+    }
+
+    /// Reads rows after `*last_rowid`, tolerating the DB file being absent.
+    /// `None` means no baseline yet; the first successful poll sets it to the
+    /// store's latest rowid without returning the backlog.
+    pub fn poll_new(&mut self, last_rowid: &mut Option<i64>) -> Result<Vec<Notification>> {
+        if self.health == StoreHealth::WaitingForStore {
+            if self.next_retry.is_some_and(|at| Instant::now() < at) {
+                return Ok(Vec::new());
+            }
+            if self.is_missing() {
+                self.next_retry = Some(Instant::now() + self.retry_delay);
+                self.retry_delay = (self.retry_delay * 2).min(STORE_RETRY_MAX);
+                return Ok(Vec::new());
+            }
+            info!("Notification DB is available: {}", self.db_path.display());
+            self.health = StoreHealth::Available;
+            if !self.schema_pinned {
+                self.schema = None;
+            }
+            self.retry_delay = STORE_RETRY_MIN;
+            self.next_retry = None;
+
+            let latest = self.latest_rowid()?;
+            match *last_rowid {
+                None => {
+                    *last_rowid = Some(latest);
+                    self.seen_rowids.clear();
+                    return Ok(Vec::new());
+                }
+                // A recreated store restarts its rowids, and its rows arrived
+                // while it was gone: read them from the start instead of
+                // waiting for the new rowids to pass the old ones.
+                Some(rowid) if rowid > latest => {
+                    *last_rowid = Some((latest - MAX_CATCH_UP_ROWS).max(0));
+                    self.seen_rowids.clear();
+                }
+                Some(_) => {}
+            }
+        } else if self.is_missing() {
+            warn!(
+                "Notification DB disappeared, waiting for it to return: {}",
+                self.db_path.display()
+            );
+            self.health = StoreHealth::WaitingForStore;
+            self.next_retry = Some(Instant::now() + self.retry_delay);
+            return Ok(Vec::new());
+        }
+
+        let Some(since) = *last_rowid else {
+            *last_rowid = Some(self.latest_rowid()?);
+            return Ok(Vec::new());
+        };
+        let notifications = self.read_new(since)?;
+        if notifications.is_empty() {
+            // The store can be recreated in place, restarting its rowids
+            // without the file ever going missing. Any drop re-baselines:
+            // new rows get rowids above the current latest, not above `since`.
+            let latest = self.latest_rowid()?;
+            if latest < since {
+                if since - latest >= ROWID_RESET_MIN_DROP {
+                    warn!(
+                        "Notification DB rowids went back from {since} to {latest}; \
+                         the store was likely reset, re-syncing"
+                    );
+                }
+                *last_rowid = Some(latest);
+                // The rowids are handed out again from here.
+                self.seen_rowids.clear();
+            }
+            return Ok(notifications);
+        }
+        // Rowids also skip when a notification is dismissed, so only a gap
+        // below the oldest surviving row counts as pruned backlog.
+        if notifications.first().is_some_and(|n| n.rowid > since + 1) {
+            let expired = expired_row_count(since, self.oldest_rowid()?);
+            if expired > 0 {
+                warn!("Notification store pruned about {expired} rows before they were read");
+                self.expired_rows += expired;
+            }
+        }
+        if let Some(last) = notifications.last() {
+            *last_rowid = Some(last.rowid);
+        }
+        Ok(notifications)
+    }
+
+    pub fn cursor(&self) -> Option<i64> {
+        self.cursor
+    }
+
+    pub fn set_cursor(&mut self, cursor: Option<i64>) {
+        self.cursor = cursor;
+    }
+
+    /// Sets how many reads of an unparseable row return it blank before it is
+    /// skipped. At least 1.
+    pub fn set_max_parse_attempts(&mut self, attempts: u32) {
+        self.max_parse_attempts = attempts.max(1);
+    }
+
+    pub fn parse_failure_stats(&self) -> ParseFailureStats {
+        let skipped = self
+            .parse_failures
+            .values()
+            .filter(|&&attempts| attempts >= self.max_parse_attempts)
+            .count();
+        ParseFailureStats {
+            max_attempts: self.max_parse_attempts,
+            retrying: self.parse_failures.len() - skipped,
+            skipped,
+            failures: self.parse_failure_count,
+        }
+    }
+
+    /// Rows found pruned by `poll_new` since the last call.
+    pub fn take_expired_rows(&mut self) -> i64 {
+        std::mem::take(&mut self.expired_rows)
+    }
+
+    /// Rows delivered at or after `since` (Unix seconds), looking back at most
+    /// `max_rows` rows from the newest. Used for catch-up backfills.
+    pub fn read_recent(&mut self, since: i64, max_rows: i64) -> Result<Vec<Notification>> {
+        if self.health == StoreHealth::WaitingForStore {
+            return Ok(Vec::new());
         }
+        let floor = (self.latest_rowid()? - max_rows).max(0);
+        // Re-reads on purpose, so already returned rows are not skipped.
+        let mut notifications = self.read_notifications(|schema| schema.query, floor.into())?;
+        notifications.retain(|n| n.timestamp >= since);
+        Ok(notifications)
     }
 
+    /// Rows after `since_rowid`, leaving out any this reader returned before.
     pub fn read_new(&mut self, since_rowid: i64) -> Result<Vec<Notification>> {
-        let conn = Connection::open_with_flags(&self.db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
-            .with_context(|| format!("cannot open notification DB: {}", self.db_path.display()))?;
+        let mut notifications =
+            self.read_notifications(|schema| schema.query, since_rowid.into())?;
+        notifications.retain(|n| !self.seen_rowids.contains(&n.rowid));
+        self.seen_rowids
+            .extend(notifications.iter().map(|n| n.rowid));
+        while self.seen_rowids.len() > SEEN_ROWIDS_CAPACITY {
+            self.seen_rowids.pop_front();
+        }
+        Ok(notifications)
+    }
 
-        let query = self.resolve_query(&conn)?;
-        let mut statement = conn.prepare(query)?;
-        let rows = statement.query_map(params![since_rowid], |row| {
-            let rowid: i64 = row.get(0)?;
-            let data: Vec<u8> = row.get(1)?;
-            let bundle_id: String = row.get(2)?;
-            Ok((rowid, data, bundle_id))
-        })?;
+    /// Re-reads the given rows as they are now, e.g. to spot blobs an app
+    /// rewrote in place. Rows that are gone are left out.
+    pub fn read_rows(&mut self, rowids: &[i64]) -> Result<Vec<Notification>> {
+        if self.health == StoreHealth::WaitingForStore || rowids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let rowids = serde_json::to_string(rowids)?;
+        self.read_notifications(|schema| schema.rows_query, rowids.into())
+    }
 
+    fn read_notifications(
+        &mut self,
+        query: fn(&NotificationSchema) -> &'static str,
+        param: rusqlite::types::Value,
+    ) -> Result<Vec<Notification>> {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs() as i64;
 
-        let mut notifications = Vec::new();
-        for row in rows {
-            let (rowid, data, bundle_id) = row?;
-            let parsed = parse_notification_plist(&data);
+        let (rows, schema) = self.query_with_schema(|conn, schema| {
+            let mut statement = conn.prepare(query(schema))?;
+            let rows = statement.query_map(params![param], |row| {
+                let rowid: i64 = row.get(0)?;
+                let data: Vec<u8> = row.get(1)?;
+                let bundle_id: String = row.get(2)?;
+                let delivered: Option<f64> = row.get(3)?;
+                Ok((rowid, data, bundle_id, delivered))
+            })?;
+
+            let mut unique = Vec::new();
+            let mut previous = None;
+            for row in rows {
+                let row = row?;
+                // Duplicate app records multiply joined rows; rows arrive in
+                // rowid order, so repeats are adjacent and the first one wins.
+                if previous.replace(row.0) == Some(row.0) {
+                    continue;
+                }
+                unique.push(row);
+            }
+            Ok((unique, schema))
+        })?;
 
+        let mut notifications = Vec::new();
+        for (rowid, data, bundle_id, delivered) in rows {
+            let Some(parsed) = self.parse_row(rowid, &data) else {
+                continue;
+            };
             notifications.push(Notification {
                 rowid,
                 title: parsed.title,
                 body: parsed.body,
                 subtitle: parsed.subtitle,
                 bundle_id,
-                timestamp: now,
+                timestamp: delivered.map(schema.date_to_unix).unwrap_or(now),
+                thread_id: parsed.thread_id,
+                request_id: parsed.request_id,
+                badge: parsed.badge,
+                interruption_level: parsed.interruption_level,
+                system_critical: parsed.system_critical,
+                category: parsed.category,
+                account: None,
+                backfill: false,
             });
         }
-
         Ok(notifications)
     }
 
+    /// Parses a row's blob. A blob that fails is returned blank for its first
+    /// `max_parse_attempts` reads and skipped after that, so rows that are
+    /// read again (catch-up, revision checks) are not reprocessed forever.
+    /// A later success forgets the failures.
+    fn parse_row(&mut self, rowid: i64, data: &[u8]) -> Option<ParsedPlist> {
+        let attempts = self.parse_failures.get(&rowid).copied().unwrap_or(0);
+        if attempts >= self.max_parse_attempts {
+            return None;
+        }
+        match read_plist(data) {
+            Ok(value) => {
+                self.parse_failures.remove(&rowid);
+                Some(plist_fields(&value))
+            }
+            Err(err) => {
+                let attempts = attempts + 1;
+                self.parse_failure_count += 1;
+                if attempts == self.max_parse_attempts {
+                    warn!(
+                        "Skipping row {rowid} from now on; its plist failed to parse \
+                         {attempts} times: {err}"
+                    );
+                } else {
+                    warn!("Failed to parse plist data of row {rowid}: {err}");
+                }
+                self.parse_failures.insert(rowid, attempts);
+                while self.parse_failures.len() > PARSE_FAILURE_CAPACITY {
+                    self.parse_failures.pop_first();
+                }
+                Some(ParsedPlist::default())
+            }
+        }
+    }
+
+    /// Per-app blob sizes and parse results over the newest `max_rows` rows.
+    /// Read-only: the caller's read position is not involved.
+    pub fn blob_stats(&mut self, max_rows: i64) -> Result<Vec<BlobStats>> {
+        if self.health == StoreHealth::WaitingForStore {
+            return Ok(Vec::new());
+        }
+        let floor = (self.latest_rowid()? - max_rows).max(0);
+        self.query_with_schema(|conn, schema| {
+            let mut statement = conn.prepare(schema.query)?;
+            let rows = statement.query_map(params![floor], |row| {
+                let rowid: i64 = row.get(0)?;
+                let data: Vec<u8> = row.get(1)?;
+                let bundle_id: String = row.get(2)?;
+                Ok((rowid, data, bundle_id))
+            })?;
+
+            let mut samples = Vec::new();
+            let mut previous = None;
+            for row in rows {
+                let (rowid, data, bundle_id) = row?;
+                if previous.replace(rowid) == Some(rowid) {
+                    continue;
+                }
+                let outcome = match read_plist(&data) {
+                    Ok(value) => {
+                        let parsed = plist_fields(&value);
+                        BlobOutcome::Parsed {
+                            blank: parsed.title.is_empty() && parsed.body.is_empty(),
+                        }
+                    }
+                    Err(_) => BlobOutcome::Failed,
+                };
+                samples.push((bundle_id, data.len(), outcome));
+            }
+            Ok(summarize_blobs(samples))
+        })
+    }
+
     pub fn latest_rowid(&mut self) -> Result<i64> {
+        self.query_with_schema(|conn, schema| {
+            let mut statement = conn.prepare(schema.max_rowid_query)?;
+            let max_rowid = statement.query_row([], |row| row.get::<_, Option<i64>>(0))?;
+            Ok(max_rowid.unwrap_or(0))
+        })
+    }
+
+    pub fn oldest_rowid(&mut self) -> Result<Option<i64>> {
+        self.query_with_schema(|conn, schema| {
+            let mut statement = conn.prepare(schema.min_rowid_query)?;
+            Ok(statement.query_row([], |row| row.get::<_, Option<i64>>(0))?)
+        })
+    }
+
+    /// Runs `query` against the resolved schema. If a schema resolved earlier
+    /// (possibly from the cache) no longer works, it is dropped and `query` is
+    /// retried once against a freshly probed one.
+    fn query_with_schema<T>(
+        &mut self,
+        query: impl Fn(&Connection, &'static NotificationSchema) -> Result<T>,
+    ) -> Result<T> {
         let conn = Connection::open_with_flags(&self.db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
             .with_context(|| format!("cannot open notification DB: {}", self.db_path.display()))?;
 
-        let query = self.resolve_query(&conn)?;
-        let max_query = match query {
-            SCHEMA_QUERY_Z => SCHEMA_MAX_ROWID_Z,
-            SCHEMA_QUERY_RECORD => SCHEMA_MAX_ROWID_RECORD,
-            _ => bail!("unsupported schema query"),
-        };
-
-        let mut statement = conn.prepare(max_query)?;
-        let max_rowid = statement.query_row([], |row| row.get::<_, Option<i64>>(0))?;
-        Ok(max_rowid.unwrap_or(0))
+        let resolved_earlier = self.schema.is_some();
+        let schema = self.resolve_schema(&conn)?;
+        match query(&conn, schema) {
+            Err(err) if resolved_earlier && !self.schema_pinned => {
+                warn!(
+                    "query with {} schema failed, detecting the schema again: {err:#}",
+                    schema.name
+                );
+                self.schema = None;
+                self.fingerprint = None;
+                let schema = self.resolve_schema(&conn)?;
+                query(&conn, schema)
+            }
+            result => result,
+        }
     }
 
-    fn resolve_query(&mut self, conn: &Connection) -> Result<&'static str> {
-        if let Some(query) = self.query {
-            return Ok(query);
+    fn resolve_schema(&mut self, conn: &Connection) -> Result<&'static NotificationSchema> {
+        if let Some(schema) = self.schema {
+            return Ok(schema);
         }
 
-        for query in [SCHEMA_QUERY_Z, SCHEMA_QUERY_RECORD] {
-            if let Ok(mut statement) = conn.prepare(query) {
+        for schema in KNOWN_SCHEMAS {
+            if let Ok(mut statement) = conn.prepare(schema.query) {
                 if statement.query(params![0]).is_ok() {
-                    self.query = Some(query);
-                    return Ok(query);
+                    self.schema = Some(schema);
+                    self.remember_fingerprint(conn, schema);
+                    return Ok(schema);
                 }
             }
         }
 
         bail!("could not determine notification DB schema")
     }
-}
 
-fn parse_notification_plist(data: &[u8]) -> ParsedPlist {
-    let parsed = PlistValue::from_reader(Cursor::new(data));
-    let Ok(value) = parsed else {
-        warn!("Failed to parse plist data");
-        return ParsedPlist {
-            title: String::new(),
-            body: String::new(),
-            subtitle: String::new(),
+    fn remember_fingerprint(&mut self, conn: &Connection, schema: &'static NotificationSchema) {
+        let fingerprint = match SchemaFingerprint::capture(conn, schema, &self.macos_version) {
+            Ok(fingerprint) => fingerprint,
+            Err(err) => {
+                warn!("failed to fingerprint notification DB schema: {err:#}");
+                return;
+            }
         };
-    };
+        if self.fingerprint.as_ref() == Some(&fingerprint) {
+            return;
+        }
+        if let Some(config) = &self.schema_cache {
+            if let Err(err) = config.save(ConfigFile::SchemaCache, &fingerprint) {
+                warn!("failed to save schema cache: {err:#}");
+            }
+        }
+        self.fingerprint = Some(fingerprint);
+    }
+}
 
-    let title = extract_plist_string(&value, &["titl"]);
-    let body = extract_plist_string(&value, &["body"]);
-    let subtitle = extract_plist_string(&value, &["subt"]);
+/// Rowids between `last_rowid` and the oldest row still in the store.
+fn expired_row_count(last_rowid: i64, oldest: Option<i64>) -> i64 {
+    oldest.map_or(0, |oldest| (oldest - last_rowid - 1).max(0))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlobOutcome {
+    Parsed { blank: bool },
+    Failed,
+}
+
+/// Groups `(bundle_id, byte length, outcome)` samples by app, sorted by bundle ID.
+fn summarize_blobs(samples: Vec<(String, usize, BlobOutcome)>) -> Vec<BlobStats> {
+    let mut by_app: BTreeMap<String, Vec<(usize, BlobOutcome)>> = BTreeMap::new();
+    for (bundle_id, len, outcome) in samples {
+        by_app.entry(bundle_id).or_default().push((len, outcome));
+    }
+    by_app
+        .into_iter()
+        .map(|(bundle_id, blobs)| {
+            let rows = blobs.len();
+            let lens = blobs.iter().map(|(len, _)| *len);
+            let parsed = blobs
+                .iter()
+                .filter(|(_, outcome)| *outcome != BlobOutcome::Failed)
+                .count();
+            BlobStats {
+                bundle_id,
+                rows,
+                min_bytes: lens.clone().min().unwrap_or(0),
+                max_bytes: lens.clone().max().unwrap_or(0),
+                avg_bytes: lens.sum::<usize>() as f64 / rows as f64,
+                parse_success_rate: parsed as f64 / rows as f64,
+                blank: blobs
+                    .iter()
+                    .filter(|(_, outcome)| *outcome == BlobOutcome::Parsed { blank: true })
+                    .count(),
+            }
+        })
+        .collect()
+}
+
+fn read_plist(data: &[u8]) -> Result<PlistValue, plist::Error> {
+    PlistValue::from_reader(Cursor::new(data)).or_else(|err| {
+        // XML plists with invalid UTF-8 fail as a whole; retry with the bad
+        // sequences replaced so the remaining fields survive.
+        let lossy = String::from_utf8_lossy(data);
+        if matches!(lossy, std::borrow::Cow::Owned(_)) {
+            warn!("Notification plist contains invalid UTF-8; retrying lossily");
+            PlistValue::from_reader(Cursor::new(lossy.as_bytes()))
+        } else {
+            Err(err)
+        }
+    })
+}
+
+/// One blob on its own, outside the reader's retry bookkeeping.
+#[cfg(test)]
+pub(crate) fn parse_notification_plist(data: &[u8]) -> ParsedPlist {
+    read_plist(data)
+        .map(|value| plist_fields(&value))
+        .unwrap_or_default()
+}
+
+/// A binary plist blob holding just `title`, as a store row would.
+#[cfg(test)]
+pub(crate) fn plist_blob(title: &str) -> Vec<u8> {
+    let mut dict = plist::Dictionary::new();
+    dict.insert("titl".to_string(), PlistValue::String(title.to_string()));
+    let mut buf = Vec::new();
+    PlistValue::Dictionary(dict)
+        .to_writer_binary(&mut buf)
+        .expect("plist serialization");
+    buf
+}
+
+fn plist_fields(value: &PlistValue) -> ParsedPlist {
+    let title = extract_plist_string(value, &["titl"]);
+    let body = extract_plist_string(value, &["body"]);
+    let subtitle = extract_plist_string(value, &["subt"]);
+    let thread_id = extract_plist_string(value, &["thre"]);
+    let thread_id = if thread_id.is_empty() {
+        extract_plist_string(value, &["req", "thre"])
+    } else {
+        thread_id
+    };
+    let request_id = extract_plist_string(value, &["req", "iden"]);
+    let category = extract_plist_string(value, &["cate"]);
+    let category = if category.is_empty() {
+        extract_plist_string(value, &["req", "cate"])
+    } else {
+        category
+    };
+    let badge = extract_plist_integer(value, &["badg"])
+        .or_else(|| extract_plist_integer(value, &["req", "badg"]));
+    let interruption_level = extract_plist_integer(value, &["intl"])
+        .or_else(|| extract_plist_integer(value, &["req", "intl"]))
+        .map(InterruptionLevel::from_raw)
+        .unwrap_or_default();
+    let system_critical = extract_plist_bool(value, &["soun", "crit"])
+        .or_else(|| extract_plist_bool(value, &["req", "soun", "crit"]))
+        .unwrap_or(false);
 
     ParsedPlist {
         title: if title.is_empty() {
-            extract_plist_string(&value, &["req", "titl"])
+            extract_plist_string(value, &["req", "titl"])
         } else {
             title
         },
         body: if body.is_empty() {
-            extract_plist_string(&value, &["req", "body"])
+            extract_plist_string(value, &["req", "body"])
         } else {
             body
         },
         subtitle: if subtitle.is_empty() {
-            extract_plist_string(&value, &["req", "subt"])
+            extract_plist_string(value, &["req", "subt"])
         } else {
             subtitle
         },
+        thread_id: Some(thread_id).filter(|id| !id.is_empty()),
+        request_id: Some(request_id).filter(|id| !id.is_empty()),
+        badge,
+        interruption_level,
+        system_critical,
+        category: Some(category).filter(|category| !category.is_empty()),
+    }
+}
+
+/// Booleans, or integers where non-zero is true.
+fn extract_plist_bool(value: &PlistValue, keys: &[&str]) -> Option<bool> {
+    let mut current = value;
+    for key in keys {
+        current = current.as_dictionary()?.get(key)?;
+    }
+
+    match current {
+        PlistValue::Boolean(flag) => Some(*flag),
+        PlistValue::Integer(number) => number.as_signed().map(|n| n != 0),
+        _ => None,
+    }
+}
+
+fn extract_plist_integer(value: &PlistValue, keys: &[&str]) -> Option<i64> {
+    let mut current = value;
+    for key in keys {
+        current = current.as_dictionary()?.get(key)?;
+    }
+
+    match current {
+        PlistValue::Integer(number) => number.as_signed(),
+        PlistValue::String(text) => text.trim().parse().ok(),
+        _ => None,
     }
 }
 
@@ -155,13 +865,29 @@ fn extract_plist_string(value: &PlistValue, keys: &[&str]) -> String {
         current = next;
     }
 
-    current
-        .as_string()
-        .map(ToString::to_string)
-        .unwrap_or_default()
+    match current {
+        PlistValue::String(text) => text.clone(),
+        PlistValue::Data(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        _ => String::new(),
+    }
 }
 
 pub fn get_notification_db_path() -> Result<PathBuf> {
+    #[cfg(feature = "fixture-db")]
+    let fixture = env::var_os(FIXTURE_DB_ENV);
+    #[cfg(not(feature = "fixture-db"))]
+    let fixture = None;
+    notification_db_path(fixture)
+}
+
+/// `fixture` is the `NOTIFY_FIXTURE_DB` value, passed in so tests need not
+/// touch the process environment.
+fn notification_db_path(fixture: Option<OsString>) -> Result<PathBuf> {
+    if let Some(path) = fixture {
+        info!("using fixture notification DB: {}", path.to_string_lossy());
+        return Ok(PathBuf::from(path));
+    }
+
     let major = macos_major_version();
     if major < 15 {
         bail!("notify supports macOS 15 (Tahoe) or newer only. detected major: {major}");
@@ -176,12 +902,856 @@ pub fn get_notification_db_path() -> Result<PathBuf> {
         .join("db"))
 }
 
-fn macos_major_version() -> u32 {
+/// Major macOS version from `sw_vers`, or 0 if it cannot be determined.
+pub fn macos_major_version() -> u32 {
+    let major = macos_product_version()
+        .split('.')
+        .next()
+        .unwrap_or_default();
+    major.parse::<u32>().unwrap_or(0)
+}
+
+/// Full macOS version from `sw_vers` (e.g. "15.1"), or empty if it cannot be
+/// determined. Cached after the first call.
+pub fn macos_product_version() -> &'static str {
+    static VERSION: OnceLock<String> = OnceLock::new();
+    VERSION.get_or_init(detect_macos_product_version)
+}
+
+fn detect_macos_product_version() -> String {
     let output = Command::new("sw_vers").arg("-productVersion").output();
     let Ok(output) = output else {
-        return 0;
+        return String::new();
     };
-    let version = String::from_utf8_lossy(&output.stdout);
-    let major = version.trim().split('.').next().unwrap_or_default();
-    major.parse::<u32>().unwrap_or(0)
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// The Apple store as a source: rows after its cursor, which advances as
+/// they are read.
+impl NotificationSource for NotificationDb {
+    fn name(&self) -> &str {
+        "apple"
+    }
+
+    fn poll(&mut self) -> Result<Vec<Notification>> {
+        let mut cursor = self.cursor;
+        let notifications = self.poll_new(&mut cursor)?;
+        self.cursor = cursor;
+        Ok(notifications)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use plist::{Dictionary, Value as PlistValue};
+    use rusqlite::{params, Connection};
+
+    use std::time::Duration;
+
+    use super::{
+        macos_product_version, parse_notification_plist, plist_blob, summarize_blobs, BlobOutcome,
+        NotificationDb, SchemaFingerprint, StoreHealth, CORE_DATA_EPOCH_OFFSET, MAX_CATCH_UP_ROWS,
+    };
+    use crate::config_store::{ConfigFile, ConfigStore};
+    use crate::models::InterruptionLevel;
+
+    fn fixture_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "notify-db-test-{}-{name}.sqlite",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn cache_store(name: &str) -> ConfigStore {
+        let dir = std::env::temp_dir().join(format!(
+            "notify-db-test-{}-{name}-config",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        ConfigStore::new(dir)
+    }
+
+    fn cached_fingerprint(config: &ConfigStore) -> SchemaFingerprint {
+        let content = config.read(ConfigFile::SchemaCache).expect("cache written");
+        serde_json::from_str(&content).expect("valid fingerprint")
+    }
+
+    fn create_z_db(path: &PathBuf) {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE ZNOTIFICATIONAPPENTRY (Z_PK INTEGER PRIMARY KEY, ZBUNDLEID TEXT);
+             CREATE TABLE ZNOTIFICATIONENTRY (Z_PK INTEGER PRIMARY KEY, ZAPP INTEGER, ZDATA BLOB, ZDELIVEREDDATE TIMESTAMP);
+             INSERT INTO ZNOTIFICATIONAPPENTRY VALUES (1, 'com.example.app');",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO ZNOTIFICATIONENTRY VALUES (7, 1, ?, 0)",
+            params![plist_blob("z")],
+        )
+        .unwrap();
+    }
+
+    fn create_record_db(path: &PathBuf, rowids: &[i64]) {
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS app (app_id INTEGER PRIMARY KEY, identifier TEXT);
+             CREATE TABLE IF NOT EXISTS record (rec_id INTEGER PRIMARY KEY, app_id INTEGER, data BLOB, delivered_date REAL);
+             INSERT OR IGNORE INTO app VALUES (1, 'com.example.app');",
+        )
+        .unwrap();
+        for rowid in rowids {
+            conn.execute(
+                "INSERT INTO record VALUES (?, 1, ?, 0)",
+                params![rowid, plist_blob("n")],
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn blob_summary_groups_sizes_and_parse_results_by_app() {
+        let sample = |bundle_id: &str, len, outcome| (bundle_id.to_string(), len, outcome);
+        let stats = summarize_blobs(vec![
+            sample("com.b", 100, BlobOutcome::Parsed { blank: false }),
+            sample("com.a", 10, BlobOutcome::Failed),
+            sample("com.b", 300, BlobOutcome::Parsed { blank: true }),
+            sample("com.b", 200, BlobOutcome::Failed),
+            sample("com.b", 400, BlobOutcome::Parsed { blank: false }),
+        ]);
+
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[0].bundle_id.as_str(), stats[0].rows), ("com.a", 1));
+        assert_eq!(stats[0].parse_success_rate, 0.0);
+        let b = &stats[1];
+        assert_eq!((b.min_bytes, b.max_bytes, b.avg_bytes), (100, 400, 250.0));
+        assert_eq!((b.parse_success_rate, b.blank), (0.75, 1));
+    }
+
+    #[test]
+    fn blob_stats_leave_the_read_position_alone() {
+        let path = fixture_path("blob-stats");
+        create_record_db(&path, &[1, 2, 3]);
+        Connection::open(&path)
+            .unwrap()
+            .execute(
+                "INSERT INTO record VALUES (4, 1, ?, 0)",
+                params![b"not a plist".to_vec()],
+            )
+            .unwrap();
+        let mut db = NotificationDb::new(path.clone());
+        let mut last_rowid = Some(2);
+
+        let stats = db.blob_stats(3).unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].rows, 3);
+        assert_eq!(stats[0].parse_success_rate, 2.0 / 3.0);
+        assert_eq!(
+            stats[0].max_bytes,
+            b"not a plist".len().max(plist_blob("n").len())
+        );
+
+        let rows = db.poll_new(&mut last_rowid).unwrap();
+        assert_eq!(rows.iter().map(|n| n.rowid).collect::<Vec<_>>(), vec![3, 4]);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn rows_are_re_read_by_rowid_with_their_current_content() {
+        let path = fixture_path("read-rows");
+        create_record_db(&path, &[1, 2, 3]);
+        let mut db = NotificationDb::new(path.clone());
+        Connection::open(&path)
+            .unwrap()
+            .execute(
+                "UPDATE record SET data = ? WHERE rec_id = 3",
+                params![plist_blob("edited")],
+            )
+            .unwrap();
+
+        let rows = db.read_rows(&[3, 1, 99]).unwrap();
+        let read: Vec<_> = rows.iter().map(|n| (n.rowid, n.title.as_str())).collect();
+        assert_eq!(read, vec![(1, "n"), (3, "edited")]);
+        assert!(db.read_rows(&[]).unwrap().is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn unparseable_rows_are_retried_a_bounded_number_of_times() {
+        let path = fixture_path("parse-retry");
+        create_record_db(&path, &[1, 2]);
+        let conn = Connection::open(&path).unwrap();
+        for rowid in [3, 4] {
+            conn.execute(
+                "INSERT INTO record VALUES (?, 1, ?, 0)",
+                params![rowid, b"not a plist".to_vec()],
+            )
+            .unwrap();
+        }
+        let mut db = NotificationDb::new(path.clone());
+        db.set_max_parse_attempts(2);
+        let ids = |rows: Vec<crate::models::Notification>| {
+            rows.iter()
+                .map(|n| (n.rowid, n.title.clone()))
+                .collect::<Vec<_>>()
+        };
+        let blank = |rowid: i64| (rowid, String::new());
+
+        assert_eq!(
+            ids(db.read_rows(&[1, 3, 4]).unwrap()),
+            vec![(1, "n".to_string()), blank(3), blank(4)]
+        );
+        // Row 4 is rewritten with a valid blob before it runs out of retries.
+        conn.execute(
+            "UPDATE record SET data = ? WHERE rec_id = 4",
+            params![plist_blob("fixed")],
+        )
+        .unwrap();
+        assert_eq!(
+            ids(db.read_rows(&[3, 4]).unwrap()),
+            vec![blank(3), (4, "fixed".to_string())]
+        );
+        let stats = db.parse_failure_stats();
+        assert_eq!((stats.retrying, stats.skipped, stats.failures), (0, 1, 3));
+
+        // Row 3 is left out of every kind of re-read from now on.
+        assert_eq!(
+            ids(db.read_rows(&[3, 4]).unwrap()),
+            vec![(4, "fixed".to_string())]
+        );
+        let recent = ids(db.read_recent(0, 100).unwrap());
+        assert_eq!(
+            recent.iter().map(|r| r.0).collect::<Vec<_>>(),
+            vec![1, 2, 4]
+        );
+        assert_eq!(db.parse_failure_stats().failures, 3);
+
+        // A higher limit gives it more attempts.
+        db.set_max_parse_attempts(3);
+        assert_eq!(ids(db.read_rows(&[3]).unwrap()), vec![blank(3)]);
+        assert_eq!(db.parse_failure_stats().skipped, 1);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn missing_store_at_startup_waits_then_baselines() {
+        let path = fixture_path("late");
+        let mut db = NotificationDb::new(path.clone());
+        let mut last_rowid = None;
+
+        assert_eq!(db.health(), StoreHealth::WaitingForStore);
+        assert!(db.poll_new(&mut last_rowid).unwrap().is_empty());
+
+        create_record_db(&path, &[1, 2]);
+        db.next_retry = None;
+        assert!(db.poll_new(&mut last_rowid).unwrap().is_empty());
+        assert_eq!(db.health(), StoreHealth::Available);
+        assert_eq!(last_rowid, Some(2));
+
+        create_record_db(&path, &[3]);
+        let rows = db.poll_new(&mut last_rowid).unwrap();
+        assert_eq!(rows.iter().map(|n| n.rowid).collect::<Vec<_>>(), vec![3]);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn store_disappearing_and_reappearing_resumes() {
+        let path = fixture_path("flap");
+        create_record_db(&path, &[10]);
+        let mut db = NotificationDb::new(path.clone());
+        let mut last_rowid = Some(db.latest_rowid().unwrap());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(db.poll_new(&mut last_rowid).unwrap().is_empty());
+        assert_eq!(db.health(), StoreHealth::WaitingForStore);
+
+        // Still missing: retry is deferred and the delay grows.
+        db.next_retry = None;
+        assert!(db.poll_new(&mut last_rowid).unwrap().is_empty());
+        assert!(db.retry_delay > Duration::from_secs(5));
+
+        // Reappears with the old rows plus a new one.
+        create_record_db(&path, &[10, 11]);
+        db.next_retry = None;
+        let rows = db.poll_new(&mut last_rowid).unwrap();
+        assert_eq!(db.health(), StoreHealth::Available);
+        assert_eq!(rows.iter().map(|n| n.rowid).collect::<Vec<_>>(), vec![11]);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn recreated_store_with_lower_rowids_is_read_from_the_start() {
+        let path = fixture_path("reset");
+        create_record_db(&path, &[50]);
+        let mut db = NotificationDb::new(path.clone());
+        let mut last_rowid = Some(50);
+
+        std::fs::remove_file(&path).unwrap();
+        db.poll_new(&mut last_rowid).unwrap();
+        create_record_db(&path, &[1, 2]);
+        db.next_retry = None;
+
+        let rows = db.poll_new(&mut last_rowid).unwrap();
+        assert_eq!(rows.iter().map(|n| n.rowid).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(last_rowid, Some(2));
+
+        // Only the latest `MAX_CATCH_UP_ROWS` of a large one.
+        let mut last_rowid = Some(10_000);
+        std::fs::remove_file(&path).unwrap();
+        db.poll_new(&mut last_rowid).unwrap();
+        create_record_db(&path, &(1..=MAX_CATCH_UP_ROWS + 20).collect::<Vec<_>>());
+        db.next_retry = None;
+        let rows = db.poll_new(&mut last_rowid).unwrap();
+        assert_eq!(rows.len() as i64, MAX_CATCH_UP_ROWS);
+        assert_eq!(rows[0].rowid, 21);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn rowids_reset_in_place_are_rebaselined() {
+        let path = fixture_path("reset-in-place");
+        create_record_db(&path, &[500]);
+        let mut db = NotificationDb::new(path.clone());
+        let mut last_rowid = Some(500);
+        assert!(db.poll_new(&mut last_rowid).unwrap().is_empty());
+        assert_eq!(last_rowid, Some(500));
+
+        Connection::open(&path)
+            .unwrap()
+            .execute("DELETE FROM record", [])
+            .unwrap();
+        create_record_db(&path, &[1, 2]);
+        assert!(db.poll_new(&mut last_rowid).unwrap().is_empty());
+        assert_eq!(last_rowid, Some(2));
+
+        create_record_db(&path, &[3]);
+        let rows = db.poll_new(&mut last_rowid).unwrap();
+        assert_eq!(rows.iter().map(|n| n.rowid).collect::<Vec<_>>(), vec![3]);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn a_rowid_read_twice_is_returned_once_until_the_store_resets() {
+        let path = fixture_path("seen-rowids");
+        create_record_db(&path, &[1, 2]);
+        let mut db = NotificationDb::new(path.clone());
+
+        let rowids = |rows: Vec<crate::models::Notification>| {
+            rows.iter().map(|n| n.rowid).collect::<Vec<_>>()
+        };
+        assert_eq!(rowids(db.read_new(0).unwrap()), vec![1, 2]);
+        assert!(db.read_new(0).unwrap().is_empty());
+        create_record_db(&path, &[3]);
+        assert_eq!(rowids(db.read_new(1).unwrap()), vec![3]);
+
+        // After a reset the same rowids are new notifications.
+        Connection::open(&path)
+            .unwrap()
+            .execute("DELETE FROM record WHERE rec_id > 1", [])
+            .unwrap();
+        let mut last_rowid = Some(3);
+        assert!(db.poll_new(&mut last_rowid).unwrap().is_empty());
+        assert_eq!(last_rowid, Some(1));
+        create_record_db(&path, &[2]);
+        assert_eq!(rowids(db.poll_new(&mut last_rowid).unwrap()), vec![2]);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn duplicate_app_records_do_not_repeat_notifications() {
+        let path = fixture_path("dup-app");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE app (app_id INTEGER, identifier TEXT);
+             CREATE TABLE record (rec_id INTEGER PRIMARY KEY, app_id INTEGER, data BLOB, delivered_date REAL);
+             INSERT INTO app VALUES (1, 'com.example.app'), (1, 'com.example.app'), (2, 'com.example.other');",
+        )
+        .unwrap();
+        for (rowid, app_id) in [(1, 1), (2, 2), (3, 1)] {
+            conn.execute(
+                "INSERT INTO record VALUES (?, ?, ?, 0)",
+                params![rowid, app_id, plist_blob("n")],
+            )
+            .unwrap();
+        }
+        let mut db = NotificationDb::new(path.clone());
+        let mut last_rowid = Some(0);
+
+        let rows = db.poll_new(&mut last_rowid).unwrap();
+        assert_eq!(
+            rows.iter().map(|n| n.rowid).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(last_rowid, Some(3));
+        assert!(db.poll_new(&mut last_rowid).unwrap().is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn rows_pruned_before_they_were_read_are_counted() {
+        let path = fixture_path("pruned");
+        create_record_db(&path, &[1, 2, 3]);
+        let mut db = NotificationDb::new(path.clone());
+        let mut last_rowid = Some(3);
+
+        // Paused while macOS kept delivering and then pruned rows 1..=43.
+        create_record_db(&path, &[44, 45]);
+        Connection::open(&path)
+            .unwrap()
+            .execute("DELETE FROM record WHERE rec_id < 44", [])
+            .unwrap();
+
+        let rows = db.poll_new(&mut last_rowid).unwrap();
+        assert_eq!(
+            rows.iter().map(|n| n.rowid).collect::<Vec<_>>(),
+            vec![44, 45]
+        );
+        assert_eq!(db.take_expired_rows(), 40);
+        assert_eq!(db.take_expired_rows(), 0);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn dismissed_rows_above_the_oldest_row_are_not_counted() {
+        let path = fixture_path("dismissed");
+        create_record_db(&path, &[5, 9]);
+        let mut db = NotificationDb::new(path.clone());
+        let mut last_rowid = Some(5);
+
+        let rows = db.poll_new(&mut last_rowid).unwrap();
+        assert_eq!(rows.iter().map(|n| n.rowid).collect::<Vec<_>>(), vec![9]);
+        assert_eq!(db.take_expired_rows(), 0);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn invalid_utf8_in_body_is_recovered_without_blanking_other_fields() {
+        let mut xml = b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+<plist version=\"1.0\"><dict>\
+<key>titl</key><string>Deploy</string>\
+<key>body</key><string>build "
+            .to_vec();
+        xml.extend_from_slice(&[0xff, 0xfe]);
+        xml.extend_from_slice(b" failed</string></dict></plist>");
+
+        let parsed = parse_notification_plist(&xml);
+
+        assert_eq!(parsed.title, "Deploy");
+        assert!(parsed.body.starts_with("build "));
+        assert!(parsed.body.ends_with(" failed"));
+        assert!(parsed.body.contains('\u{FFFD}'));
+    }
+
+    #[test]
+    fn data_valued_fields_are_decoded_lossily() {
+        let mut dict = Dictionary::new();
+        dict.insert("titl".to_string(), PlistValue::String("ok".to_string()));
+        dict.insert("body".to_string(), PlistValue::Data(vec![b'h', b'i', 0xff]));
+        let mut buf = Vec::new();
+        PlistValue::Dictionary(dict)
+            .to_writer_binary(&mut buf)
+            .unwrap();
+
+        let parsed = parse_notification_plist(&buf);
+
+        assert_eq!(parsed.title, "ok");
+        assert_eq!(parsed.body, "hi\u{FFFD}");
+    }
+
+    #[test]
+    fn thread_identifier_is_read_from_the_request() {
+        let mut request = Dictionary::new();
+        request.insert("titl".to_string(), PlistValue::String("hi".to_string()));
+        request.insert("thre".to_string(), PlistValue::String("C123".to_string()));
+        let mut dict = Dictionary::new();
+        dict.insert("req".to_string(), PlistValue::Dictionary(request));
+        let mut buf = Vec::new();
+        PlistValue::Dictionary(dict)
+            .to_writer_binary(&mut buf)
+            .unwrap();
+
+        assert_eq!(
+            parse_notification_plist(&buf).thread_id.as_deref(),
+            Some("C123")
+        );
+        assert_eq!(parse_notification_plist(&plist_blob("n")).thread_id, None);
+    }
+
+    #[test]
+    fn request_identifier_is_read_from_the_request() {
+        let mut request = Dictionary::new();
+        request.insert("iden".to_string(), PlistValue::String("dl-42".to_string()));
+        request.insert("body".to_string(), PlistValue::String("45%".to_string()));
+        let mut dict = Dictionary::new();
+        dict.insert("req".to_string(), PlistValue::Dictionary(request));
+        let mut buf = Vec::new();
+        PlistValue::Dictionary(dict)
+            .to_writer_binary(&mut buf)
+            .unwrap();
+
+        let parsed = parse_notification_plist(&buf);
+        assert_eq!(parsed.request_id.as_deref(), Some("dl-42"));
+        assert_eq!(parsed.body, "45%");
+        assert_eq!(parse_notification_plist(&plist_blob("n")).request_id, None);
+    }
+
+    #[test]
+    fn category_is_read_from_the_request() {
+        let mut request = Dictionary::new();
+        request.insert("titl".to_string(), PlistValue::String("hi".to_string()));
+        request.insert(
+            "cate".to_string(),
+            PlistValue::String("MESSAGE_CATEGORY".to_string()),
+        );
+        let mut dict = Dictionary::new();
+        dict.insert("req".to_string(), PlistValue::Dictionary(request));
+        let mut buf = Vec::new();
+        PlistValue::Dictionary(dict)
+            .to_writer_binary(&mut buf)
+            .unwrap();
+
+        assert_eq!(
+            parse_notification_plist(&buf).category.as_deref(),
+            Some("MESSAGE_CATEGORY")
+        );
+        assert_eq!(parse_notification_plist(&plist_blob("n")).category, None);
+    }
+
+    #[test]
+    fn badge_is_read_from_the_request() {
+        let mut request = Dictionary::new();
+        request.insert("titl".to_string(), PlistValue::String("hi".to_string()));
+        request.insert("badg".to_string(), PlistValue::Integer(42.into()));
+        let mut dict = Dictionary::new();
+        dict.insert("req".to_string(), PlistValue::Dictionary(request));
+        let mut buf = Vec::new();
+        PlistValue::Dictionary(dict)
+            .to_writer_binary(&mut buf)
+            .unwrap();
+
+        let parsed = parse_notification_plist(&buf);
+        assert_eq!(parsed.badge, Some(42));
+        assert_eq!(parse_notification_plist(&plist_blob("n")).badge, None);
+    }
+
+    #[test]
+    fn critical_sound_flag_is_read_from_either_level() {
+        let sound = |crit: PlistValue| {
+            let mut sound = Dictionary::new();
+            sound.insert("crit".to_string(), crit);
+            PlistValue::Dictionary(sound)
+        };
+        let blob = |dict: Dictionary| {
+            let mut buf = Vec::new();
+            PlistValue::Dictionary(dict)
+                .to_writer_binary(&mut buf)
+                .unwrap();
+            buf
+        };
+
+        let mut request = Dictionary::new();
+        request.insert(
+            "titl".to_string(),
+            PlistValue::String("Incident".to_string()),
+        );
+        request.insert("soun".to_string(), sound(PlistValue::Boolean(true)));
+        let mut nested = Dictionary::new();
+        nested.insert("req".to_string(), PlistValue::Dictionary(request));
+        assert!(parse_notification_plist(&blob(nested)).system_critical);
+
+        let mut top_level = Dictionary::new();
+        top_level.insert("soun".to_string(), sound(PlistValue::Integer(1.into())));
+        assert!(parse_notification_plist(&blob(top_level)).system_critical);
+
+        let mut off = Dictionary::new();
+        off.insert("soun".to_string(), sound(PlistValue::Boolean(false)));
+        assert!(!parse_notification_plist(&blob(off)).system_critical);
+        assert!(!parse_notification_plist(&plist_blob("n")).system_critical);
+    }
+
+    fn interruption_blob(level: PlistValue, nested: bool) -> Vec<u8> {
+        let mut dict = Dictionary::new();
+        if nested {
+            let mut request = Dictionary::new();
+            request.insert("intl".to_string(), level);
+            dict.insert("req".to_string(), PlistValue::Dictionary(request));
+        } else {
+            dict.insert("intl".to_string(), level);
+        }
+        let mut buf = Vec::new();
+        PlistValue::Dictionary(dict)
+            .to_writer_binary(&mut buf)
+            .unwrap();
+        buf
+    }
+
+    #[test]
+    fn interruption_level_is_read_for_each_level() {
+        let levels = [
+            (0, InterruptionLevel::Passive),
+            (1, InterruptionLevel::Active),
+            (2, InterruptionLevel::TimeSensitive),
+            (3, InterruptionLevel::Critical),
+        ];
+        for (raw, expected) in levels {
+            for nested in [false, true] {
+                let blob = interruption_blob(PlistValue::Integer(raw.into()), nested);
+                assert_eq!(
+                    parse_notification_plist(&blob).interruption_level,
+                    expected,
+                    "raw {raw}, nested {nested}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn missing_or_unknown_interruption_level_is_active() {
+        for level in [
+            PlistValue::Integer(7.into()),
+            PlistValue::Integer((-1).into()),
+            PlistValue::String("urgent".to_string()),
+            PlistValue::Boolean(true),
+        ] {
+            assert_eq!(
+                parse_notification_plist(&interruption_blob(level, true)).interruption_level,
+                InterruptionLevel::Active
+            );
+        }
+        assert_eq!(
+            parse_notification_plist(&plist_blob("n")).interruption_level,
+            InterruptionLevel::Active
+        );
+    }
+
+    #[test]
+    fn record_schema_converts_core_data_delivered_date() {
+        let path = fixture_path("record");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE app (app_id INTEGER PRIMARY KEY, identifier TEXT);
+             CREATE TABLE record (rec_id INTEGER PRIMARY KEY, app_id INTEGER, data BLOB, delivered_date REAL);
+             INSERT INTO app VALUES (1, 'com.example.app');",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO record VALUES (7, 1, ?, 700000000.5)",
+            params![plist_blob("hello")],
+        )
+        .unwrap();
+
+        let mut db = NotificationDb::new(path.clone());
+        let rows = db.read_new(0).unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].rowid, 7);
+        assert_eq!(rows[0].title, "hello");
+        assert_eq!(rows[0].timestamp, 700_000_000 + CORE_DATA_EPOCH_OFFSET);
+        assert_eq!(db.latest_rowid().unwrap(), 7);
+        assert_eq!(db.active_schema(), Some("record"));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn z_schema_converts_core_data_delivered_date() {
+        let path = fixture_path("z");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE ZNOTIFICATIONAPPENTRY (Z_PK INTEGER PRIMARY KEY, ZBUNDLEID TEXT);
+             CREATE TABLE ZNOTIFICATIONENTRY (Z_PK INTEGER PRIMARY KEY, ZAPP INTEGER, ZDATA BLOB, ZDELIVEREDDATE TIMESTAMP);
+             INSERT INTO ZNOTIFICATIONAPPENTRY VALUES (1, 'com.example.app');",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO ZNOTIFICATIONENTRY VALUES (3, 1, ?, 0)",
+            params![plist_blob("hi")],
+        )
+        .unwrap();
+
+        let mut db = NotificationDb::new(path.clone());
+        let rows = db.read_new(0).unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].bundle_id, "com.example.app");
+        assert_eq!(rows[0].timestamp, CORE_DATA_EPOCH_OFFSET);
+        assert_eq!(db.active_schema(), Some("Z"));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn missing_delivered_date_falls_back_to_now() {
+        let path = fixture_path("null-date");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE app (app_id INTEGER PRIMARY KEY, identifier TEXT);
+             CREATE TABLE record (rec_id INTEGER PRIMARY KEY, app_id INTEGER, data BLOB, delivered_date REAL);
+             INSERT INTO app VALUES (1, 'com.example.app');",
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO record VALUES (1, 1, ?, NULL)",
+            params![plist_blob("x")],
+        )
+        .unwrap();
+
+        let mut db = NotificationDb::new(path.clone());
+        let rows = db.read_new(0).unwrap();
+
+        assert!(rows[0].timestamp > CORE_DATA_EPOCH_OFFSET);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn read_recent_is_bounded_by_rows_and_time() {
+        let path = fixture_path("recent");
+        create_record_db(&path, &[1, 2, 3, 4]);
+        let conn = Connection::open(&path).unwrap();
+        for (rowid, delivered) in [(1, 100.0), (2, 500.0), (3, 600.0), (4, 700.0)] {
+            conn.execute(
+                "UPDATE record SET delivered_date = ? WHERE rec_id = ?",
+                params![delivered, rowid],
+            )
+            .unwrap();
+        }
+
+        let mut db = NotificationDb::new(path.clone());
+        let since = 550 + CORE_DATA_EPOCH_OFFSET;
+        let rowids: Vec<_> = db
+            .read_recent(since, 10)
+            .unwrap()
+            .iter()
+            .map(|n| n.rowid)
+            .collect();
+        assert_eq!(rowids, vec![3, 4]);
+
+        let rowids: Vec<_> = db
+            .read_recent(0, 1)
+            .unwrap()
+            .iter()
+            .map(|n| n.rowid)
+            .collect();
+        assert_eq!(rowids, vec![4]);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn detected_schema_is_cached_for_the_next_launch() {
+        let path = fixture_path("cache-write");
+        create_record_db(&path, &[1, 2]);
+        let config = cache_store("cache-write");
+
+        let mut db = NotificationDb::new(path.clone()).with_schema_cache(config.clone());
+        assert_eq!(db.active_schema(), None);
+        assert_eq!(db.latest_rowid().unwrap(), 2);
+
+        let fingerprint = cached_fingerprint(&config);
+        assert_eq!(fingerprint.schema, "record");
+        assert_eq!(fingerprint.tables, vec!["app", "record"]);
+        assert!(fingerprint.columns.contains(&"record.rec_id".to_string()));
+        assert_eq!(fingerprint.macos_version, macos_product_version());
+
+        let reloaded = NotificationDb::new(path.clone()).with_schema_cache(config);
+        assert_eq!(reloaded.active_schema(), Some("record"));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn stale_fingerprint_is_replaced_when_the_schema_changed() {
+        let path = fixture_path("cache-stale");
+        create_record_db(&path, &[1]);
+        let config = cache_store("cache-stale");
+        NotificationDb::new(path.clone())
+            .with_schema_cache(config.clone())
+            .latest_rowid()
+            .unwrap();
+
+        // The store comes back with the other layout; the cache still says "record".
+        let _ = std::fs::remove_file(&path);
+        create_z_db(&path);
+        let mut db = NotificationDb::new(path.clone()).with_schema_cache(config.clone());
+        assert_eq!(db.active_schema(), Some("record"));
+
+        let rows = db.read_new(0).unwrap();
+        assert_eq!(rows.iter().map(|n| n.rowid).collect::<Vec<_>>(), vec![7]);
+        assert_eq!(db.active_schema(), Some("Z"));
+        let fingerprint = cached_fingerprint(&config);
+        assert_eq!(fingerprint.schema, "Z");
+        assert!(fingerprint
+            .columns
+            .contains(&"ZNOTIFICATIONENTRY.ZDATA".to_string()));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn fingerprint_from_another_macos_version_is_ignored() {
+        let path = fixture_path("cache-version");
+        create_record_db(&path, &[1]);
+        let config = cache_store("cache-version");
+        let stale = SchemaFingerprint {
+            schema: "record".to_string(),
+            tables: vec!["app".to_string(), "record".to_string()],
+            columns: Vec::new(),
+            macos_version: format!("{}-previous", macos_product_version()),
+        };
+        config.save(ConfigFile::SchemaCache, &stale).unwrap();
+
+        let mut db = NotificationDb::new(path.clone()).with_schema_cache(config.clone());
+        assert_eq!(db.active_schema(), None);
+        db.latest_rowid().unwrap();
+        assert_eq!(
+            cached_fingerprint(&config).macos_version,
+            macos_product_version()
+        );
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(feature = "fixture-db")]
+    #[test]
+    fn fixture_db_env_bypasses_the_macos_gate() {
+        let path = fixture_path("fixture-env");
+        create_record_db(&path, &[1]);
+        let resolved = super::notification_db_path(Some(path.clone().into()));
+
+        assert_eq!(resolved.unwrap(), path);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(feature = "fixture-db")]
+    #[test]
+    fn pinned_schema_reads_each_layout_without_probing() {
+        let z = fixture_path("pinned-z");
+        create_z_db(&z);
+        let record = fixture_path("pinned-record");
+        create_record_db(&record, &[4, 5]);
+
+        let mut db = NotificationDb::new(z.clone()).with_schema("Z").unwrap();
+        let rows = db.read_new(0).unwrap();
+        assert_eq!((rows.len(), rows[0].title.as_str()), (1, "z"));
+
+        let mut db = NotificationDb::new(record.clone())
+            .with_schema("record")
+            .unwrap();
+        let mut last_rowid = None;
+        assert!(db.poll_new(&mut last_rowid).unwrap().is_empty());
+        assert_eq!(last_rowid, Some(5));
+        let rows = db.read_new(3).unwrap();
+        assert_eq!(rows.iter().map(|n| n.rowid).collect::<Vec<_>>(), [4, 5]);
+        assert!(rows.iter().all(|n| n.title == "n"));
+
+        // A pinned schema that does not match is an error, not a re-probe.
+        let mut db = NotificationDb::new(record.clone())
+            .with_schema("Z")
+            .unwrap();
+        assert!(db.read_new(0).is_err());
+        assert_eq!(db.active_schema(), Some("Z"));
+        assert!(NotificationDb::new(record.clone())
+            .with_schema("ZZ")
+            .is_err());
+
+        let _ = std::fs::remove_file(z);
+        let _ = std::fs::remove_file(record);
+    }
 }
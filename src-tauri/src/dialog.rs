@@ -0,0 +1,317 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::llm::truncate_chars;
+use crate::models::{AnalyzedNotification, UrgencyLevel};
+
+pub const OPEN_APP_BUTTON: &str = "アプリを開く";
+/// The reason is LLM output; keep the dialog from growing past the screen.
+const MAX_DIALOG_REASON_CHARS: usize = 200;
+/// `{body}` in a dialog template is cut here for the same reason.
+const MAX_DIALOG_BODY_CHARS: usize = 500;
+pub const DEFAULT_DIALOG_TITLE_TEMPLATE: &str = "{urgency}: {app}";
+pub const DEFAULT_FOCUS_END_TITLE_TEMPLATE: &str = "集中モード終了";
+pub const DEFAULT_FOCUS_END_MESSAGE_TEMPLATE: &str = "{summary}";
+
+/// Text of the interrupting dialog and the focus-end banner, with `{app}`,
+/// `{title}`, `{body}`, `{summary}`, `{reason}`, `{urgency}` and `{count}`
+/// placeholders. Placeholders that do not apply (e.g. `{app}` in the
+/// banner) are left empty.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MessageTemplates {
+    pub dialog_title: String,
+    /// Unset keeps the built-in layout: the summary, the reason, and a
+    /// pointer to the panel when there is a body.
+    pub dialog_message: Option<String>,
+    pub focus_end_title: String,
+    pub focus_end_message: String,
+}
+
+impl Default for MessageTemplates {
+    fn default() -> Self {
+        Self {
+            dialog_title: DEFAULT_DIALOG_TITLE_TEMPLATE.to_string(),
+            dialog_message: None,
+            focus_end_title: DEFAULT_FOCUS_END_TITLE_TEMPLATE.to_string(),
+            focus_end_message: DEFAULT_FOCUS_END_MESSAGE_TEMPLATE.to_string(),
+        }
+    }
+}
+
+/// Values for the placeholders of [`MessageTemplates`].
+#[derive(Debug, Default)]
+pub struct TemplateValues<'a> {
+    pub app: &'a str,
+    pub title: &'a str,
+    pub body: &'a str,
+    pub summary: &'a str,
+    pub reason: &'a str,
+    pub urgency: &'a str,
+    pub count: usize,
+}
+
+/// Replaces known `{name}` placeholders in one pass, so braces inside the
+/// substituted text are left alone. Unknown names are kept as written.
+pub fn render_template(template: &str, values: &TemplateValues) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        let value = match &after[..end] {
+            "app" => values.app.to_string(),
+            "title" => values.title.to_string(),
+            "body" => values.body.to_string(),
+            "summary" => values.summary.to_string(),
+            "reason" => values.reason.to_string(),
+            "urgency" => values.urgency.to_string(),
+            "count" => values.count.to_string(),
+            _ => {
+                out.push('{');
+                rest = after;
+                continue;
+            }
+        };
+        out.push_str(&value);
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// What the interrupting dialog shows for one notification. The raw body is
+/// left to the main window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogContent {
+    pub title: String,
+    pub message: String,
+    /// An `.icns` file for the sender's icon, when one could be found.
+    pub icon: Option<PathBuf>,
+}
+
+/// `count` is the number of dialogs shown together with this one.
+pub fn notification_dialog_content(
+    notification: &AnalyzedNotification,
+    app_name: &str,
+    icon: Option<PathBuf>,
+    templates: &MessageTemplates,
+    count: usize,
+) -> DialogContent {
+    let headline = [&notification.summary_line, &notification.title]
+        .into_iter()
+        .map(|text| text.trim())
+        .find(|text| !text.is_empty())
+        .unwrap_or("(内容なし)");
+    let reason = truncate_chars(notification.reason.trim(), MAX_DIALOG_REASON_CHARS);
+    let body = truncate_chars(notification.body.trim(), MAX_DIALOG_BODY_CHARS);
+    let values = TemplateValues {
+        app: app_name,
+        title: &notification.title,
+        body: &body,
+        summary: headline,
+        reason: &reason,
+        urgency: urgency_word(notification.urgency),
+        count,
+    };
+
+    let message = match &templates.dialog_message {
+        Some(template) => render_template(template, &values),
+        None => {
+            let mut message = headline.to_string();
+            if !reason.is_empty() {
+                message.push_str("\n\n理由: ");
+                message.push_str(&reason);
+            }
+            if !body.is_empty() {
+                message.push_str("\n\n本文は Notify のパネルで確認できます。");
+            }
+            message
+        }
+    };
+
+    DialogContent {
+        title: render_template(&templates.dialog_title, &values),
+        message,
+        icon,
+    }
+}
+
+/// Title and message of the banner shown when a focus session ends.
+pub fn focus_end_banner(
+    templates: &MessageTemplates,
+    summary: &str,
+    count: usize,
+) -> (String, String) {
+    let values = TemplateValues {
+        summary,
+        count,
+        ..TemplateValues::default()
+    };
+    (
+        render_template(&templates.focus_end_title, &values),
+        render_template(&templates.focus_end_message, &values),
+    )
+}
+
+/// `display dialog` script for `content`, with OK and `OPEN_APP_BUTTON`.
+pub fn dialog_script(content: &DialogContent) -> String {
+    let icon = content
+        .icon
+        .as_ref()
+        .map(|path| {
+            format!(
+                " with icon (POSIX file \"{}\")",
+                escape_applescript(&path.to_string_lossy())
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        "display dialog \"{}\" with title \"{}\" buttons {{\"OK\", \"{OPEN_APP_BUTTON}\"}} default button \"OK\"{icon}",
+        escape_applescript(&content.message),
+        escape_applescript(&content.title)
+    )
+}
+
+pub fn escape_applescript(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn urgency_word(urgency: UrgencyLevel) -> &'static str {
+    match urgency {
+        UrgencyLevel::Critical => "緊急",
+        UrgencyLevel::High => "高",
+        UrgencyLevel::Medium => "中",
+        UrgencyLevel::Low => "低",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{
+        dialog_script, focus_end_banner, notification_dialog_content, render_template,
+        DialogContent, MessageTemplates, TemplateValues,
+    };
+    use crate::models::{AnalyzedNotification, UrgencyLevel};
+
+    fn critical(summary_line: &str, reason: &str, body: &str) -> AnalyzedNotification {
+        AnalyzedNotification {
+            id: 1,
+            title: "#incident".to_string(),
+            body: body.to_string(),
+            bundle_id: "com.tinyspeck.slackmacgap".to_string(),
+            app_name: "Slack".to_string(),
+            urgency: UrgencyLevel::Critical,
+            summary_line: summary_line.to_string(),
+            reason: reason.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn dialog_names_the_app_and_shows_the_summary_instead_of_the_body() {
+        let body = "ログ ".repeat(500);
+        let content = notification_dialog_content(
+            &critical("本番DBのCPU使用率が95%超過", "本番障害の可能性", &body),
+            "Slack",
+            None,
+            &MessageTemplates::default(),
+            1,
+        );
+
+        assert_eq!(
+            content,
+            DialogContent {
+                title: "緊急: Slack".to_string(),
+                message: "本番DBのCPU使用率が95%超過\n\n理由: 本番障害の可能性\n\n\
+                          本文は Notify のパネルで確認できます。"
+                    .to_string(),
+                icon: None,
+            }
+        );
+    }
+
+    #[test]
+    fn missing_summary_falls_back_to_the_title_and_long_reasons_are_cut() {
+        let mut notification = critical(" ", &"あ".repeat(300), "");
+        notification.urgency = UrgencyLevel::High;
+
+        let content = notification_dialog_content(
+            &notification,
+            "Slack",
+            None,
+            &MessageTemplates::default(),
+            1,
+        );
+
+        assert_eq!(content.title, "高: Slack");
+        let (headline, reason) = content.message.split_once("\n\n理由: ").unwrap();
+        assert_eq!(headline, "#incident");
+        assert_eq!(reason.chars().count(), 201);
+        assert!(!content.message.contains("パネル"));
+    }
+
+    #[test]
+    fn placeholders_are_substituted_once_and_unknown_ones_kept() {
+        let values = TemplateValues {
+            app: "Slack",
+            summary: "{app} が落ちた",
+            count: 3,
+            ..TemplateValues::default()
+        };
+        assert_eq!(
+            render_template("[{app}] {summary} ({count}) {other} {", &values),
+            "[Slack] {app} が落ちた (3) {other} {"
+        );
+
+        let templates = MessageTemplates {
+            dialog_title: "[{app}] {summary}".to_string(),
+            dialog_message: Some("{title}\n{body}\n{reason}".to_string()),
+            focus_end_title: "終了 ({count}件)".to_string(),
+            ..MessageTemplates::default()
+        };
+        let content = notification_dialog_content(
+            &critical("障害", "本番", "詳細"),
+            "Slack",
+            None,
+            &templates,
+            2,
+        );
+        assert_eq!(content.title, "[Slack] 障害");
+        assert_eq!(content.message, "#incident\n詳細\n本番");
+        assert_eq!(
+            focus_end_banner(&templates, "まとめ", 4),
+            ("終了 (4件)".to_string(), "まとめ".to_string())
+        );
+    }
+
+    #[test]
+    fn script_escapes_text_and_adds_the_icon() {
+        let content = DialogContent {
+            title: "緊急: \"Ops\"".to_string(),
+            message: "C:\\path".to_string(),
+            icon: Some(PathBuf::from(
+                "/Applications/Ops.app/Contents/Resources/AppIcon.icns",
+            )),
+        };
+
+        assert_eq!(
+            dialog_script(&content),
+            "display dialog \"C:\\\\path\" with title \"緊急: \\\"Ops\\\"\" \
+             buttons {\"OK\", \"アプリを開く\"} default button \"OK\" \
+             with icon (POSIX file \"/Applications/Ops.app/Contents/Resources/AppIcon.icns\")"
+        );
+        assert!(!dialog_script(&DialogContent {
+            icon: None,
+            ..content
+        })
+        .contains("with icon"));
+    }
+}
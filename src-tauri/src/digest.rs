@@ -0,0 +1,117 @@
+use serde::Deserialize;
+
+use crate::models::{AnalyzedNotification, UrgencyLevel};
+
+const URGENCY_ORDER: [UrgencyLevel; 4] = [
+    UrgencyLevel::Critical,
+    UrgencyLevel::High,
+    UrgencyLevel::Medium,
+    UrgencyLevel::Low,
+];
+
+/// Markup used for the text digest, depending on where it will be pasted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestFormat {
+    #[default]
+    Plain,
+    Slack,
+    Markdown,
+}
+
+fn urgency_emoji(urgency: UrgencyLevel) -> &'static str {
+    match urgency {
+        UrgencyLevel::Critical => "🔴",
+        UrgencyLevel::High => "🟠",
+        UrgencyLevel::Medium => "🟡",
+        UrgencyLevel::Low => "🟢",
+    }
+}
+
+/// Builds a text digest grouped by urgency (most urgent first, newest first within a tier).
+pub fn build_digest(items: &[AnalyzedNotification], format: DigestFormat) -> String {
+    let mut sections = Vec::new();
+
+    for urgency in URGENCY_ORDER {
+        let mut tier: Vec<&AnalyzedNotification> =
+            items.iter().filter(|n| n.urgency == urgency).collect();
+        if tier.is_empty() {
+            continue;
+        }
+        tier.sort_by_key(|n| std::cmp::Reverse(n.timestamp));
+
+        let label = urgency.label();
+        let emoji = urgency_emoji(urgency);
+        let count = tier.len();
+        let mut lines = vec![match format {
+            DigestFormat::Plain => format!("{label} ({count})"),
+            DigestFormat::Slack => format!("{emoji} *{label}* ({count})"),
+            DigestFormat::Markdown => format!("## {emoji} {label} ({count})"),
+        }];
+        for n in tier {
+            lines.push(match format {
+                DigestFormat::Plain => format!("- [{}] {}", n.app_name, n.summary_line),
+                DigestFormat::Slack => format!("• *{}*: {}", n.app_name, n.summary_line),
+                DigestFormat::Markdown => format!("- **{}**: {}", n.app_name, n.summary_line),
+            });
+        }
+        sections.push(lines.join("\n"));
+    }
+
+    if sections.is_empty() {
+        return "通知はありません".to_string();
+    }
+    sections.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_digest, DigestFormat};
+    use crate::models::{AnalyzedNotification, UrgencyLevel};
+
+    fn sample(app_name: &str, urgency: UrgencyLevel, summary: &str) -> AnalyzedNotification {
+        AnalyzedNotification {
+            id: 1,
+            app_name: app_name.to_string(),
+            urgency,
+            summary_line: summary.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn items() -> Vec<AnalyzedNotification> {
+        vec![
+            sample("Mail", UrgencyLevel::Low, "請求書"),
+            sample("Slack", UrgencyLevel::Critical, "本番障害"),
+        ]
+    }
+
+    #[test]
+    fn plain_has_no_markup() {
+        assert_eq!(
+            build_digest(&items(), DigestFormat::Plain),
+            "URGENT (1)\n- [Slack] 本番障害\n\nLOW (1)\n- [Mail] 請求書"
+        );
+    }
+
+    #[test]
+    fn slack_uses_mrkdwn_bold_and_emoji() {
+        assert_eq!(
+            build_digest(&items(), DigestFormat::Slack),
+            "🔴 *URGENT* (1)\n• *Slack*: 本番障害\n\n🟢 *LOW* (1)\n• *Mail*: 請求書"
+        );
+    }
+
+    #[test]
+    fn markdown_uses_headings_and_double_asterisks() {
+        assert_eq!(
+            build_digest(&items(), DigestFormat::Markdown),
+            "## 🔴 URGENT (1)\n- **Slack**: 本番障害\n\n## 🟢 LOW (1)\n- **Mail**: 請求書"
+        );
+    }
+
+    #[test]
+    fn empty_digest_is_explicit() {
+        assert_eq!(build_digest(&[], DigestFormat::Slack), "通知はありません");
+    }
+}
@@ -1,51 +1,306 @@
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
 
+use chrono::{Duration, Local, NaiveDateTime, NaiveTime, TimeZone};
 use log::warn;
+use serde::Serialize;
 use serde_json::Value;
 
+use crate::db::core_data_to_unix;
 use crate::models::FocusState;
 
+/// `assertionDetailsReason` of a focus turned on by hand.
+const MANUAL_REASON: &str = "user-action";
+/// macOS rewrites `Assertions.json` in place, so a read can land mid-write.
+const PARSE_RETRIES: usize = 2;
+const PARSE_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(20);
+/// Assertions older than this are probably left over from a crashed app.
+pub const DEFAULT_STALE_ASSERTION_SECS: i64 = 12 * 60 * 60;
+
+/// How an assertion was started. Manual assertions outrank scheduled ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AssertionOrigin {
+    /// Schedules and other automatic triggers.
+    Scheduled,
+    Manual,
+}
+
+/// One record from `storeAssertionRecords`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusAssertion {
+    pub mode_id: Option<String>,
+    pub origin: AssertionOrigin,
+    /// `assertionStartDateTimestamp` (Core Data seconds), when present.
+    pub started_at: Option<f64>,
+}
+
+/// Where the focus state was read from. Sources are tried in declaration
+/// order and the first one present on disk decides.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FocusSource {
+    /// `Assertions.json`, which lists the active assertions.
+    Assertions,
+    /// Schedule triggers in `ModeConfigurations.json` covering the current
+    /// time. Misses focus turned on by hand.
+    ModeSchedules,
+    /// The `donotdisturbd` preferences plist, read through `plutil`.
+    DndPreferences,
+    /// None of the files exist; focus is reported inactive.
+    #[default]
+    None,
+}
+
+/// An assertion that has been active for longer than the stale threshold.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StaleAssertion {
+    #[serde(flatten)]
+    pub assertion: FocusAssertion,
+    /// Unix time the assertion started.
+    pub started_at_unix: i64,
+    pub age_secs: i64,
+}
+
+/// Result of `get_stale_assertions`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StaleAssertionReport {
+    pub threshold_secs: i64,
+    /// Whether focus held up only by these assertions counts as inactive.
+    pub treated_as_inactive: bool,
+    pub assertions: Vec<StaleAssertion>,
+    /// Shown to the user when any assertion is stale.
+    pub warning: Option<String>,
+}
+
+impl StaleAssertionReport {
+    pub fn new(
+        assertions: Vec<StaleAssertion>,
+        threshold_secs: i64,
+        treated_as_inactive: bool,
+    ) -> Self {
+        let warning = (!assertions.is_empty()).then(|| {
+            format!(
+                "{}件の集中モードが{}以上続いています。終了したアプリが残した可能性があります。",
+                assertions.len(),
+                threshold_label(threshold_secs)
+            )
+        });
+        Self {
+            threshold_secs,
+            treated_as_inactive,
+            assertions,
+            warning,
+        }
+    }
+}
+
+/// `secs` in the largest unit that divides it, so a threshold under an hour
+/// does not read as 「0時間」.
+fn threshold_label(secs: i64) -> String {
+    match secs {
+        s if s >= 3600 && s % 3600 == 0 => format!("{}時間", s / 3600),
+        s if s >= 3600 => format!("{}時間{}分", s / 3600, s % 3600 / 60),
+        s if s >= 60 => format!("{}分", s / 60),
+        s => format!("{s}秒"),
+    }
+}
+
 pub struct FocusModeDetector {
     assertions_path: PathBuf,
+    mode_configurations_path: PathBuf,
+    dnd_preferences_path: PathBuf,
 }
 
 impl FocusModeDetector {
-    pub fn new(assertions_path: PathBuf) -> Self {
-        Self { assertions_path }
+    pub fn new(assertions_path: PathBuf, dnd_preferences_path: PathBuf) -> Self {
+        let mode_configurations_path = assertions_path.with_file_name("ModeConfigurations.json");
+        Self::with_paths(
+            assertions_path,
+            mode_configurations_path,
+            dnd_preferences_path,
+        )
+    }
+
+    pub fn with_paths(
+        assertions_path: PathBuf,
+        mode_configurations_path: PathBuf,
+        dnd_preferences_path: PathBuf,
+    ) -> Self {
+        Self {
+            assertions_path,
+            mode_configurations_path,
+            dnd_preferences_path,
+        }
+    }
+
+    /// Unix time at which the active focus mode's schedule ends, if the active
+    /// mode was started by a schedule that covers the current time.
+    pub fn scheduled_end(&self) -> Option<i64> {
+        let mode_id = self.effective_mode()?;
+        let configurations = read_json(&self.mode_configurations_path)?;
+        let now = Local::now().naive_local();
+        let end = scheduled_end_for_mode(&configurations, &mode_id, now)?;
+        Local
+            .from_local_datetime(&end)
+            .earliest()
+            .map(|dt| dt.timestamp())
+    }
+
+    /// Every active assertion, in file order.
+    pub fn assertions(&self) -> Vec<FocusAssertion> {
+        read_json(&self.assertions_path)
+            .map(|assertions| parse_assertions(&assertions))
+            .unwrap_or_default()
+    }
+
+    /// The mode that applies when several assertions overlap.
+    pub fn effective_mode(&self) -> Option<String> {
+        effective_assertion(&self.assertions())?.mode_id.clone()
     }
 
-    pub fn get_state(&self) -> FocusState {
-        let text = match std::fs::read_to_string(&self.assertions_path) {
+    /// The focus state and the source it was read from. With
+    /// `ignore_stale_after`, assertions that are all older than that many
+    /// seconds count as inactive.
+    pub fn detect(&self, ignore_stale_after: Option<i64>) -> (FocusState, FocusSource) {
+        let now = Local::now();
+        if let Some(read) = read_assertions_file(&self.assertions_path) {
+            let mut state = assertions_state(&read);
+            let stale_check = ignore_stale_after.filter(|_| state == FocusState::Active);
+            if let (Ok(data), Some(threshold)) = (&read, stale_check) {
+                let assertions = parse_assertions(data);
+                let stale = stale_assertions(&assertions, now.timestamp(), threshold);
+                if !stale.is_empty() && stale.len() == assertions.len() {
+                    warn!(
+                        "Ignoring {} stale focus assertion(s) older than {threshold}s",
+                        stale.len()
+                    );
+                    state = FocusState::Inactive;
+                }
+            }
+            (state, FocusSource::Assertions)
+        } else if let Some(state) =
+            schedule_state(&self.mode_configurations_path, now.naive_local())
+        {
+            (state, FocusSource::ModeSchedules)
+        } else if let Some(state) = dnd_preferences_state(&self.dnd_preferences_path) {
+            (state, FocusSource::DndPreferences)
+        } else {
+            (FocusState::Inactive, FocusSource::None)
+        }
+    }
+}
+
+/// `None` when there is no assertions file to read.
+fn read_assertions_file(path: &Path) -> Option<Result<Value, FocusState>> {
+    path.exists().then(|| {
+        read_assertions(
+            || std::fs::read_to_string(path),
+            || thread::sleep(PARSE_RETRY_DELAY),
+            path,
+        )
+    })
+}
+
+/// The focus state a `read_assertions` result stands for.
+fn assertions_state(read: &Result<Value, FocusState>) -> FocusState {
+    match read {
+        Ok(data) if is_focus_active(data) => FocusState::Active,
+        Ok(_) => FocusState::Inactive,
+        Err(state) => *state,
+    }
+}
+
+/// Active when any enabled schedule covers `now`. `None` when the
+/// configurations cannot be read.
+fn schedule_state(path: &Path, now: NaiveDateTime) -> Option<FocusState> {
+    let configurations = read_json(path)?;
+    let active = mode_configurations(&configurations)
+        .flat_map(schedule_triggers)
+        .any(|trigger| schedule_window_end(trigger, now).is_some());
+    Some(if active {
+        FocusState::Active
+    } else {
+        FocusState::Inactive
+    })
+}
+
+/// `None` when the plist is missing or `plutil` cannot convert it.
+fn dnd_preferences_state(path: &Path) -> Option<FocusState> {
+    if !path.exists() {
+        return None;
+    }
+    let output = match Command::new("plutil")
+        .args(["-convert", "json", "-o", "-"])
+        .arg(path)
+        .output()
+    {
+        Ok(output) if output.status.success() => output.stdout,
+        Ok(output) => {
+            warn!(
+                "plutil cannot convert {}: {}",
+                path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            return None;
+        }
+        Err(err) => {
+            warn!("Cannot run plutil for {}: {}", path.display(), err);
+            return None;
+        }
+    };
+    let preferences = serde_json::from_slice(&output).ok()?;
+    Some(match dnd_preferences_active(&preferences)? {
+        true => FocusState::Active,
+        false => FocusState::Inactive,
+    })
+}
+
+/// Reads the DND flag from the preferences, either the top-level
+/// `doNotDisturb` or `userPref.enabled`.
+fn dnd_preferences_active(preferences: &Value) -> Option<bool> {
+    preferences
+        .get("doNotDisturb")
+        .or_else(|| preferences.get("userPref")?.get("enabled"))
+        .and_then(Value::as_bool)
+}
+
+/// Reads and parses the assertions through `read`, calling `wait` before each
+/// retry of a parse failure. On failure, `Err` holds the state to report: a
+/// file that never parses is `Unknown`, not `Inactive`, so a partial write
+/// cannot end a focus session.
+fn read_assertions(
+    mut read: impl FnMut() -> std::io::Result<String>,
+    mut wait: impl FnMut(),
+    path: &Path,
+) -> Result<Value, FocusState> {
+    for attempt in 0..=PARSE_RETRIES {
+        let text = match read() {
             Ok(text) => text,
             Err(err) => {
-                warn!(
-                    "Cannot read focus assertions: {} ({})",
-                    self.assertions_path.display(),
-                    err
-                );
-                return FocusState::Inactive;
+                warn!("Cannot read focus assertions: {} ({})", path.display(), err);
+                return Err(FocusState::Inactive);
             }
         };
 
-        let data: Value = match serde_json::from_str(&text) {
-            Ok(data) => data,
-            Err(err) => {
+        match serde_json::from_str::<Value>(&text) {
+            Ok(data) => return Ok(data),
+            Err(err) if attempt == PARSE_RETRIES => {
                 warn!(
                     "Cannot parse focus assertions JSON: {} ({})",
-                    self.assertions_path.display(),
+                    path.display(),
                     err
                 );
-                return FocusState::Inactive;
             }
-        };
-
-        if is_focus_active(&data) {
-            FocusState::Active
-        } else {
-            FocusState::Inactive
+            Err(_) => wait(),
         }
     }
+    Err(FocusState::Unknown)
 }
 
 fn is_focus_active(data: &Value) -> bool {
@@ -62,6 +317,151 @@ fn is_focus_active(data: &Value) -> bool {
         .unwrap_or(false)
 }
 
+fn read_json(path: &Path) -> Option<Value> {
+    let text = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+fn parse_assertions(assertions: &Value) -> Vec<FocusAssertion> {
+    let Some(records) = assertions.get("data").and_then(Value::as_array) else {
+        return Vec::new();
+    };
+    records
+        .iter()
+        .filter_map(|record| record.get("storeAssertionRecords")?.as_array())
+        .flatten()
+        .map(|assertion| {
+            let details = assertion.get("assertionDetails");
+            let detail = |key: &str| details.and_then(|d| d.get(key)).and_then(Value::as_str);
+            FocusAssertion {
+                mode_id: detail("assertionDetailsModeIdentifier").map(ToString::to_string),
+                origin: if detail("assertionDetailsReason") == Some(MANUAL_REASON) {
+                    AssertionOrigin::Manual
+                } else {
+                    AssertionOrigin::Scheduled
+                },
+                started_at: assertion
+                    .get("assertionStartDateTimestamp")
+                    .and_then(Value::as_f64),
+            }
+        })
+        .collect()
+}
+
+/// Assertions that started more than `threshold_secs` before `now`.
+/// Undated ones are never flagged.
+pub fn stale_assertions(
+    assertions: &[FocusAssertion],
+    now: i64,
+    threshold_secs: i64,
+) -> Vec<StaleAssertion> {
+    assertions
+        .iter()
+        .filter_map(|assertion| {
+            let started_at_unix = core_data_to_unix(assertion.started_at?);
+            let age_secs = now - started_at_unix;
+            (age_secs > threshold_secs).then(|| StaleAssertion {
+                assertion: assertion.clone(),
+                started_at_unix,
+                age_secs,
+            })
+        })
+        .collect()
+}
+
+/// Picks the assertion that decides the focus mode: manual over scheduled,
+/// then the newest. Assertions without a mode are skipped; on a full tie the
+/// earlier record wins.
+pub fn effective_assertion(assertions: &[FocusAssertion]) -> Option<&FocusAssertion> {
+    assertions
+        .iter()
+        .filter(|assertion| assertion.mode_id.is_some())
+        .reduce(|best, candidate| {
+            let started = |a: &FocusAssertion| a.started_at.unwrap_or(f64::NEG_INFINITY);
+            let ordering = candidate
+                .origin
+                .cmp(&best.origin)
+                .then(started(candidate).total_cmp(&started(best)));
+            if ordering.is_gt() {
+                candidate
+            } else {
+                best
+            }
+        })
+}
+
+/// Finds the end of the schedule window of `mode_id` that contains `now`.
+/// Windows whose end is before their start run overnight.
+fn scheduled_end_for_mode(
+    configurations: &Value,
+    mode_id: &str,
+    now: NaiveDateTime,
+) -> Option<NaiveDateTime> {
+    let configuration = configurations
+        .get("data")?
+        .as_array()?
+        .iter()
+        .find_map(|record| record.get("modeConfigurations")?.get(mode_id))?;
+    schedule_triggers(configuration).find_map(|trigger| schedule_window_end(trigger, now))
+}
+
+/// Every mode configuration, across all records.
+fn mode_configurations(configurations: &Value) -> impl Iterator<Item = &Value> {
+    configurations
+        .get("data")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|record| record.get("modeConfigurations")?.as_object())
+        .flat_map(|modes| modes.values())
+}
+
+/// The schedule triggers of one mode. `enabledSetting` 1 marks a schedule
+/// switched off in System Settings.
+fn schedule_triggers(configuration: &Value) -> impl Iterator<Item = &Value> {
+    configuration
+        .get("triggers")
+        .and_then(|triggers| triggers.get("triggers"))
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|trigger| {
+            trigger
+                .get("class")
+                .and_then(Value::as_str)
+                .is_some_and(|class| class.contains("Schedule"))
+                && trigger.get("enabledSetting").and_then(Value::as_u64) != Some(1)
+        })
+}
+
+/// End of the trigger's window if it contains `now`.
+fn schedule_window_end(trigger: &Value, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let start = trigger_time(
+        trigger,
+        "timePeriodStartTimeHour",
+        "timePeriodStartTimeMinute",
+    )?;
+    let end = trigger_time(trigger, "timePeriodEndTimeHour", "timePeriodEndTimeMinute")?;
+    let today = now.date();
+    let time = now.time();
+
+    if start < end {
+        (start <= time && time < end).then(|| today.and_time(end))
+    } else if time >= start {
+        Some((today + Duration::days(1)).and_time(end))
+    } else if time < end {
+        Some(today.and_time(end))
+    } else {
+        None
+    }
+}
+
+fn trigger_time(trigger: &Value, hour_key: &str, minute_key: &str) -> Option<NaiveTime> {
+    let hour = trigger.get(hour_key)?.as_u64()?;
+    let minute = trigger.get(minute_key)?.as_u64()?;
+    NaiveTime::from_hms_opt(hour as u32, minute as u32, 0)
+}
+
 pub fn get_focus_assertions_path() -> PathBuf {
     let home = env::var("HOME").unwrap_or_default();
     let primary = PathBuf::from(home)
@@ -76,3 +476,365 @@ pub fn get_focus_assertions_path() -> PathBuf {
 
     PathBuf::from("/Users/Shared/.FocusConfiguration/Assertions.json")
 }
+
+pub fn get_dnd_preferences_path() -> PathBuf {
+    let home = env::var("HOME").unwrap_or_default();
+    PathBuf::from(home)
+        .join("Library")
+        .join("Containers")
+        .join("com.apple.donotdisturbd")
+        .join("Data")
+        .join("Library")
+        .join("Preferences")
+        .join("com.apple.donotdisturbd.plist")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+    use serde_json::{json, Value};
+
+    use super::{
+        assertions_state, dnd_preferences_active, effective_assertion, parse_assertions,
+        read_assertions, schedule_state, scheduled_end_for_mode, stale_assertions, threshold_label,
+        AssertionOrigin, FocusAssertion, FocusModeDetector, FocusSource, StaleAssertionReport,
+        DEFAULT_STALE_ASSERTION_SECS, PARSE_RETRIES,
+    };
+    use crate::db::CORE_DATA_EPOCH_OFFSET;
+    use crate::models::FocusState;
+
+    const WORK: &str = "com.apple.focus.work";
+    const DRIVING: &str = "com.apple.focus.driving";
+    const SLEEP: &str = "com.apple.focus.sleep";
+
+    fn active_mode_identifier(assertions: &Value) -> Option<String> {
+        effective_assertion(&parse_assertions(assertions))?
+            .mode_id
+            .clone()
+    }
+
+    fn record(mode: &str, reason: &str, started_at: f64) -> Value {
+        json!({
+            "assertionStartDateTimestamp": started_at,
+            "assertionDetails": {
+                "assertionDetailsModeIdentifier": mode,
+                "assertionDetailsReason": reason
+            }
+        })
+    }
+
+    fn assertions(records: Vec<Value>) -> Value {
+        json!({ "data": [{ "storeAssertionRecords": records }] })
+    }
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2025, 3, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn configurations(start: (u64, u64), end: (u64, u64)) -> Value {
+        json!({
+            "data": [{
+                "modeConfigurations": {
+                    WORK: {
+                        "mode": { "modeIdentifier": WORK },
+                        "triggers": {
+                            "triggers": [{
+                                "class": "DNDModeConfigurationScheduleTrigger",
+                                "timePeriodStartTimeHour": start.0,
+                                "timePeriodStartTimeMinute": start.1,
+                                "timePeriodEndTimeHour": end.0,
+                                "timePeriodEndTimeMinute": end.1
+                            }]
+                        }
+                    }
+                }
+            }]
+        })
+    }
+
+    #[test]
+    fn sources_are_tried_in_order() {
+        let dir = std::env::temp_dir().join(format!("notify-focus-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let detector = FocusModeDetector::with_paths(
+            dir.join("Assertions.json"),
+            dir.join("ModeConfigurations.json"),
+            dir.join("com.apple.donotdisturbd.plist"),
+        );
+        assert_eq!(
+            detector.detect(None),
+            (FocusState::Inactive, FocusSource::None)
+        );
+
+        // 00:00–00:00 runs overnight, so it covers every time of day.
+        let all_day = configurations((0, 0), (0, 0)).to_string();
+        std::fs::write(dir.join("ModeConfigurations.json"), all_day).unwrap();
+        assert_eq!(
+            detector.detect(None),
+            (FocusState::Active, FocusSource::ModeSchedules)
+        );
+
+        std::fs::write(dir.join("Assertions.json"), r#"{"data":[]}"#).unwrap();
+        assert_eq!(
+            detector.detect(None),
+            (FocusState::Inactive, FocusSource::Assertions)
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn old_assertions_are_flagged_and_can_be_ignored() {
+        let now = chrono::Utc::now().timestamp();
+        let core_data = |age_secs: i64| (now - age_secs - CORE_DATA_EPOCH_OFFSET) as f64;
+        let day_old = record(WORK, "user-action", core_data(24 * 60 * 60));
+
+        let parsed = parse_assertions(&assertions(vec![
+            day_old.clone(),
+            record(SLEEP, "schedule", core_data(60)),
+        ]));
+        let stale = stale_assertions(&parsed, now, DEFAULT_STALE_ASSERTION_SECS);
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].assertion.mode_id.as_deref(), Some(WORK));
+        assert_eq!(stale[0].age_secs, 24 * 60 * 60);
+        let report = StaleAssertionReport::new(stale, DEFAULT_STALE_ASSERTION_SECS, false);
+        assert!(report
+            .warning
+            .unwrap()
+            .starts_with("1件の集中モードが12時間以上"));
+        assert_eq!(threshold_label(30 * 60), "30分");
+        assert_eq!(threshold_label(90 * 60), "1時間30分");
+
+        let dir =
+            std::env::temp_dir().join(format!("notify-focus-stale-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let detector = FocusModeDetector::with_paths(
+            dir.join("Assertions.json"),
+            dir.join("ModeConfigurations.json"),
+            dir.join("com.apple.donotdisturbd.plist"),
+        );
+        let stale_only = assertions(vec![day_old.clone()]).to_string();
+        std::fs::write(dir.join("Assertions.json"), stale_only).unwrap();
+        assert_eq!(detector.detect(None).0, FocusState::Active);
+        let ignore = Some(DEFAULT_STALE_ASSERTION_SECS);
+        assert_eq!(detector.detect(ignore).0, FocusState::Inactive);
+
+        // A fresh assertion alongside keeps focus active.
+        let mixed = assertions(vec![day_old, record(SLEEP, "schedule", core_data(60))]);
+        std::fs::write(dir.join("Assertions.json"), mixed.to_string()).unwrap();
+        assert_eq!(detector.detect(ignore).0, FocusState::Active);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn schedules_decide_state_unless_switched_off() {
+        let dir =
+            std::env::temp_dir().join(format!("notify-focus-schedule-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ModeConfigurations.json");
+
+        let mut config = configurations((9, 0), (12, 0));
+        std::fs::write(&path, config.to_string()).unwrap();
+        assert_eq!(
+            schedule_state(&path, at(10, 10, 0)),
+            Some(FocusState::Active)
+        );
+        assert_eq!(
+            schedule_state(&path, at(10, 13, 0)),
+            Some(FocusState::Inactive)
+        );
+
+        config["data"][0]["modeConfigurations"][WORK]["triggers"]["triggers"][0]
+            ["enabledSetting"] = json!(1);
+        std::fs::write(&path, config.to_string()).unwrap();
+        assert_eq!(
+            schedule_state(&path, at(10, 10, 0)),
+            Some(FocusState::Inactive)
+        );
+
+        std::fs::write(&path, "{").unwrap();
+        assert_eq!(schedule_state(&path, at(10, 10, 0)), None);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn dnd_preferences_flag_is_read_from_either_key() {
+        assert_eq!(
+            dnd_preferences_active(&json!({ "doNotDisturb": true })),
+            Some(true)
+        );
+        assert_eq!(
+            dnd_preferences_active(&json!({ "userPref": { "enabled": false } })),
+            Some(false)
+        );
+        assert_eq!(dnd_preferences_active(&json!({ "doNotDisturb": 1 })), None);
+        assert_eq!(dnd_preferences_active(&json!({})), None);
+    }
+
+    #[test]
+    fn partial_write_is_retried_before_deciding() {
+        let active = json!({
+            "data": [{ "storeAssertionRecords": [{ "assertionDetails": {} }] }]
+        })
+        .to_string();
+        let path = std::path::Path::new("Assertions.json");
+
+        let mut reads = vec![Ok(active[..active.len() / 2].to_string()), Ok(active)].into_iter();
+        let mut waits = 0;
+        let read = read_assertions(|| reads.next().unwrap(), || waits += 1, path);
+        assert_eq!((assertions_state(&read), waits), (FocusState::Active, 1));
+
+        let mut waits = 0;
+        let read = read_assertions(|| Ok(String::new()), || waits += 1, path);
+        assert_eq!((read, waits), (Err(FocusState::Unknown), PARSE_RETRIES));
+
+        let missing = || Err(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert_eq!(
+            read_assertions(missing, || {}, path),
+            Err(FocusState::Inactive)
+        );
+    }
+
+    #[test]
+    fn reads_active_mode_from_assertions() {
+        let assertions = json!({
+            "data": [{
+                "storeAssertionRecords": [{
+                    "assertionDetails": { "assertionDetailsModeIdentifier": WORK }
+                }]
+            }]
+        });
+        assert_eq!(active_mode_identifier(&assertions).as_deref(), Some(WORK));
+        assert_eq!(active_mode_identifier(&json!({ "data": [] })), None);
+    }
+
+    #[test]
+    fn all_overlapping_assertions_are_parsed() {
+        let parsed = parse_assertions(&assertions(vec![
+            record(WORK, "schedule", 760_000_000.0),
+            record(DRIVING, "user-action", 760_000_100.5),
+        ]));
+        assert_eq!(
+            parsed,
+            vec![
+                FocusAssertion {
+                    mode_id: Some(WORK.to_string()),
+                    origin: AssertionOrigin::Scheduled,
+                    started_at: Some(760_000_000.0),
+                },
+                FocusAssertion {
+                    mode_id: Some(DRIVING.to_string()),
+                    origin: AssertionOrigin::Manual,
+                    started_at: Some(760_000_100.5),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn manual_assertion_outranks_newer_scheduled_one() {
+        let overlapping = assertions(vec![
+            record(DRIVING, "user-action", 760_000_000.0),
+            record(WORK, "schedule", 760_000_500.0),
+        ]);
+        assert_eq!(
+            active_mode_identifier(&overlapping).as_deref(),
+            Some(DRIVING)
+        );
+    }
+
+    #[test]
+    fn newer_assertion_wins_within_the_same_origin() {
+        let scheduled = assertions(vec![
+            record(SLEEP, "schedule", 760_000_500.0),
+            record(WORK, "schedule", 760_000_000.0),
+        ]);
+        assert_eq!(active_mode_identifier(&scheduled).as_deref(), Some(SLEEP));
+
+        let manual = assertions(vec![
+            record(WORK, "user-action", 760_000_000.0),
+            record(DRIVING, "user-action", 760_000_500.0),
+            record(SLEEP, "schedule", 760_000_900.0),
+        ]);
+        assert_eq!(active_mode_identifier(&manual).as_deref(), Some(DRIVING));
+    }
+
+    #[test]
+    fn assertions_spread_over_several_records_are_ranked_together() {
+        let split = json!({
+            "data": [
+                { "storeAssertionRecords": [record(WORK, "schedule", 760_000_000.0)] },
+                { "storeAssertionRecords": false },
+                { "storeAssertionRecords": [record(DRIVING, "user-action", 759_000_000.0)] }
+            ]
+        });
+        assert_eq!(active_mode_identifier(&split).as_deref(), Some(DRIVING));
+    }
+
+    #[test]
+    fn incomplete_assertions_rank_last() {
+        let mut undated = record(SLEEP, "schedule", 0.0);
+        undated
+            .as_object_mut()
+            .unwrap()
+            .remove("assertionStartDateTimestamp");
+        let overlapping = assertions(vec![
+            json!({ "assertionDetails": { "assertionDetailsReason": "user-action" } }),
+            undated,
+            record(WORK, "schedule", 760_000_000.0),
+        ]);
+        assert_eq!(parse_assertions(&overlapping).len(), 3);
+        assert_eq!(active_mode_identifier(&overlapping).as_deref(), Some(WORK));
+    }
+
+    #[test]
+    fn daytime_schedule_ends_today() {
+        let config = configurations((9, 0), (12, 30));
+        assert_eq!(
+            scheduled_end_for_mode(&config, WORK, at(10, 11, 5)),
+            Some(at(10, 12, 30))
+        );
+    }
+
+    #[test]
+    fn outside_schedule_window_is_none() {
+        let config = configurations((9, 0), (12, 0));
+        assert_eq!(scheduled_end_for_mode(&config, WORK, at(10, 13, 0)), None);
+    }
+
+    #[test]
+    fn overnight_schedule_ends_next_morning() {
+        let config = configurations((22, 0), (7, 0));
+        assert_eq!(
+            scheduled_end_for_mode(&config, WORK, at(10, 23, 0)),
+            Some(at(11, 7, 0))
+        );
+        assert_eq!(
+            scheduled_end_for_mode(&config, WORK, at(11, 6, 0)),
+            Some(at(11, 7, 0))
+        );
+    }
+
+    #[test]
+    fn unknown_mode_or_malformed_trigger_is_none() {
+        let config = configurations((9, 0), (12, 0));
+        assert_eq!(
+            scheduled_end_for_mode(&config, "com.apple.focus.sleep", at(10, 10, 0)),
+            None
+        );
+
+        let malformed = json!({
+            "data": [{ "modeConfigurations": { WORK: { "triggers": { "triggers": [{
+                "class": "DNDModeConfigurationScheduleTrigger",
+                "timePeriodStartTimeHour": "nine"
+            }]}}}}]
+        });
+        assert_eq!(
+            scheduled_end_for_mode(&malformed, WORK, at(10, 10, 0)),
+            None
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use std::sync::Mutex;
+
+use log::{info, warn};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Failed window events in a row after which the window counts as gone.
+pub const DEGRADE_AFTER_FAILURES: u32 = 3;
+
+/// Where window events go: the webview, or a mock in tests.
+pub trait EventSink {
+    fn emit(&self, event: &str, payload: &Value) -> Result<(), String>;
+}
+
+/// Whether the window is receiving events, for diagnostics.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrontendHealth {
+    /// Summaries and urgent arrivals go to banners instead of the window.
+    pub degraded: bool,
+    /// Failed window events in a row.
+    pub consecutive_failures: u32,
+    /// Unix time the current degradation began.
+    pub degraded_since: Option<i64>,
+    /// Times delivery switched to banners since launch.
+    pub degradations: u32,
+    pub last_error: Option<String>,
+}
+
+/// What one event did to the delivery mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Unchanged,
+    /// Too many events failed; the window should be recreated.
+    Degraded,
+    /// An event got through again and the missed ones were re-sent.
+    Recovered,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delivery {
+    pub delivered: bool,
+    pub transition: Transition,
+}
+
+#[derive(Debug, Default)]
+struct MonitorState {
+    health: FrontendHealth,
+    /// The latest payload of each event that failed, in the order they were
+    /// last emitted, re-sent once an event gets through.
+    missed: Vec<(String, Value)>,
+}
+
+/// Tracks window event failures and switches delivery to banners while the
+/// webview is gone. Shared between the orchestrator (for health) and the
+/// emitting threads.
+#[derive(Debug, Default)]
+pub struct FrontendMonitor {
+    state: Mutex<MonitorState>,
+}
+
+impl FrontendMonitor {
+    /// Emits one event through `sink`. The first success after failures
+    /// re-sends the latest payload of each missed event, so a recreated
+    /// window catches up.
+    pub fn emit(&self, sink: &dyn EventSink, event: &str, payload: Value, now: i64) -> Delivery {
+        let result = sink.emit(event, &payload);
+        let (transition, missed) = {
+            let Ok(mut state) = self.state.lock() else {
+                return Delivery {
+                    delivered: result.is_ok(),
+                    transition: Transition::Unchanged,
+                };
+            };
+            match &result {
+                Ok(()) => {
+                    let recovered = state.health.degraded;
+                    state.health.consecutive_failures = 0;
+                    state.health.degraded = false;
+                    state.health.degraded_since = None;
+                    // A newer payload of the same event was just delivered.
+                    state.missed.retain(|(name, _)| name != event);
+                    let missed = std::mem::take(&mut state.missed);
+                    let transition = if recovered {
+                        info!("window events are delivered again");
+                        Transition::Recovered
+                    } else {
+                        Transition::Unchanged
+                    };
+                    (transition, missed)
+                }
+                Err(err) => {
+                    state.health.consecutive_failures += 1;
+                    state.health.last_error = Some(err.clone());
+                    state.missed.retain(|(name, _)| name != event);
+                    state.missed.push((event.to_string(), payload));
+                    let degrade = !state.health.degraded
+                        && state.health.consecutive_failures >= DEGRADE_AFTER_FAILURES;
+                    if degrade {
+                        warn!("window events keep failing ({err}); using banners instead");
+                        state.health.degraded = true;
+                        state.health.degraded_since = Some(now);
+                        state.health.degradations += 1;
+                    }
+                    let transition = if degrade {
+                        Transition::Degraded
+                    } else {
+                        Transition::Unchanged
+                    };
+                    (transition, Vec::new())
+                }
+            }
+        };
+        for (event, payload) in missed {
+            self.emit(sink, &event, payload, now);
+        }
+        Delivery {
+            delivered: result.is_ok(),
+            transition,
+        }
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.state
+            .lock()
+            .map(|state| state.health.degraded)
+            .unwrap_or(false)
+    }
+
+    pub fn health(&self) -> FrontendHealth {
+        self.state
+            .lock()
+            .map(|state| state.health.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use serde_json::{json, Value};
+
+    use super::{EventSink, FrontendMonitor, Transition, DEGRADE_AFTER_FAILURES};
+
+    #[derive(Default)]
+    struct MockSink {
+        down: Cell<bool>,
+        delivered: RefCell<Vec<(String, Value)>>,
+    }
+
+    impl EventSink for MockSink {
+        fn emit(&self, event: &str, payload: &Value) -> Result<(), String> {
+            if self.down.get() {
+                return Err("webview is gone".to_string());
+            }
+            self.delivered
+                .borrow_mut()
+                .push((event.to_string(), payload.clone()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn repeated_failures_degrade_and_recovery_flushes_the_latest_state() {
+        let sink = MockSink::default();
+        let monitor = FrontendMonitor::default();
+        let emit = |event: &str, payload: Value| monitor.emit(&sink, event, payload, 1_000);
+
+        assert!(emit("notifications-updated", json!({ "revision": 1 })).delivered);
+        sink.down.set(true);
+        let transitions: Vec<_> = (2..=DEGRADE_AFTER_FAILURES + 2)
+            .map(|revision| emit("notifications-updated", json!({ "revision": revision })))
+            .map(|delivery| (delivery.delivered, delivery.transition))
+            .collect();
+        assert_eq!(
+            transitions,
+            vec![
+                (false, Transition::Unchanged),
+                (false, Transition::Unchanged),
+                (false, Transition::Degraded),
+                (false, Transition::Unchanged),
+            ]
+        );
+        emit("summary-ready", json!({ "text": "要約" }));
+        let health = monitor.health();
+        assert!(monitor.is_degraded());
+        assert_eq!(health.degraded_since, Some(1_000));
+        assert_eq!(health.degradations, 1);
+        assert_eq!(health.consecutive_failures, DEGRADE_AFTER_FAILURES + 2);
+        assert_eq!(health.last_error.as_deref(), Some("webview is gone"));
+
+        sink.down.set(false);
+        sink.delivered.borrow_mut().clear();
+        let delivery = emit("urgency-changed", json!({}));
+        assert_eq!(delivery.transition, Transition::Recovered);
+        assert!(!monitor.is_degraded());
+        // Only the newest payload of each missed event is re-sent.
+        let names: Vec<_> = sink
+            .delivered
+            .borrow()
+            .iter()
+            .map(|(name, payload)| format!("{name} {payload}"))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "urgency-changed {}".to_string(),
+                r#"notifications-updated {"revision":5}"#.to_string(),
+                r#"summary-ready {"text":"要約"}"#.to_string(),
+            ]
+        );
+        assert_eq!(monitor.health().degradations, 1);
+    }
+
+    #[test]
+    fn an_occasional_failure_does_not_degrade() {
+        let sink = MockSink::default();
+        let monitor = FrontendMonitor::default();
+        for _ in 0..10 {
+            sink.down.set(true);
+            monitor.emit(&sink, "summary-chunk", json!("..."), 0);
+            sink.down.set(false);
+            let delivery = monitor.emit(&sink, "notifications-updated", json!({}), 0);
+            assert_eq!(delivery.transition, Transition::Unchanged);
+        }
+        assert_eq!(monitor.health().degradations, 0);
+        assert!(!monitor.is_degraded());
+    }
+}
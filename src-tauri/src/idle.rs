@@ -0,0 +1,58 @@
+use std::process::Command;
+
+use log::warn;
+
+/// How long the user has not touched the keyboard or mouse.
+pub trait IdleSource: Send + Sync {
+    /// Seconds since the last input event; `None` when it cannot be read.
+    fn idle_secs(&self) -> Option<u64>;
+}
+
+/// `HIDIdleTime` of the `IOHIDSystem` registry entry, read through `ioreg`.
+pub struct HidIdleSource;
+
+impl IdleSource for HidIdleSource {
+    fn idle_secs(&self) -> Option<u64> {
+        let output = match Command::new("/usr/sbin/ioreg")
+            .args(["-c", "IOHIDSystem", "-d", "4", "-k", "HIDIdleTime"])
+            .output()
+        {
+            Ok(output) => output,
+            Err(err) => {
+                warn!("Failed to run ioreg: {err}");
+                return None;
+            }
+        };
+        parse_hid_idle_secs(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// Reads the first `"HIDIdleTime" = <nanoseconds>` line.
+fn parse_hid_idle_secs(ioreg: &str) -> Option<u64> {
+    ioreg.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        if key.trim().trim_start_matches(['|', ' ']) != "\"HIDIdleTime\"" {
+            return None;
+        }
+        let nanos: u64 = value.trim().parse().ok()?;
+        Some(nanos / 1_000_000_000)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_hid_idle_secs;
+
+    #[test]
+    fn idle_time_is_read_from_ioreg_output() {
+        let output = r#"+-o Root  <class IORegistryEntry, id 0x100000100, retain 30>
+  +-o IOHIDSystem  <class IOHIDSystem, id 0x100000457, registered, matched, active, busy 0 (0 ms), retain 23>
+    | {
+    |   "HIDIdleTime" = 93417260125
+    | }
+"#;
+        assert_eq!(parse_hid_idle_secs(output), Some(93));
+        assert_eq!(parse_hid_idle_secs("\"HIDIdleTime\" = soon"), None);
+        assert_eq!(parse_hid_idle_secs(""), None);
+    }
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+/// Weight of one kana or CJK character against one Latin letter; a Japanese
+/// word takes far fewer characters than an English one.
+const JAPANESE_CHAR_WEIGHT: usize = 3;
+
+/// The language summaries and reasons are written in. Stored in
+/// `llm_settings.json` as `ja` or `en`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputLanguage {
+    #[default]
+    #[serde(rename = "ja")]
+    Japanese,
+    #[serde(rename = "en")]
+    English,
+}
+
+impl OutputLanguage {
+    /// The language's name as written in the (Japanese) prompts.
+    pub fn prompt_name(self) -> &'static str {
+        match self {
+            Self::Japanese => "日本語",
+            Self::English => "英語",
+        }
+    }
+}
+
+/// Prompt rule fixing the output language while leaving names as written.
+pub fn language_instruction(language: OutputLanguage) -> String {
+    format!(
+        "出力は{}で書き、途中で言語を切り替えないでください。\
+人名・製品名・アプリ名・チャンネル名・コード識別子（関数名、ファイル名、ブランチ名、PR番号など）は翻訳せず原文の表記のまま残してください。",
+        language.prompt_name()
+    )
+}
+
+/// Guesses the language of `text` from its scripts. Tokens that look like
+/// code, paths or URLs are skipped; `None` means no letters were left.
+pub fn detect_language(text: &str) -> Option<OutputLanguage> {
+    let mut japanese = 0;
+    let mut latin = 0;
+    for token in text.split_whitespace() {
+        let mut token_latin = 0;
+        for c in token.chars() {
+            if is_japanese_char(c) {
+                japanese += 1;
+            } else if c.is_ascii_alphabetic() {
+                token_latin += 1;
+            }
+        }
+        if !looks_like_code(token) {
+            latin += token_latin;
+        }
+    }
+    match (japanese * JAPANESE_CHAR_WEIGHT, latin) {
+        (0, 0) => None,
+        (japanese, latin) if japanese >= latin => Some(OutputLanguage::Japanese),
+        _ => Some(OutputLanguage::English),
+    }
+}
+
+/// Whether `text` is written in a language other than `output`.
+pub fn needs_translation(text: &str, output: OutputLanguage) -> bool {
+    detect_language(text).is_some_and(|source| source != output)
+}
+
+fn is_japanese_char(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}' // hiragana, katakana
+        | '\u{31f0}'..='\u{31ff}'
+        | '\u{ff66}'..='\u{ff9f}' // half-width katakana
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}')
+}
+
+/// Identifiers such as `fetch_user`, `src/main.rs`, `https://…` or `getUser`.
+fn looks_like_code(token: &str) -> bool {
+    let token = token.trim_matches(|c: char| !c.is_alphanumeric());
+    token.contains(['_', '/', '.', ':', '=', '`', '@'])
+        || token.chars().skip(1).any(|c| c.is_ascii_uppercase())
+            && token.chars().any(|c| c.is_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{detect_language, language_instruction, needs_translation, OutputLanguage};
+
+    #[test]
+    fn mixed_script_text_is_judged_by_weighted_share() {
+        let cases = [
+            (
+                "田中さんがPR #42にレビューコメント",
+                Some(OutputLanguage::Japanese),
+            ),
+            (
+                "Build failed on main (ビルド)",
+                Some(OutputLanguage::English),
+            ),
+            (
+                "Meeting with 田中 moved to 3pm",
+                Some(OutputLanguage::English),
+            ),
+            ("本番DBのCPU使用率が95%超過", Some(OutputLanguage::Japanese)),
+            ("Your package has shipped", Some(OutputLanguage::English)),
+            ("12:30 #42 🎉", None),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(detect_language(text), expected, "{text}");
+        }
+    }
+
+    #[test]
+    fn code_identifiers_do_not_count_as_english() {
+        let text = "fetchUserProfile src/api/user_profile.rs https://example.com でエラー";
+        assert_eq!(detect_language(text), Some(OutputLanguage::Japanese));
+        assert!(!needs_translation(text, OutputLanguage::Japanese));
+        assert!(needs_translation(
+            "Deploy to production finished",
+            OutputLanguage::Japanese
+        ));
+        assert!(!needs_translation("", OutputLanguage::English));
+    }
+
+    #[test]
+    fn instruction_names_the_language_and_keeps_names() {
+        let rule = language_instruction(OutputLanguage::English);
+        assert!(rule.starts_with("出力は英語で書き"));
+        assert!(rule.contains("コード識別子"));
+    }
+}
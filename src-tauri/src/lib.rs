@@ -0,0 +1,857 @@
+//! Everything behind the app: the notification modules and the background
+//! work (polling, summaries, dialogs) that reports back through the app handle.
+//! `main.rs` only wires these into Tauri.
+
+mod analysis_log;
+mod away;
+mod changes;
+pub mod commands;
+mod config_store;
+mod db;
+mod diagnostics;
+mod dialog;
+mod digest;
+mod focus;
+mod frontend;
+mod http;
+mod idle;
+mod injection;
+mod language;
+mod llm;
+mod models;
+mod noise;
+mod notification_center;
+mod onboarding;
+mod orchestrator;
+mod prompt_templates;
+mod read_state;
+mod resources;
+mod rollup;
+mod safe_mode;
+mod settings;
+mod snapshot;
+mod sources;
+mod staleness;
+mod storage;
+mod summary;
+mod usage;
+mod webhook;
+
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use log::{error, info, warn};
+use serde::Serialize;
+use tauri::{menu::CheckMenuItem, AppHandle, Emitter, Manager};
+
+use away::AwayInterval;
+use changes::RevisionCounter;
+use dialog::{
+    dialog_script, escape_applescript, notification_dialog_content, DialogContent, OPEN_APP_BUTTON,
+};
+use frontend::{EventSink, FrontendMonitor, Transition};
+use models::{NotificationsUpdatedEvent, UrgencyChangedEvent};
+use orchestrator::{analyze_notifications_batch, app_icon_file, sleep_gap, POLL_INTERVAL_SECONDS};
+use rollup::{build_daily_rollup_prompt, fallback_rollup_text, DailyRollupSource};
+use safe_mode::{reset_config, CLEAN_RUN_SECS};
+use snapshot::SnapshotWriter;
+use summary::{build_session_summary_prompt, enforce_summary_tiers};
+
+pub use config_store::ConfigStore;
+pub use llm::{notify_config_dir, LlmClient, SharedLlm};
+pub use orchestrator::{NotifyOrchestrator, SharedOrchestrator};
+pub use safe_mode::{LaunchCounter, LaunchMode};
+pub use snapshot::start_snapshot_writer;
+pub use webhook::start_webhook_sender;
+
+pub fn show_notification(title: &str, message: &str) {
+    let escaped_title = escape_applescript(title);
+    let escaped_message = escape_applescript(message);
+    let script = format!(
+        "display notification \"{}\" with title \"{}\"",
+        escaped_message, escaped_title
+    );
+    run_osascript(&script);
+}
+
+pub(crate) fn show_dialog(content: &DialogContent) -> Option<String> {
+    let result = Command::new("/usr/bin/osascript")
+        .arg("-e")
+        .arg(dialog_script(content))
+        .output();
+
+    match result {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            if stdout.contains(OPEN_APP_BUTTON) {
+                Some("open_app".to_string())
+            } else {
+                None
+            }
+        }
+        Err(err) => {
+            warn!("Failed to run osascript: {err}");
+            None
+        }
+    }
+}
+
+fn run_osascript(script: &str) {
+    let result = Command::new("/usr/bin/osascript")
+        .arg("-e")
+        .arg(script)
+        .output();
+
+    if let Err(err) = result {
+        warn!("Failed to run osascript: {err}");
+    }
+}
+
+fn compact_error_text(err: &str, max_chars: usize) -> String {
+    let compact = err
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" / ");
+
+    let mut chars = compact.chars();
+    let truncated: String = chars.by_ref().take(max_chars).collect();
+    if chars.next().is_some() {
+        format!("{truncated}...")
+    } else {
+        compact
+    }
+}
+
+pub fn show_startup_error_dialog(error_detail: &str) {
+    let detail = compact_error_text(error_detail, 220);
+    let message = format!(
+        "初期化に失敗しました。\\n\\n\
+主な原因: Notify.app のフルディスクアクセス未許可\\n\
+対処: システム設定 > プライバシーとセキュリティ > フルディスクアクセスで \
+Notify.app を許可後、再起動してください。\\n\\n\
+詳細: {detail}"
+    );
+    let escaped_message = escape_applescript(&message);
+    let script = format!(
+        "display dialog \"{}\" with title \"Notify 起動エラー\" \
+buttons {{\"閉じる\", \"設定を開く\"}} default button \"閉じる\" with icon stop",
+        escaped_message
+    );
+
+    match Command::new("/usr/bin/osascript")
+        .arg("-e")
+        .arg(&script)
+        .output()
+    {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            if stdout.contains("設定を開く") {
+                if let Err(err) = Command::new("open")
+                    .arg("x-apple.systempreferences:com.apple.preference.security")
+                    .spawn()
+                {
+                    warn!("Failed to open system settings: {err}");
+                }
+            }
+        }
+        Err(err) => {
+            warn!("Failed to show startup error dialog: {err}");
+        }
+    }
+}
+
+const SAFE_MODE_RESTART_BUTTON: &str = "通常モードで再起動";
+const SAFE_MODE_RESET_BUTTON: &str = "設定をリセット";
+
+/// Explains safe mode and offers a way out. Returns the button pressed, if any.
+fn show_safe_mode_dialog(crashes: u32) -> Option<String> {
+    let message = format!(
+        "Notify は直前の {crashes} 回の起動で正常に動作しなかったため、\
+セーフモードで起動しました。\n\n\
+セーフモードでは設定ファイルを読み込まず、AI分析も停止しています。\n\
+設定が原因と思われる場合は「{SAFE_MODE_RESET_BUTTON}」を選ぶと、\
+設定ファイルを退避して既定値で再起動します。"
+    );
+    let script = format!(
+        "display dialog \"{}\" with title \"Notify セーフモード\" \
+buttons {{\"閉じる\", \"{SAFE_MODE_RESTART_BUTTON}\", \"{SAFE_MODE_RESET_BUTTON}\"}} \
+default button \"閉じる\" with icon caution",
+        escape_applescript(&message)
+    );
+
+    match Command::new("/usr/bin/osascript")
+        .arg("-e")
+        .arg(&script)
+        .output()
+    {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            [SAFE_MODE_RESTART_BUTTON, SAFE_MODE_RESET_BUTTON]
+                .into_iter()
+                .find(|button| stdout.contains(button))
+                .map(str::to_string)
+        }
+        Err(err) => {
+            warn!("Failed to show safe mode dialog: {err}");
+            None
+        }
+    }
+}
+
+/// Counts this launch towards safe mode until it has run cleanly; a launch
+/// already in safe mode keeps the count until the user leaves it.
+pub fn start_launch_watch(
+    app: AppHandle,
+    config: ConfigStore,
+    counter: LaunchCounter,
+    mode: LaunchMode,
+) {
+    match mode {
+        LaunchMode::Normal => {
+            thread::spawn(move || {
+                thread::sleep(Duration::from_secs(CLEAN_RUN_SECS));
+                counter.mark_clean();
+            });
+        }
+        LaunchMode::Safe { crashes } => {
+            thread::spawn(move || {
+                let Some(button) = show_safe_mode_dialog(crashes) else {
+                    return;
+                };
+                if button == SAFE_MODE_RESET_BUTTON {
+                    match reset_config(&config) {
+                        Ok(backup) => warn!("config moved to {}", backup.display()),
+                        Err(err) => {
+                            error!("failed to reset config: {err:#}");
+                            return;
+                        }
+                    }
+                }
+                counter.mark_clean();
+                app.restart();
+            });
+        }
+    }
+}
+
+pub struct TrayState(pub tauri::tray::TrayIcon);
+
+/// Tray check item mirroring whether AI analysis is paused.
+pub struct PauseAnalysisItem(pub CheckMenuItem<tauri::Wry>);
+
+fn highest_urgency_index(counts: [usize; 4]) -> Option<usize> {
+    // counts: [critical, high, medium, low]
+    counts.iter().position(|&c| c > 0)
+}
+
+fn update_tray(app: &AppHandle, counts: [usize; 4]) {
+    let total: usize = counts.iter().sum();
+    let title = if total == 0 {
+        String::new()
+    } else {
+        format!("{total}")
+    };
+
+    #[cfg(target_os = "macos")]
+    if let Some(state) = app.try_state::<TrayState>() {
+        if let Err(err) = state.0.set_title(Some(&title)) {
+            warn!("failed to update tray title: {err}");
+        }
+
+        let (icon, as_template) = match highest_urgency_index(counts) {
+            Some(0) => (tauri::include_image!("icons/tray-critical.png"), false),
+            Some(1) => (tauri::include_image!("icons/tray-high.png"), false),
+            Some(2) => (tauri::include_image!("icons/tray-medium.png"), false),
+            Some(3) => (tauri::include_image!("icons/tray-low.png"), false),
+            _ => (tauri::include_image!("icons/tray.png"), true),
+        };
+
+        if let Err(err) = state.0.set_icon(Some(icon)) {
+            warn!("failed to update tray icon: {err}");
+        }
+        if let Err(err) = state.0.set_icon_as_template(as_template) {
+            warn!("failed to set icon template mode: {err}");
+        }
+    }
+}
+
+/// Window events through the webview.
+struct WebviewSink<'a>(&'a AppHandle);
+
+impl EventSink for WebviewSink<'_> {
+    fn emit(&self, event: &str, payload: &serde_json::Value) -> Result<(), String> {
+        self.0.emit(event, payload).map_err(|err| err.to_string())
+    }
+}
+
+/// Emits `event` to the window through the [`FrontendMonitor`]. When events
+/// keep failing, says so in a banner and tries to bring the window back.
+/// Returns whether the window received it.
+pub(crate) fn emit_to_window(app: &AppHandle, event: &str, payload: impl Serialize) -> bool {
+    let payload = match serde_json::to_value(payload) {
+        Ok(payload) => payload,
+        Err(err) => {
+            warn!("failed to serialize {event}: {err}");
+            return false;
+        }
+    };
+    let Some(monitor) = app.try_state::<Arc<FrontendMonitor>>() else {
+        return app.emit(event, payload).is_ok();
+    };
+    let delivery = monitor.emit(
+        &WebviewSink(app),
+        event,
+        payload,
+        chrono::Utc::now().timestamp(),
+    );
+    if !delivery.delivered {
+        warn!("failed to emit {event}");
+    }
+    if delivery.transition == Transition::Degraded {
+        show_notification(
+            "Notify",
+            "ウィンドウに接続できないため、要約と重要な通知をバナーで表示します",
+        );
+        recreate_main_window(app);
+    }
+    delivery.delivered
+}
+
+/// Summaries and urgent arrivals go to banners while the window is gone.
+fn window_degraded(app: &AppHandle) -> bool {
+    app.try_state::<Arc<FrontendMonitor>>()
+        .is_some_and(|monitor| monitor.is_degraded())
+}
+
+/// Reloads the main window's webview, or builds the window again from the
+/// config when it is gone.
+fn recreate_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        match window.reload() {
+            Ok(()) => return,
+            Err(err) => {
+                warn!("failed to reload the main window: {err}");
+                if let Err(err) = window.destroy() {
+                    warn!("failed to close the main window: {err}");
+                }
+            }
+        }
+    }
+    let Some(config) = app
+        .config()
+        .app
+        .windows
+        .iter()
+        .find(|window| window.label == "main")
+        .cloned()
+    else {
+        warn!("no main window in the config to recreate");
+        return;
+    };
+    if let Err(err) =
+        tauri::WebviewWindowBuilder::from_config(app, &config).and_then(|builder| builder.build())
+    {
+        warn!("failed to recreate the main window: {err}");
+    }
+}
+
+pub fn emit_notifications_updated(app: &AppHandle, counts: [usize; 4]) {
+    let revision = app.try_state::<RevisionCounter>().map_or(0, |r| r.get());
+    emit_to_window(
+        app,
+        "notifications-updated",
+        NotificationsUpdatedEvent { revision },
+    );
+    if let Some(writer) = app.try_state::<SnapshotWriter>() {
+        writer.request();
+    }
+    update_tray(app, counts);
+}
+
+/// Streams an LLM summary of the collected notifications to the summary window
+/// (`summary-chunk`, then `summary-ready`). The count-based summary stays in
+/// place when the model is unavailable.
+pub(crate) fn spawn_summary_stream(
+    app: AppHandle,
+    orchestrator: Arc<Mutex<NotifyOrchestrator>>,
+    llm: Arc<LlmClient>,
+) {
+    tauri::async_runtime::spawn(async move {
+        stream_summary(&app, &orchestrator, &llm).await;
+    });
+}
+
+/// Summarizes the session that just ended, then lets Ollama unload the model
+/// if no new session has warmed it up since.
+fn spawn_session_end(
+    app: AppHandle,
+    orchestrator: Arc<Mutex<NotifyOrchestrator>>,
+    llm: Arc<LlmClient>,
+) {
+    let session = llm.warm_session();
+    tauri::async_runtime::spawn(async move {
+        stream_summary(&app, &orchestrator, &llm).await;
+        llm.release(session).await;
+    });
+}
+
+async fn stream_summary(
+    app: &AppHandle,
+    orchestrator: &Mutex<NotifyOrchestrator>,
+    llm: &LlmClient,
+) {
+    let source = match orchestrator.lock() {
+        Ok(guard) => guard.summary_source(),
+        Err(err) => {
+            error!("Orchestrator lock poisoned: {err}");
+            return;
+        }
+    };
+    let Some(source) = source else {
+        return;
+    };
+    if !llm.analysis_enabled() {
+        // Paused analysis keeps the counting summary only.
+        return;
+    }
+
+    let prompt = build_session_summary_prompt(&source, llm.output_language(), chrono::Local::now());
+    let emitter = app.clone();
+    let mut on_chunk = move |partial: &str| {
+        emit_to_window(&emitter, "summary-chunk", partial);
+    };
+    let text = match llm.generate_summary(&prompt, &mut on_chunk).await {
+        Ok(text) => text,
+        Err(err) => {
+            warn!("failed to generate session summary: {err:#}");
+            return;
+        }
+    };
+
+    // Critical/High items the model left out are appended deterministically.
+    let text = enforce_summary_tiers(&text, &source);
+    let summary = match orchestrator.lock() {
+        Ok(mut guard) => guard.store_summary(text),
+        Err(err) => {
+            error!("Orchestrator lock poisoned: {err}");
+            return;
+        }
+    };
+    if !emit_to_window(app, "summary-ready", &summary) && window_degraded(app) {
+        show_notification("要約", &summary.text);
+    }
+}
+
+/// Generates the end-of-day rollup, records it in the summary history, posts
+/// it as a banner and hands it to the summary window (`summary-ready`).
+/// Without the model the counts-only text is used instead.
+fn spawn_daily_rollup(
+    app: AppHandle,
+    orchestrator: Arc<Mutex<NotifyOrchestrator>>,
+    llm: Arc<LlmClient>,
+    source: DailyRollupSource,
+) {
+    tauri::async_runtime::spawn(async move {
+        let text = if llm.analysis_enabled() {
+            let prompt = build_daily_rollup_prompt(&source, llm.output_language());
+            match llm.generate_summary(&prompt, &mut |_: &str| {}).await {
+                Ok(text) => enforce_summary_tiers(&text, &source.items),
+                Err(err) => {
+                    warn!("failed to generate daily rollup: {err:#}");
+                    fallback_rollup_text(&source)
+                }
+            }
+        } else {
+            fallback_rollup_text(&source)
+        };
+
+        let summary = match orchestrator.lock() {
+            Ok(mut guard) => guard.store_daily_rollup(&source, text),
+            Err(err) => {
+                error!("Orchestrator lock poisoned: {err}");
+                return;
+            }
+        };
+        show_notification("今日のまとめ", &summary.text);
+        emit_to_window(&app, "summary-ready", &summary);
+    });
+}
+
+/// Runs catch-up over the time the user was away and posts it as a banner
+/// and to the summary window (`away-summary`). Nothing is added to the
+/// collected list and no focus session is started. Nothing is shown when no
+/// notification arrived meanwhile.
+fn spawn_away_catch_up(
+    app: AppHandle,
+    orchestrator: Arc<Mutex<NotifyOrchestrator>>,
+    llm: Arc<LlmClient>,
+    interval: AwayInterval,
+) {
+    tauri::async_runtime::spawn(async move {
+        let routed = match orchestrator.lock() {
+            Ok(mut guard) => guard.catch_up_read_since(interval.since),
+            Err(err) => {
+                error!("Orchestrator lock poisoned: {err}");
+                return;
+            }
+        };
+        let routed = match routed {
+            Ok(routed) => routed,
+            Err(err) => {
+                warn!("failed to read notifications for away catch-up: {err:#}");
+                return;
+            }
+        };
+        let mut analyzed = routed.escalated;
+        analyzed.extend(analyze_notifications_batch(&llm, routed.pending).await);
+        if analyzed.is_empty() {
+            return;
+        }
+
+        let summary = match orchestrator.lock() {
+            Ok(mut guard) => {
+                let result = guard.catch_up_finish(analyzed, false);
+                guard.away_summary(interval, &result)
+            }
+            Err(err) => {
+                error!("Orchestrator lock poisoned: {err}");
+                return;
+            }
+        };
+        show_notification("離席中の通知", &summary.text);
+        if emit_to_window(&app, "away-summary", &summary) {
+            auto_show_main_window(&app);
+        }
+    });
+}
+
+/// Re-runs analysis for fallback and paused items in the background and
+/// upgrades them in place. Urgency changes are emitted as `urgency-changed`.
+pub(crate) fn spawn_reanalysis(
+    app: AppHandle,
+    orchestrator: Arc<Mutex<NotifyOrchestrator>>,
+    llm: Arc<LlmClient>,
+) {
+    tauri::async_runtime::spawn(async move {
+        let pending = match orchestrator.lock() {
+            Ok(guard) => guard.reanalysis_candidates(),
+            Err(err) => {
+                error!("Orchestrator lock poisoned: {err}");
+                return;
+            }
+        };
+        if pending.is_empty() || !llm.analysis_enabled() {
+            return;
+        }
+
+        let analyzed = analyze_notifications_batch(&llm, pending).await;
+
+        let (counts, changes) = match orchestrator.lock() {
+            Ok(mut guard) => {
+                if guard.apply_reanalysis(analyzed) == 0 {
+                    return;
+                }
+                let changes = UrgencyChangedEvent {
+                    revision: guard.revision(),
+                    changes: guard.recent_urgency_changes(),
+                };
+                (guard.urgency_counts(), changes)
+            }
+            Err(err) => {
+                error!("Orchestrator lock poisoned: {err}");
+                return;
+            }
+        };
+        emit_notifications_updated(&app, counts);
+        if !changes.changes.is_empty() {
+            emit_to_window(&app, "urgency-changed", &changes);
+        }
+    });
+}
+
+/// Pauses or resumes AI analysis, keeping the tray check item in sync.
+/// Resuming upgrades items collected while paused.
+pub fn apply_analysis_enabled(app: &AppHandle, enabled: bool) -> anyhow::Result<()> {
+    let llm = app.state::<SharedLlm>().0.clone();
+    llm.set_analysis_enabled(enabled)?;
+    if let Some(item) = app.try_state::<PauseAnalysisItem>() {
+        if let Err(err) = item.0.set_checked(!enabled) {
+            warn!("failed to update pause analysis item: {err}");
+        }
+    }
+    if enabled {
+        let orchestrator = app.state::<SharedOrchestrator>().0.clone();
+        spawn_reanalysis(app.clone(), orchestrator, llm);
+    }
+    Ok(())
+}
+
+fn position_window_under_tray(window: &tauri::WebviewWindow, tray_rect: &tauri::Rect) {
+    let scale = window.scale_factor().unwrap_or(1.0);
+
+    let (tray_x, tray_y) = match tray_rect.position {
+        tauri::Position::Physical(ref p) => (p.x as f64, p.y as f64),
+        tauri::Position::Logical(ref p) => (p.x * scale, p.y * scale),
+    };
+    let (tray_w, tray_h) = match tray_rect.size {
+        tauri::Size::Physical(ref s) => (s.width as f64, s.height as f64),
+        tauri::Size::Logical(ref s) => (s.width * scale, s.height * scale),
+    };
+
+    // Physical pixel coordinates
+    let tray_center_x = tray_x + tray_w / 2.0;
+    let tray_bottom_y = tray_y + tray_h;
+
+    let win_size = window
+        .outer_size()
+        .unwrap_or(tauri::PhysicalSize::new(520, 640));
+    let win_width = win_size.width as f64;
+
+    // Center the window horizontally under the tray icon
+    let x = tray_center_x - win_width / 2.0;
+    let y = tray_bottom_y;
+
+    let _ = window.set_position(tauri::PhysicalPosition::new(x as i32, y as i32));
+}
+
+pub fn toggle_main_window(app: &AppHandle, tray_rect: Option<tauri::Rect>) {
+    let Some(window) = app.get_webview_window("main") else {
+        warn!("main window not found");
+        return;
+    };
+
+    match window.is_visible() {
+        Ok(true) => {
+            if let Err(err) = window.hide() {
+                warn!("failed to hide window: {err}");
+            }
+        }
+        Ok(false) => show_main_window(app, &window, tray_rect),
+        Err(err) => {
+            warn!("failed to read window visibility: {err}");
+        }
+    }
+}
+
+/// Shows, focuses and refreshes the hidden main window. Must be called
+/// without the orchestrator lock held.
+fn show_main_window(
+    app: &AppHandle,
+    window: &tauri::WebviewWindow,
+    tray_rect: Option<tauri::Rect>,
+) {
+    if let Some(rect) = tray_rect {
+        position_window_under_tray(window, &rect);
+    }
+    if let Err(err) = window.show() {
+        warn!("failed to show window: {err}");
+        return;
+    }
+    let _ = window.unminimize();
+    let _ = window.set_focus();
+    let counts = app
+        .state::<SharedOrchestrator>()
+        .0
+        .lock()
+        .map(|guard| guard.urgency_counts())
+        .unwrap_or([0; 4]);
+    emit_notifications_updated(app, counts);
+}
+
+/// Opens the panel for an urgent arrival, leaving it alone if already open.
+fn auto_show_main_window(app: &AppHandle) {
+    let Some(window) = app.get_webview_window("main") else {
+        warn!("main window not found");
+        return;
+    };
+    if !window.is_visible().unwrap_or(false) {
+        show_main_window(app, &window, None);
+    }
+}
+
+/// How often the wait between polls checks whether the Mac slept.
+const WAKE_CHECK_SECS: u64 = 1;
+
+/// Waits out the poll interval, returning early when the wall clock jumps
+/// ahead of the monotonic one: the Mac just woke, and what arrived during
+/// sleep should be read now. The poll then records the sleep itself.
+fn wait_for_next_poll() {
+    let interval = Duration::from_secs(POLL_INTERVAL_SECONDS);
+    let started = Instant::now();
+    loop {
+        let remaining = interval.saturating_sub(started.elapsed());
+        if remaining.is_zero() {
+            return;
+        }
+        let (mono, wall) = (Instant::now(), SystemTime::now());
+        thread::sleep(remaining.min(Duration::from_secs(WAKE_CHECK_SECS)));
+        let wall_elapsed = wall.elapsed().unwrap_or_default();
+        if sleep_gap(wall_elapsed, mono.elapsed(), interval).is_some() {
+            info!("woke from sleep; polling now");
+            return;
+        }
+    }
+}
+
+pub fn start_polling_thread(
+    app: AppHandle,
+    orchestrator: Arc<Mutex<NotifyOrchestrator>>,
+    llm: Arc<LlmClient>,
+    config: ConfigStore,
+) {
+    thread::spawn(move || loop {
+        // Phase 1: Read focus and idle time (NO lock held, may wait out a
+        // half-written assertions file), then Lock → DB read + filter → Unlock (fast)
+        let poll_started = Instant::now();
+        let (read_focus, read_idle) = match orchestrator.lock() {
+            Ok(guard) => (guard.focus_reader(), guard.idle_reader()),
+            Err(err) => {
+                error!("Orchestrator lock poisoned: {err}");
+                thread::sleep(Duration::from_secs(POLL_INTERVAL_SECONDS));
+                continue;
+            }
+        };
+        let focus = read_focus();
+        let idle_secs = read_idle();
+        let poll_result = {
+            let mut guard = match orchestrator.lock() {
+                Ok(guard) => guard,
+                Err(err) => {
+                    error!("Orchestrator lock poisoned: {err}");
+                    thread::sleep(Duration::from_secs(POLL_INTERVAL_SECONDS));
+                    continue;
+                }
+            };
+            guard.poll_read_new_with(focus, idle_secs)
+        };
+        let poll_time = poll_started.elapsed();
+        if poll_result.focus_started {
+            // Not awaited: the first batch waits on the same load anyway.
+            let llm = llm.clone();
+            tauri::async_runtime::spawn(async move { llm.warm_up().await });
+        }
+
+        // Phase 2: LLM analysis (NO lock held, may take seconds/minutes).
+        // Runs on the shared async runtime; this thread waits for the batch.
+        let analysis_started = Instant::now();
+        let sent = poll_result.pending.len();
+        let mut analyzed = poll_result.escalated;
+        if !poll_result.pending.is_empty() {
+            analyzed.extend(tauri::async_runtime::block_on(analyze_notifications_batch(
+                &llm,
+                poll_result.pending,
+            )));
+        }
+        let analysis_time = analysis_started.elapsed();
+
+        if llm.is_shutting_down() {
+            break;
+        }
+
+        // Phase 3: Lock → store results → Unlock (fast)
+        let (counts, interruptions, auto_show, rollup, away, templates) = {
+            let mut guard = match orchestrator.lock() {
+                Ok(guard) => guard,
+                Err(err) => {
+                    error!("Orchestrator lock poisoned: {err}");
+                    thread::sleep(Duration::from_secs(POLL_INTERVAL_SECONDS));
+                    continue;
+                }
+            };
+            guard.record_cycle(poll_time, analysis_time, sent);
+            let idle = analyzed.is_empty() && !poll_result.updated_in_place;
+            guard.apply_interruption_floors(&mut analyzed);
+            let interruptions = guard.interruptions(&mut analyzed);
+            let auto_show = guard.should_auto_show(&analyzed);
+            guard.enqueue_webhooks(&analyzed);
+            let changed = guard.poll_store_results(analyzed) || poll_result.updated_in_place;
+            if idle {
+                guard.maintain_if_due(&llm);
+            }
+            if poll_result.focus_ended {
+                guard.on_focus_ended();
+            }
+            let rollup = guard.daily_rollup_source(chrono::Local::now());
+            let away = guard.check_away(idle_secs, llm.analysis_enabled(), chrono::Local::now());
+            let counts = if changed || poll_result.focus_ended {
+                Some(guard.urgency_counts())
+            } else {
+                None
+            };
+            let templates = guard.settings().message_templates.clone();
+            (counts, interruptions, auto_show, rollup, away, templates)
+        };
+
+        // Ledgers only stage their files under the lock.
+        if let Err(err) = config.flush() {
+            warn!("failed to write staged config files: {err:#}");
+        }
+
+        if let Some(counts) = counts {
+            emit_notifications_updated(&app, counts);
+        }
+
+        if auto_show {
+            if window_degraded(&app) {
+                // The window cannot open; a banner says what arrived instead.
+                let counts = counts.unwrap_or_default();
+                show_notification(
+                    "Notify",
+                    &format!(
+                        "重要な通知が届いています（緊急 {} / 高 {}）",
+                        counts[0], counts[1]
+                    ),
+                );
+            } else {
+                auto_show_main_window(&app);
+            }
+        }
+
+        if poll_result.focus_ended {
+            spawn_session_end(app.clone(), orchestrator.clone(), llm.clone());
+        } else if poll_result.ended_without_summary {
+            let llm = llm.clone();
+            let session = llm.warm_session();
+            tauri::async_runtime::spawn(async move { llm.release(session).await });
+        }
+
+        if let Some(source) = rollup {
+            spawn_daily_rollup(app.clone(), orchestrator.clone(), llm.clone(), source);
+        }
+
+        if let Some(interval) = away {
+            spawn_away_catch_up(app.clone(), orchestrator.clone(), llm.clone(), interval);
+        }
+
+        for notification in &interruptions.banners {
+            show_notification(&notification.app_name, &notification.summary_line);
+        }
+
+        // Phase 4: Show dialogs (NO lock held, may block on user input)
+        for notification in &interruptions.dialogs {
+            let content = notification_dialog_content(
+                notification,
+                &notification.app_name,
+                app_icon_file(&notification.bundle_id),
+                &templates,
+                interruptions.dialogs.len(),
+            );
+            let result = show_dialog(&content);
+            if result.as_deref() == Some("open_app") {
+                if let Err(err) = std::process::Command::new("open")
+                    .arg("-b")
+                    .arg(&notification.bundle_id)
+                    .spawn()
+                {
+                    warn!("failed to open app {}: {err}", notification.bundle_id);
+                }
+            }
+        }
+
+        wait_for_next_poll();
+    });
+}
@@ -1,327 +1,39 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod commands;
-mod db;
-mod focus;
-mod llm;
-mod models;
-mod orchestrator;
-
-use std::process::Command;
 use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
 
-use log::{error, warn};
+use log::{info, warn};
 use tauri::{
-    menu::{Menu, MenuItem, PredefinedMenuItem},
+    menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
-    AppHandle, Emitter, Manager, WindowEvent,
+    AppHandle, Manager, WindowEvent,
 };
 
-use commands::{
-    add_ignored_app, clear_all_notifications, clear_app_notifications, clear_notification,
-    delete_app_prompt, get_app_prompts, get_ignored_apps, get_llm_settings,
-    get_notification_groups, hide_main_window, inject_dummy_notifications, open_app,
-    remove_ignored_app, set_app_prompt, set_llm_model,
-};
-use llm::{LlmClient, SharedLlm};
-use orchestrator::{
-    analyze_notifications_batch, NotifyOrchestrator, SharedOrchestrator, POLL_INTERVAL_SECONDS,
+use notify_lib::commands;
+use notify_lib::{
+    apply_analysis_enabled, emit_notifications_updated, notify_config_dir, show_notification,
+    show_startup_error_dialog, start_launch_watch, start_polling_thread, start_snapshot_writer,
+    start_webhook_sender, toggle_main_window, ConfigStore, LaunchCounter, LaunchMode, LlmClient,
+    NotifyOrchestrator, PauseAnalysisItem, SharedLlm, SharedOrchestrator, TrayState,
 };
 
-pub(crate) fn show_notification(title: &str, message: &str) {
-    let escaped_title = escape_applescript(title);
-    let escaped_message = escape_applescript(message);
-    let script = format!(
-        "display notification \"{}\" with title \"{}\"",
-        escaped_message, escaped_title
-    );
-    run_osascript(&script);
-}
-
-pub(crate) fn show_dialog(title: &str, message: &str) -> Option<String> {
-    let escaped_title = escape_applescript(title);
-    let escaped_message = escape_applescript(message);
-    let script = format!(
-        "display dialog \"{}\" with title \"{}\" buttons {{\"OK\", \"アプリを開く\"}} default button \"OK\"",
-        escaped_message, escaped_title
-    );
-    let result = Command::new("/usr/bin/osascript")
-        .arg("-e")
-        .arg(&script)
-        .output();
-
-    match result {
-        Ok(output) => {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            if stdout.contains("アプリを開く") {
-                Some("open_app".to_string())
-            } else {
-                None
-            }
-        }
-        Err(err) => {
-            warn!("Failed to run osascript: {err}");
-            None
-        }
-    }
-}
-
-fn escape_applescript(text: &str) -> String {
-    text.replace('\\', "\\\\").replace('"', "\\\"")
-}
-
-fn run_osascript(script: &str) {
-    let result = Command::new("/usr/bin/osascript")
-        .arg("-e")
-        .arg(script)
-        .output();
-
-    if let Err(err) = result {
-        warn!("Failed to run osascript: {err}");
-    }
-}
-
-fn compact_error_text(err: &str, max_chars: usize) -> String {
-    let compact = err
-        .lines()
-        .map(str::trim)
-        .filter(|line| !line.is_empty())
-        .collect::<Vec<_>>()
-        .join(" / ");
-
-    let mut chars = compact.chars();
-    let truncated: String = chars.by_ref().take(max_chars).collect();
-    if chars.next().is_some() {
-        format!("{truncated}...")
-    } else {
-        compact
-    }
-}
-
-fn show_startup_error_dialog(error_detail: &str) {
-    let detail = compact_error_text(error_detail, 220);
-    let message = format!(
-        "初期化に失敗しました。\\n\\n\
-主な原因: Notify.app のフルディスクアクセス未許可\\n\
-対処: システム設定 > プライバシーとセキュリティ > フルディスクアクセスで \
-Notify.app を許可後、再起動してください。\\n\\n\
-詳細: {detail}"
-    );
-    let escaped_message = escape_applescript(&message);
-    let script = format!(
-        "display dialog \"{}\" with title \"Notify 起動エラー\" \
-buttons {{\"閉じる\", \"設定を開く\"}} default button \"閉じる\" with icon stop",
-        escaped_message
-    );
-
-    match Command::new("/usr/bin/osascript")
-        .arg("-e")
-        .arg(&script)
-        .output()
-    {
-        Ok(output) => {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            if stdout.contains("設定を開く") {
-                if let Err(err) = Command::new("open")
-                    .arg("x-apple.systempreferences:com.apple.preference.security")
-                    .spawn()
-                {
-                    warn!("Failed to open system settings: {err}");
-                }
-            }
-        }
-        Err(err) => {
-            warn!("Failed to show startup error dialog: {err}");
-        }
-    }
-}
-
-struct TrayState(tauri::tray::TrayIcon);
-
-fn highest_urgency_index(counts: [usize; 4]) -> Option<usize> {
-    // counts: [critical, high, medium, low]
-    counts.iter().position(|&c| c > 0)
-}
-
-fn update_tray(app: &AppHandle, counts: [usize; 4]) {
-    let total: usize = counts.iter().sum();
-    let title = if total == 0 {
-        String::new()
-    } else {
-        format!("{total}")
-    };
-
-    #[cfg(target_os = "macos")]
-    if let Some(state) = app.try_state::<TrayState>() {
-        if let Err(err) = state.0.set_title(Some(&title)) {
-            warn!("failed to update tray title: {err}");
-        }
-
-        let (icon, as_template) = match highest_urgency_index(counts) {
-            Some(0) => (tauri::include_image!("icons/tray-critical.png"), false),
-            Some(1) => (tauri::include_image!("icons/tray-high.png"), false),
-            Some(2) => (tauri::include_image!("icons/tray-medium.png"), false),
-            Some(3) => (tauri::include_image!("icons/tray-low.png"), false),
-            _ => (tauri::include_image!("icons/tray.png"), true),
-        };
-
-        if let Err(err) = state.0.set_icon(Some(icon)) {
-            warn!("failed to update tray icon: {err}");
-        }
-        if let Err(err) = state.0.set_icon_as_template(as_template) {
-            warn!("failed to set icon template mode: {err}");
-        }
-    }
-}
-
-pub(crate) fn emit_notifications_updated(app: &AppHandle, counts: [usize; 4]) {
-    if let Err(err) = app.emit("notifications-updated", ()) {
-        warn!("failed to emit notifications-updated: {err}");
-    }
-    update_tray(app, counts);
-}
-
-fn position_window_under_tray(window: &tauri::WebviewWindow, tray_rect: &tauri::Rect) {
-    let scale = window.scale_factor().unwrap_or(1.0);
-
-    let (tray_x, tray_y) = match tray_rect.position {
-        tauri::Position::Physical(ref p) => (p.x as f64, p.y as f64),
-        tauri::Position::Logical(ref p) => (p.x * scale, p.y * scale),
-    };
-    let (tray_w, tray_h) = match tray_rect.size {
-        tauri::Size::Physical(ref s) => (s.width as f64, s.height as f64),
-        tauri::Size::Logical(ref s) => (s.width * scale, s.height * scale),
-    };
-
-    // Physical pixel coordinates
-    let tray_center_x = tray_x + tray_w / 2.0;
-    let tray_bottom_y = tray_y + tray_h;
-
-    let win_size = window
-        .outer_size()
-        .unwrap_or(tauri::PhysicalSize::new(520, 640));
-    let win_width = win_size.width as f64;
-
-    // Center the window horizontally under the tray icon
-    let x = tray_center_x - win_width / 2.0;
-    let y = tray_bottom_y;
-
-    let _ = window.set_position(tauri::PhysicalPosition::new(x as i32, y as i32));
-}
-
-fn toggle_main_window(app: &AppHandle, tray_rect: Option<tauri::Rect>) {
-    let Some(window) = app.get_webview_window("main") else {
-        warn!("main window not found");
-        return;
-    };
-
-    match window.is_visible() {
-        Ok(true) => {
-            if let Err(err) = window.hide() {
-                warn!("failed to hide window: {err}");
-            }
-        }
-        Ok(false) => {
-            if let Some(rect) = tray_rect {
-                position_window_under_tray(&window, &rect);
-            }
-            if let Err(err) = window.show() {
-                warn!("failed to show window: {err}");
-                return;
-            }
-            let _ = window.unminimize();
-            let _ = window.set_focus();
-            let counts = app
-                .state::<SharedOrchestrator>()
-                .0
-                .lock()
-                .map(|guard| guard.urgency_counts())
-                .unwrap_or([0; 4]);
-            emit_notifications_updated(app, counts);
-        }
-        Err(err) => {
-            warn!("failed to read window visibility: {err}");
-        }
-    }
-}
-
-fn start_polling_thread(
-    app: AppHandle,
-    orchestrator: Arc<Mutex<NotifyOrchestrator>>,
-    llm: Arc<LlmClient>,
-) {
-    thread::spawn(move || loop {
-        // Phase 1: Lock → DB read + filter → Unlock (fast, sub-millisecond)
-        let poll_result = {
-            let mut guard = match orchestrator.lock() {
-                Ok(guard) => guard,
-                Err(err) => {
-                    error!("Orchestrator lock poisoned: {err}");
-                    thread::sleep(Duration::from_secs(POLL_INTERVAL_SECONDS));
-                    continue;
-                }
-            };
-            guard.poll_read_new()
-        };
-
-        // Phase 2: LLM analysis (NO lock held, may take seconds/minutes)
-        let (analyzed, criticals) = if poll_result.pending.is_empty() {
-            (Vec::new(), Vec::new())
-        } else {
-            analyze_notifications_batch(&llm, poll_result.pending)
-        };
-
-        // Phase 3: Lock → store results → Unlock (fast)
-        let counts = {
-            let mut guard = match orchestrator.lock() {
-                Ok(guard) => guard,
-                Err(err) => {
-                    error!("Orchestrator lock poisoned: {err}");
-                    thread::sleep(Duration::from_secs(POLL_INTERVAL_SECONDS));
-                    continue;
-                }
-            };
-            let changed = guard.poll_store_results(analyzed);
-            if poll_result.focus_ended {
-                guard.on_focus_ended();
-            }
-            if changed || poll_result.focus_ended {
-                Some(guard.urgency_counts())
-            } else {
-                None
-            }
-        };
-
-        if let Some(counts) = counts {
-            emit_notifications_updated(&app, counts);
-        }
-
-        // Phase 4: Show critical dialogs (NO lock held, may block on user input)
-        for critical in &criticals {
-            let result = show_dialog(
-                "緊急通知",
-                &format!("{}\n{}", critical.title, critical.body),
-            );
-            if result.as_deref() == Some("open_app") {
-                if let Err(err) = std::process::Command::new("open")
-                    .arg("-b")
-                    .arg(&critical.bundle_id)
-                    .spawn()
-                {
-                    warn!("failed to open app {}: {err}", critical.bundle_id);
-                }
-            }
-        }
-
-        thread::sleep(Duration::from_secs(POLL_INTERVAL_SECONDS));
-    });
-}
+/// This launch's counter and mode; quitting from the tray is a clean run.
+struct Launch(LaunchCounter, LaunchMode);
 
 fn handle_tray_menu_event(app: &AppHandle, id: &str) {
     match id {
         "quit" => {
+            // Abandon in-flight analysis so it cannot hold the process open.
+            app.state::<SharedLlm>().0.request_shutdown();
+            if let Ok(guard) = app.state::<SharedOrchestrator>().0.lock() {
+                if let Err(err) = guard.config().flush() {
+                    warn!("failed to write staged config files: {err:#}");
+                }
+            }
+            let launch = app.state::<Launch>();
+            if launch.1 == LaunchMode::Normal {
+                launch.0.mark_clean();
+            }
             app.exit(0);
         }
         "clear_all" => {
@@ -340,16 +52,64 @@ fn handle_tray_menu_event(app: &AppHandle, id: &str) {
                 show_notification("通知クリア", &format!("{}件を削除しました", cleared.0));
             }
         }
+        "pause_analysis" => {
+            let enabled = !app.state::<SharedLlm>().0.analysis_enabled();
+            if let Err(err) = apply_analysis_enabled(app, enabled) {
+                warn!("failed to toggle AI analysis: {err:#}");
+            }
+        }
+        "last_summary" => {
+            let summary = app
+                .state::<SharedOrchestrator>()
+                .0
+                .lock()
+                .ok()
+                .and_then(|guard| guard.last_summary());
+            match summary {
+                Some(summary) => {
+                    let created = chrono::DateTime::from_timestamp(summary.created_at, 0)
+                        .map(|dt| {
+                            dt.with_timezone(&chrono::Local)
+                                .format("%m/%d %H:%M")
+                                .to_string()
+                        })
+                        .unwrap_or_default();
+                    show_notification(&format!("前回の要約 ({created})"), &summary.text);
+                }
+                None => show_notification("前回の要約", "まだ要約はありません"),
+            }
+        }
         _ => {}
     }
 }
 
 fn setup_tray(app: &tauri::App) -> Result<tauri::tray::TrayIcon, Box<dyn std::error::Error>> {
+    let summary_item =
+        MenuItem::with_id(app, "last_summary", "前回の要約を表示", true, None::<&str>)?;
     let clear_item = MenuItem::with_id(app, "clear_all", "全通知をクリア", true, None::<&str>)?;
+    let paused = !app.state::<SharedLlm>().0.analysis_enabled();
+    let pause_item = CheckMenuItem::with_id(
+        app,
+        "pause_analysis",
+        "AI分析を一時停止",
+        true,
+        paused,
+        None::<&str>,
+    )?;
     let separator = PredefinedMenuItem::separator(app)?;
     let quit_item = MenuItem::with_id(app, "quit", "終了", true, None::<&str>)?;
 
-    let menu = Menu::with_items(app, &[&clear_item, &separator, &quit_item])?;
+    let menu = Menu::with_items(
+        app,
+        &[
+            &summary_item,
+            &clear_item,
+            &pause_item,
+            &separator,
+            &quit_item,
+        ],
+    )?;
+    app.manage(PauseAnalysisItem(pause_item));
 
     let tray = TrayIconBuilder::new()
         .menu(&menu)
@@ -379,36 +139,155 @@ fn main() {
     dotenvy::dotenv().ok();
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    let llm = Arc::new(LlmClient::new());
+    let launch_config = ConfigStore::new(notify_config_dir());
+    let launches = LaunchCounter::new(launch_config.clone());
+    let launch_mode = launches.begin();
+    // Settled before the orchestrator writes its first files.
+    if launch_config.is_first_launch() {
+        info!("first launch; starting onboarding");
+    }
+    let config = match launch_mode {
+        LaunchMode::Normal => launch_config.clone(),
+        LaunchMode::Safe { crashes } => {
+            warn!("starting in safe mode after {crashes} unfinished launches");
+            ConfigStore::detached(notify_config_dir())
+        }
+    };
+    let llm = Arc::new(LlmClient::new(&config));
+    if launch_mode != LaunchMode::Normal {
+        if let Err(err) = llm.set_analysis_enabled(false) {
+            warn!("failed to pause analysis for safe mode: {err:#}");
+        }
+    }
 
-    let orchestrator = match NotifyOrchestrator::new() {
-        Ok(orchestrator) => Arc::new(Mutex::new(orchestrator)),
+    let orchestrator = match NotifyOrchestrator::new(config.clone()) {
+        Ok(orchestrator) => orchestrator,
         Err(err) => {
             show_startup_error_dialog(&format!("{err:#}"));
             eprintln!("failed to initialize notify: {err:#}");
             std::process::exit(1);
         }
     };
+    let revision = orchestrator.revision_counter();
+    let orchestrator = Arc::new(Mutex::new(orchestrator));
 
     tauri::Builder::default()
         .manage(SharedLlm(llm.clone()))
         .manage(SharedOrchestrator(orchestrator))
+        .manage(revision)
+        .manage(Launch(launches.clone(), launch_mode))
         .invoke_handler(tauri::generate_handler![
-            get_notification_groups,
-            clear_notification,
-            clear_app_notifications,
-            clear_all_notifications,
-            inject_dummy_notifications,
-            get_app_prompts,
-            set_app_prompt,
-            delete_app_prompt,
-            get_ignored_apps,
-            add_ignored_app,
-            remove_ignored_app,
-            get_llm_settings,
-            set_llm_model,
-            hide_main_window,
-            open_app
+            commands::get_notification_groups,
+            commands::get_changes_since,
+            commands::get_notification_tree,
+            commands::get_notifications_by_time_bucket,
+            commands::get_agenda,
+            commands::get_raw_response,
+            commands::get_rule_precedence,
+            commands::get_escalation_keywords,
+            commands::set_escalation_keywords,
+            commands::get_collection_summary,
+            commands::mark_notifications_read,
+            commands::triage_next,
+            commands::set_notification_pinned,
+            commands::get_focus_status,
+            commands::get_store_health,
+            commands::get_blob_stats,
+            commands::get_schema_info,
+            commands::get_session_notifications,
+            commands::get_app_notifications,
+            commands::get_stale_assertions,
+            commands::get_digest,
+            commands::catch_me_up,
+            commands::summarize_given,
+            commands::get_last_summary,
+            commands::get_summary_history,
+            commands::regenerate_summary,
+            commands::get_app_order,
+            commands::set_app_order,
+            commands::clear_notification,
+            commands::clear_app_notifications,
+            commands::clear_all_notifications,
+            commands::get_storage_usage,
+            commands::run_maintenance_now,
+            commands::set_storage_settings,
+            commands::undo_clear,
+            commands::restore_notification,
+            commands::get_recent_urgency_changes,
+            commands::get_recently_cleared,
+            commands::set_focus_start_lookback,
+            commands::set_critical_min_confidence,
+            commands::set_daily_rollup_time,
+            commands::get_away_settings,
+            commands::set_away_settings,
+            commands::set_defer_analysis_idle_secs,
+            commands::set_max_analysis_per_poll,
+            commands::set_max_parse_attempts,
+            commands::get_onboarding_state,
+            commands::verify_full_disk_access,
+            commands::choose_onboarding_model,
+            commands::confirm_ollama,
+            commands::choose_interruption_threshold,
+            commands::send_test_notification,
+            commands::skip_onboarding_step,
+            commands::reset_onboarding,
+            commands::get_message_templates,
+            commands::set_message_templates,
+            commands::get_network_settings,
+            commands::set_network_settings,
+            commands::test_llm_connection,
+            commands::set_ignore_interruption_level,
+            commands::set_incremental_sort,
+            commands::set_group_by_category,
+            commands::set_group_by_account,
+            commands::set_title_merge_max_chars,
+            commands::set_output_language,
+            commands::set_undo_clear_window,
+            commands::set_restore_window,
+            commands::clear_where,
+            commands::group_action,
+            commands::snooze_app_until_focus_end,
+            commands::inject_dummy_notifications,
+            commands::get_app_prompts,
+            commands::get_unconfigured_apps,
+            commands::set_app_prompt,
+            commands::set_app_delivery,
+            commands::set_summary_fields,
+            commands::set_session_context,
+            commands::set_stale_assertion_policy,
+            commands::clear_session_context,
+            commands::delete_app_prompt,
+            commands::get_prompt_templates,
+            commands::apply_prompt_templates,
+            commands::get_ignored_apps,
+            commands::add_ignored_app,
+            commands::remove_ignored_app,
+            commands::get_notification_databases,
+            commands::add_notification_database,
+            commands::remove_notification_database,
+            commands::get_llm_settings,
+            commands::get_llm_metrics,
+            commands::get_backend_latency_stats,
+            commands::get_resource_estimate,
+            commands::get_llm_costs,
+            commands::set_llm_token_price,
+            commands::set_llm_model,
+            commands::set_analysis_enabled,
+            commands::set_analysis_log,
+            commands::set_model_lifecycle,
+            commands::set_keep_raw_responses,
+            commands::get_analysis_prompt_template,
+            commands::get_active_thresholds,
+            commands::reset_analysis_prompt_template,
+            commands::reanalyze_fallbacks,
+            commands::export_diagnostics,
+            commands::get_noise_report,
+            commands::export_noise_report,
+            commands::set_diagnostics_include_content,
+            commands::get_state_snapshot_enabled,
+            commands::set_state_snapshot_enabled,
+            commands::hide_main_window,
+            commands::open_app
         ])
         .on_window_event(|window, event| {
             if window.label() == "main" {
@@ -440,7 +319,23 @@ fn main() {
                 }
             }
             let orchestrator = app.state::<SharedOrchestrator>().0.clone();
-            start_polling_thread(app.handle().clone(), orchestrator, llm.clone());
+            app.manage(start_snapshot_writer(orchestrator.clone()));
+            if let Ok(guard) = orchestrator.lock() {
+                app.manage(guard.frontend_monitor());
+                start_webhook_sender(guard.webhook_outbox(), &guard.network_settings());
+            }
+            start_polling_thread(
+                app.handle().clone(),
+                orchestrator,
+                llm.clone(),
+                config.clone(),
+            );
+            start_launch_watch(
+                app.handle().clone(),
+                launch_config.clone(),
+                launches.clone(),
+                launch_mode,
+            );
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -1,4 +1,10 @@
-use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::llm::DEFAULT_CONFIDENCE;
+use crate::staleness::notification_due_time;
 
 #[derive(Debug, Clone)]
 pub struct Notification {
@@ -8,6 +14,24 @@ pub struct Notification {
     pub subtitle: String,
     pub bundle_id: String,
     pub timestamp: i64,
+    /// The sender's thread identifier, when the notification belongs to one.
+    pub thread_id: Option<String>,
+    /// Stays the same when the app updates the notification in place.
+    pub request_id: Option<String>,
+    /// The app's badge (unread count) at delivery time.
+    pub badge: Option<i64>,
+    pub interruption_level: InterruptionLevel,
+    /// Posted as a critical alert (critical sound), which only apps macOS
+    /// entitles for it can do.
+    pub system_critical: bool,
+    /// The app's category identifier (e.g. `MESSAGE_CATEGORY`), when it set one.
+    pub category: Option<String>,
+    /// Label of the extra notification database (another macOS user's store)
+    /// it was read from; `None` for this user's own store and other sources.
+    pub account: Option<String>,
+    /// Read by a catch-up or the focus-start lookback rather than as it
+    /// arrived.
+    pub backfill: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -22,6 +46,84 @@ pub struct AnalyzedNotification {
     pub summary_line: String,
     pub reason: String,
     pub timestamp: i64,
+    pub read: bool,
+    pub pinned: bool,
+    pub analyzed_by: AnalyzedBy,
+    pub low_confidence: bool,
+    /// The model's own confidence in the urgency, 0.0–1.0.
+    pub confidence: f64,
+    /// The `app_prompts` context that was included in the analysis prompt.
+    pub app_context: Option<String>,
+    /// The session-only context that was included in the analysis prompt.
+    pub session_context: Option<String>,
+    pub thread_id: Option<String>,
+    pub request_id: Option<String>,
+    pub interruption_level: InterruptionLevel,
+    pub system_critical: bool,
+    pub translated_summary: Option<String>,
+    /// Focus session the item was collected in; `None` outside any session.
+    pub session_id: Option<u64>,
+    pub category: Option<String>,
+    pub reason_code: ReasonCode,
+    pub is_time_sensitive: bool,
+    /// See [`Notification::account`].
+    pub account: Option<String>,
+    /// See [`NotificationAnalysis::raw_response`].
+    pub raw_response: Option<String>,
+    pub origin: Origin,
+    /// The urgency was raised to the floor of the interruption level.
+    pub clamped: bool,
+    /// The latest time or deadline the title, summary or body mentions, as
+    /// Unix seconds. See [`AnalyzedNotification::refresh_due_at`].
+    pub due_at: Option<i64>,
+}
+
+impl AnalyzedNotification {
+    /// Parses `due_at` from the current text. Called wherever the text is set
+    /// or changed, so the view and the session summary never parse it.
+    pub fn refresh_due_at(&mut self) {
+        self.due_at = notification_due_time(self);
+    }
+}
+
+/// An empty Medium item as the LLM would leave it; hand-made lists and test
+/// fixtures set the fields they care about.
+impl Default for AnalyzedNotification {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            title: String::new(),
+            body: String::new(),
+            subtitle: String::new(),
+            bundle_id: String::new(),
+            app_name: String::new(),
+            urgency: UrgencyLevel::Medium,
+            summary_line: String::new(),
+            reason: String::new(),
+            timestamp: 0,
+            read: false,
+            pinned: false,
+            analyzed_by: AnalyzedBy::Llm,
+            low_confidence: false,
+            confidence: DEFAULT_CONFIDENCE,
+            app_context: None,
+            session_context: None,
+            thread_id: None,
+            request_id: None,
+            interruption_level: InterruptionLevel::Active,
+            system_critical: false,
+            translated_summary: None,
+            session_id: None,
+            category: None,
+            reason_code: ReasonCode::Unknown,
+            is_time_sensitive: false,
+            account: None,
+            raw_response: None,
+            origin: Origin::Live,
+            clamped: false,
+            due_at: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -29,9 +131,138 @@ pub struct NotificationAnalysis {
     pub urgency: UrgencyLevel,
     pub summary_line: String,
     pub reason: String,
+    pub analyzed_by: AnalyzedBy,
+    /// The classification should be re-checked (vacuous reason, echoed title or fallback).
+    pub low_confidence: bool,
+    /// Confidence the model reported, 0.0–1.0; `DEFAULT_CONFIDENCE` when it
+    /// reported none or the model was not asked.
+    pub confidence: f64,
+    /// The content in the output language, when it was written in another one.
+    pub translated_summary: Option<String>,
+    pub reason_code: ReasonCode,
+    /// Tied to a deadline or a scheduled time. The model's answer, or else a
+    /// `Deadline` reason code or a time, deadline or meeting in the text.
+    pub is_time_sensitive: bool,
+    /// The model's reply before parsing; only kept with `keep_raw_responses`.
+    pub raw_response: Option<String>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+/// How a collected notification got into the list.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Origin {
+    /// Read by live polling as it arrived.
+    #[default]
+    Live,
+    /// Read afterwards by a catch-up or the focus-start lookback.
+    Backfill,
+    /// Injected by `inject_dummy_notifications`.
+    Simulated,
+}
+
+/// Why a notification got its urgency, from a fixed vocabulary, for
+/// filtering and statistics. `reason` keeps the model's own words. Anything
+/// not classified by the model is `Unknown`.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum ReasonCode {
+    Deadline,
+    DirectMention,
+    Incident,
+    Security,
+    Social,
+    Marketing,
+    Transactional,
+    #[default]
+    Unknown,
+}
+
+impl ReasonCode {
+    pub const ALL: [Self; 8] = [
+        Self::Deadline,
+        Self::DirectMention,
+        Self::Incident,
+        Self::Security,
+        Self::Social,
+        Self::Marketing,
+        Self::Transactional,
+        Self::Unknown,
+    ];
+
+    /// The code as the model writes it (`direct_mention`).
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Deadline => "deadline",
+            Self::DirectMention => "direct_mention",
+            Self::Incident => "incident",
+            Self::Security => "security",
+            Self::Social => "social",
+            Self::Marketing => "marketing",
+            Self::Transactional => "transactional",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// How a notification's urgency and summary line were produced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalyzedBy {
+    Llm,
+    /// The LLM was unreachable, timed out or returned an unusable response.
+    Fallback,
+    /// Collected while AI analysis was paused.
+    Disabled,
+    /// Fixed by a rule (an escalation keyword) without asking the LLM.
+    Rule,
+}
+
+/// The interruption level the sender requested. Missing or unknown values
+/// are read as `Active`, the level of an ordinary notification.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum InterruptionLevel {
+    Passive,
+    #[default]
+    Active,
+    TimeSensitive,
+    Critical,
+}
+
+impl InterruptionLevel {
+    /// `UNNotificationInterruptionLevel` raw values.
+    pub fn from_raw(raw: i64) -> Self {
+        match raw {
+            0 => Self::Passive,
+            2 => Self::TimeSensitive,
+            3 => Self::Critical,
+            _ => Self::Active,
+        }
+    }
+
+    /// The least urgent level a notification at this interruption level may
+    /// be classified as, if any.
+    pub fn urgency_floor(self) -> Option<UrgencyLevel> {
+        match self {
+            Self::TimeSensitive => Some(UrgencyLevel::High),
+            Self::Critical => Some(UrgencyLevel::Critical),
+            Self::Passive | Self::Active => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Passive => "passive",
+            Self::Active => "active",
+            Self::TimeSensitive => "time-sensitive",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum UrgencyLevel {
     Critical,
@@ -41,6 +272,16 @@ pub enum UrgencyLevel {
 }
 
 impl UrgencyLevel {
+    /// Index into `[critical, high, medium, low]` count arrays; lower is more urgent.
+    pub fn rank(self) -> usize {
+        match self {
+            Self::Critical => 0,
+            Self::High => 1,
+            Self::Medium => 2,
+            Self::Low => 3,
+        }
+    }
+
     pub fn label(self) -> &'static str {
         match self {
             Self::Critical => "URGENT",
@@ -60,17 +301,103 @@ impl UrgencyLevel {
     }
 }
 
+/// A notification field `summary_line_from_fields` can fall back to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryField {
+    Title,
+    Subtitle,
+    Body,
+}
+
+/// Per-app override of how analyzed notifications are delivered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppDelivery {
+    /// Follow the global policy (dialog for critical notifications).
+    #[default]
+    Default,
+    /// Never show a dialog; notifications are only collected for the summary.
+    BatchOnly,
+    /// Show a dialog for every notification from this app.
+    AlwaysDialog,
+}
+
+/// Result of a one-shot catch-up over recent notifications.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatchUpResult {
+    pub summary: String,
+    /// Critical notifications, newest first.
+    pub criticals: Vec<UiNotification>,
+    /// `[critical, high, medium, low]`
+    pub counts: [usize; 4],
+}
+
+/// What a [`SessionSummary`] covers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SummaryKind {
+    /// One focus session (or a hand-triggered regeneration).
+    #[default]
+    Session,
+    /// The end-of-day rollup over every session and loose notification.
+    Daily,
+    /// The catch-up shown on return from being away. Never stored.
+    Away,
+}
+
+/// The summary shown when a focus session ends, kept so it can be re-read later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSummary {
+    pub text: String,
+    pub created_at: i64,
+    pub notification_count: usize,
+    /// Focus time with sleep excluded, when the session was observed.
+    #[serde(default)]
+    pub protected_secs: Option<i64>,
+    /// Time the Mac slept during the session.
+    #[serde(default)]
+    pub slept_secs: i64,
+    #[serde(default)]
+    pub kind: SummaryKind,
+    /// Start of the summarized session, when one was observed.
+    #[serde(default)]
+    pub started_at: Option<i64>,
+    /// Id of the summarized session, matching `AnalyzedNotification::session_id`.
+    #[serde(default)]
+    pub session_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusStatus {
+    pub active: bool,
+    /// Unix time the active focus schedule ends, when known.
+    pub focus_ends_at: Option<i64>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FocusState {
     Active,
     Inactive,
+    /// The assertions file could not be parsed even after retrying, most
+    /// likely because macOS was still writing it. Keep the previous state.
+    Unknown,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ParsedPlist {
     pub title: String,
     pub body: String,
     pub subtitle: String,
+    pub thread_id: Option<String>,
+    pub request_id: Option<String>,
+    pub badge: Option<i64>,
+    pub interruption_level: InterruptionLevel,
+    pub system_critical: bool,
+    pub category: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -78,6 +405,9 @@ pub struct ParsedPlist {
 pub struct UiNotification {
     pub id: i64,
     pub title: String,
+    /// The title, or title and subtitle merged under
+    /// `title_merge_max_chars`.
+    pub headline: String,
     pub body: String,
     pub subtitle: String,
     pub bundle_id: String,
@@ -88,13 +418,354 @@ pub struct UiNotification {
     pub summary_line: String,
     pub reason: String,
     pub timestamp: i64,
+    pub read: bool,
+    pub pinned: bool,
+    pub analyzed_by: AnalyzedBy,
+    pub low_confidence: bool,
+    pub confidence: f64,
+    pub context_applied: bool,
+    pub app_context: Option<String>,
+    pub session_context: Option<String>,
+    pub thread_id: Option<String>,
+    pub interruption_level: InterruptionLevel,
+    pub system_critical: bool,
+    pub translated_summary: Option<String>,
+    pub session_id: Option<u64>,
+    pub category: Option<String>,
+    pub reason_code: ReasonCode,
+    /// See [`AnalyzedNotification::due_at`]; the view flags the item once it
+    /// has passed.
+    pub due_at: Option<i64>,
+    pub is_time_sensitive: bool,
+    pub account: Option<String>,
+    pub provenance: Provenance,
+}
+
+impl From<&AnalyzedNotification> for UiNotification {
+    fn from(item: &AnalyzedNotification) -> Self {
+        Self {
+            id: item.id,
+            title: item.title.clone(),
+            headline: item.title.clone(),
+            body: item.body.clone(),
+            subtitle: item.subtitle.clone(),
+            bundle_id: item.bundle_id.clone(),
+            app_name: item.app_name.clone(),
+            urgency_level: item.urgency,
+            urgency_label: item.urgency.label().to_string(),
+            urgency_color: item.urgency.color().to_string(),
+            summary_line: item.summary_line.clone(),
+            reason: item.reason.clone(),
+            timestamp: item.timestamp,
+            read: item.read,
+            pinned: item.pinned,
+            analyzed_by: item.analyzed_by,
+            low_confidence: item.low_confidence,
+            confidence: item.confidence,
+            context_applied: item.app_context.is_some(),
+            app_context: item.app_context.clone(),
+            session_context: item.session_context.clone(),
+            thread_id: item.thread_id.clone(),
+            interruption_level: item.interruption_level,
+            system_critical: item.system_critical,
+            translated_summary: item.translated_summary.clone(),
+            session_id: item.session_id,
+            category: item.category.clone(),
+            reason_code: item.reason_code,
+            due_at: item.due_at,
+            is_time_sensitive: item.is_time_sensitive,
+            account: item.account.clone(),
+            provenance: Provenance::from(item),
+        }
+    }
+}
+
+/// Why a notification is shown the way it is, read off the flags the
+/// pipeline stored on it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Provenance {
+    pub source: Origin,
+    /// The rules that decided the urgency, in precedence order.
+    pub matched_rules: Vec<Rule>,
+    pub clamped: bool,
+    /// A rule fixed it at critical ahead of the ignore and mute lists.
+    pub broke_through: bool,
+}
+
+impl From<&AnalyzedNotification> for Provenance {
+    fn from(item: &AnalyzedNotification) -> Self {
+        let broke_through = item.analyzed_by == AnalyzedBy::Rule;
+        let mut matched_rules = Vec::new();
+        if broke_through {
+            matched_rules.push(if item.system_critical {
+                Rule::SystemCritical
+            } else {
+                Rule::EscalationKeyword
+            });
+        }
+        if item.clamped {
+            matched_rules.push(Rule::InterruptionLevel);
+        }
+        match item.analyzed_by {
+            AnalyzedBy::Disabled => matched_rules.push(Rule::AnalysisPaused),
+            AnalyzedBy::Llm => matched_rules.push(Rule::LlmAnalysis),
+            AnalyzedBy::Fallback => matched_rules.push(Rule::Fallback),
+            AnalyzedBy::Rule => {}
+        }
+        Self {
+            source: item.origin,
+            matched_rules,
+            clamped: item.clamped,
+            broke_through,
+        }
+    }
+}
+
+/// One entry of `get_rule_precedence`, highest precedence first.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleInfo {
+    pub rule: Rule,
+    pub description: &'static str,
+}
+
+/// Rules that decide whether a notification is collected and how urgent it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    SystemCritical,
+    EscalationKeyword,
+    IgnoredApp,
+    MutedApp,
+    InterruptionLevel,
+    AnalysisPaused,
+    LlmAnalysis,
+    Fallback,
+}
+
+/// A collected app with no prompt context and not ignored, for nudging the
+/// user to describe it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnconfiguredApp {
+    pub bundle_id: String,
+    pub app_name: String,
+    /// Collected notifications from the app.
+    pub count: usize,
+}
+
+/// Which of an app's notifications a group action covers. With
+/// `group_by_account`, this user's own store and each extra account have a
+/// group of their own; otherwise one group covers them all.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "name", rename_all = "camelCase")]
+pub enum AccountScope {
+    All,
+    Own,
+    Named(String),
+}
+
+impl AccountScope {
+    /// The scope of the account-split group for `account`.
+    pub fn of_account(account: Option<&str>) -> Self {
+        account.map_or(Self::Own, |name| Self::Named(name.to_string()))
+    }
+
+    pub fn matches(&self, account: Option<&str>) -> bool {
+        match self {
+            Self::All => true,
+            Self::Own => account.is_none(),
+            Self::Named(name) => account == Some(name.as_str()),
+        }
+    }
+}
+
+/// Per-app state returned after a group action.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupState {
+    pub bundle_id: String,
+    pub remaining: usize,
+    pub ignored: bool,
+    pub muted_until: Option<i64>,
+    /// Hidden from the view until the current focus session ends.
+    pub snoozed: bool,
+    pub context: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupActionResult {
+    pub affected: usize,
+    pub new_state: GroupState,
+}
+
+/// Outcome of a triage keypress plus the item to show next, so the list can
+/// advance without a second round-trip. `next` is `None` outside a triage
+/// pass or once the pass is done.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TriageStep<T> {
+    pub result: T,
+    pub next: Option<UiNotification>,
+}
+
+/// A client-supplied notification for `summarize_given`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UiNotificationInput {
+    pub app_name: String,
+    pub urgency: UrgencyLevel,
+    #[serde(default)]
+    pub summary_line: String,
+    /// Summarized in place of an empty `summary_line`.
+    #[serde(default)]
+    pub body: String,
+}
+
+/// One clear operation that `undo_clear` can still restore.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentlyCleared {
+    pub cleared_at: i64,
+    pub notifications: Vec<UiNotification>,
+}
+
+/// An item whose urgency a reanalysis changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UrgencyChange {
+    pub id: i64,
+    pub from: UrgencyLevel,
+    pub to: UrgencyLevel,
+}
+
+/// Payload of `urgency-changed`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UrgencyChangedEvent {
+    /// Revision of the collected items once the changes were applied.
+    pub revision: u64,
+    pub changes: Vec<UrgencyChange>,
+}
+
+/// Payload of `notifications-updated`.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationsUpdatedEvent {
+    pub revision: u64,
+}
+
+/// Compact counts for the header status widget.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionSummary {
+    pub total: usize,
+    pub unread: usize,
+    pub pinned: usize,
+    /// `[critical, high, medium, low]`
+    pub by_urgency: [usize; 4],
+    /// Counts per reason code; codes with no notification are left out.
+    pub by_reason_code: BTreeMap<ReasonCode, usize>,
+    pub apps: usize,
 }
 
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UiNotificationGroup {
+    pub bundle_id: String,
+    /// Set only with `group_by_account`, which keeps each account's
+    /// notifications from an app in a group of their own.
+    pub account: Option<String>,
+    /// What the group's actions cover; sent back with them.
+    pub scope: AccountScope,
+    pub app_name: String,
+    pub icon_base64: Option<String>,
+    pub notifications: Vec<UiNotification>,
+    /// How many of the app's notifications were left out and why, e.g.
+    /// 「このアプリの3件はミュート中のため非表示」.
+    pub note: Option<String>,
+}
+
+/// `get_notification_groups` with the revision the groups were built at, to
+/// pass to `get_changes_since` later.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UiNotificationGroups {
+    pub revision: u64,
+    pub groups: Arc<Vec<UiNotificationGroup>>,
+}
+
+/// An app node of `get_notification_tree`: its threads, newest first.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UiAppNode {
     pub bundle_id: String,
     pub app_name: String,
     pub icon_base64: Option<String>,
+    pub count: usize,
+    pub unread: usize,
+    /// The app's newest notification.
+    pub headline: UiNotification,
+    pub threads: Vec<UiThreadNode>,
+}
+
+/// Local calendar-day range of `get_notifications_by_time_bucket`. Weeks
+/// start on Monday.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TimeBucket {
+    Today,
+    Yesterday,
+    /// Earlier this week, before yesterday.
+    ThisWeek,
+    Older,
+}
+
+impl TimeBucket {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Today => "今日",
+            Self::Yesterday => "昨日",
+            Self::ThisWeek => "今週",
+            Self::Older => "それ以前",
+        }
+    }
+}
+
+/// One non-empty bucket of the chronological view, newest first.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UiTimeBucket {
+    pub bucket: TimeBucket,
+    pub label: &'static str,
+    pub notifications: Vec<UiNotification>,
+}
+
+/// One entry of `get_agenda`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UiAgendaItem {
+    /// Unix time of the latest time or deadline the notification mentions;
+    /// `None` when it names none and the entry sits at its delivery time.
+    pub due_at: Option<i64>,
+    pub notification: UiNotification,
+}
+
+/// A thread under an app. Notifications without a thread identifier each
+/// form their own single-item node. With `group_by_category`, threads are
+/// split by category and kept next to the other threads of their category,
+/// and category items without a thread share one node.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UiThreadNode {
+    pub thread_id: Option<String>,
+    /// Set only with `group_by_category`.
+    pub category: Option<String>,
+    pub count: usize,
+    pub unread: usize,
+    /// The thread's newest notification, shown when the thread is collapsed.
+    pub headline: UiNotification,
     pub notifications: Vec<UiNotification>,
 }
@@ -0,0 +1,500 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Days, Local, NaiveDate};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::config_store::{ConfigFile, ConfigStore};
+use crate::models::{AnalyzedNotification, ReasonCode, UrgencyLevel};
+use crate::storage::{file_size, prune_dated, Maintainable, StoreLimit};
+
+/// Default age limit for `app_stats.json`.
+const STATS_RETENTION_DAYS: u64 = 90;
+/// Longest period a noise report covers; older days are pruned by default.
+pub const MAX_REPORT_DAYS: u32 = STATS_RETENTION_DAYS as u32;
+/// Low-urgency summary lines kept per app and day as report examples.
+const EXAMPLES_PER_DAY: usize = 3;
+const EXAMPLES_PER_SUGGESTION: usize = 3;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct DailyAppStats {
+    #[serde(default)]
+    app_name: String,
+    /// `[critical, high, medium, low]`
+    counts: [u64; 4],
+    low_examples: Vec<String>,
+    /// Low notifications per reason code.
+    #[serde(default)]
+    low_reasons: BTreeMap<ReasonCode, u64>,
+}
+
+type DailyStats = BTreeMap<String, BTreeMap<String, DailyAppStats>>;
+
+/// Urgency counts per local date and app, persisted in `app_stats.json`.
+/// Recording only stages the file; the poll loop flushes the config store
+/// once the orchestrator lock is released.
+#[derive(Debug)]
+pub struct AppStatsLedger {
+    days: Mutex<DailyStats>,
+    store: ConfigStore,
+}
+
+impl AppStatsLedger {
+    pub fn load(store: &ConfigStore) -> Self {
+        let days = match store.read(ConfigFile::AppStats) {
+            Some(content) => serde_json::from_str(&content).unwrap_or_else(|err| {
+                warn!("Failed to parse app_stats.json: {err:#}");
+                DailyStats::new()
+            }),
+            None => DailyStats::new(),
+        };
+        Self {
+            days: Mutex::new(days),
+            store: store.clone(),
+        }
+    }
+
+    /// Counts newly collected notifications under the day they were delivered.
+    pub fn record(&mut self, items: &[AnalyzedNotification]) -> Result<()> {
+        if items.is_empty() {
+            return Ok(());
+        }
+        let days = self
+            .days
+            .get_mut()
+            .map_err(|err| anyhow!("app stats lock error: {err}"))?;
+        for item in items {
+            let day = days
+                .entry(local_date(item.timestamp).format("%Y-%m-%d").to_string())
+                .or_default()
+                .entry(item.bundle_id.clone())
+                .or_default();
+            day.app_name.clone_from(&item.app_name);
+            day.counts[item.urgency.rank()] += 1;
+            if item.urgency == UrgencyLevel::Low {
+                *day.low_reasons.entry(item.reason_code).or_default() += 1;
+            }
+            if item.urgency == UrgencyLevel::Low
+                && day.low_examples.len() < EXAMPLES_PER_DAY
+                && !item.summary_line.is_empty()
+                && !day.low_examples.contains(&item.summary_line)
+            {
+                day.low_examples.push(item.summary_line.clone());
+            }
+        }
+        self.store.stage(ConfigFile::AppStats, &*days)
+    }
+
+    /// Per-app totals for the `days` days up to and including `today`.
+    pub fn totals(&self, today: NaiveDate, days: u32) -> Vec<AppNoiseStats> {
+        let Ok(stats) = self.days.lock() else {
+            return Vec::new();
+        };
+        let mut totals: BTreeMap<&str, AppNoiseStats> = BTreeMap::new();
+        for back in 0..days {
+            let Some(date) = today.checked_sub_days(Days::new(back.into())) else {
+                continue;
+            };
+            let Some(apps) = stats.get(&date.format("%Y-%m-%d").to_string()) else {
+                continue;
+            };
+            for (bundle_id, day) in apps {
+                let entry = totals.entry(bundle_id).or_insert_with(|| AppNoiseStats {
+                    bundle_id: bundle_id.clone(),
+                    app_name: day.app_name.clone(),
+                    ..AppNoiseStats::default()
+                });
+                entry.total += day.counts.iter().sum::<u64>();
+                entry.low += day.counts[UrgencyLevel::Low.rank()];
+                for (code, count) in &day.low_reasons {
+                    *entry.low_reasons.entry(*code).or_default() += count;
+                }
+                // Newest days come first, so examples favor recent noise.
+                for example in &day.low_examples {
+                    if !entry.examples.contains(example) {
+                        entry.examples.push(example.clone());
+                    }
+                }
+            }
+        }
+        totals.into_values().collect()
+    }
+}
+
+impl Maintainable for AppStatsLedger {
+    fn store_name(&self) -> &'static str {
+        "app_stats"
+    }
+
+    fn default_limit(&self) -> StoreLimit {
+        StoreLimit {
+            max_age_days: Some(STATS_RETENTION_DAYS),
+            max_bytes: None,
+        }
+    }
+
+    fn size_bytes(&self) -> u64 {
+        file_size(&self.store.path(ConfigFile::AppStats))
+    }
+
+    fn prune(&self, limit: StoreLimit, today: NaiveDate) -> Result<usize> {
+        let pruned = {
+            let mut days = self
+                .days
+                .lock()
+                .map_err(|err| anyhow!("app stats lock error: {err}"))?;
+            let pruned = prune_dated(&mut days, limit, today)?;
+            if pruned > 0 {
+                self.store.stage(ConfigFile::AppStats, &*days)?;
+            }
+            pruned
+        };
+        if pruned > 0 {
+            self.store.flush()?;
+        }
+        Ok(pruned)
+    }
+}
+
+fn local_date(timestamp: i64) -> NaiveDate {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|utc| utc.with_timezone(&Local).date_naive())
+        .unwrap_or_else(|| Local::now().date_naive())
+}
+
+/// One app's notifications over the report window.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AppNoiseStats {
+    pub bundle_id: String,
+    pub app_name: String,
+    pub total: u64,
+    pub low: u64,
+    pub examples: Vec<String>,
+    pub low_reasons: BTreeMap<ReasonCode, u64>,
+}
+
+/// When an app counts as noise worth silencing in System Settings. Stored in
+/// `settings.json` under `noise_thresholds`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoiseThresholds {
+    /// Fewer Low notifications than this are not worth a suggestion.
+    pub min_low_count: u64,
+    /// Share of the app's own notifications that must be Low, 0.0–1.0.
+    pub min_low_share: f64,
+    /// Stop suggesting once this share of all Low notifications is covered.
+    pub target_coverage: f64,
+    pub max_suggestions: usize,
+}
+
+impl Default for NoiseThresholds {
+    fn default() -> Self {
+        Self {
+            min_low_count: 10,
+            min_low_share: 0.8,
+            target_coverage: 0.8,
+            max_suggestions: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoiseSuggestion {
+    pub bundle_id: String,
+    pub app_name: String,
+    pub low: u64,
+    pub total: u64,
+    /// Share of this app's notifications that were Low.
+    pub low_share: f64,
+    /// Share of all Low notifications in the window that came from this app.
+    pub share_of_noise: f64,
+    pub examples: Vec<String>,
+    /// The app's Low notifications per reason code.
+    pub low_reasons: BTreeMap<ReasonCode, u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NoiseReport {
+    pub days: u32,
+    pub total_low: u64,
+    /// Share of all Low notifications produced by the suggested apps.
+    pub covered_share: f64,
+    pub suggestions: Vec<NoiseSuggestion>,
+}
+
+/// Ranks apps by Low-urgency volume and suggests the smallest set that covers
+/// `target_coverage` of the noise. Ignored apps are already silent here and are
+/// left out of both the suggestions and the totals.
+pub fn build_noise_report(
+    stats: &[AppNoiseStats],
+    ignored: &[String],
+    thresholds: &NoiseThresholds,
+    days: u32,
+) -> NoiseReport {
+    let considered: Vec<&AppNoiseStats> = stats
+        .iter()
+        .filter(|app| !ignored.contains(&app.bundle_id))
+        .collect();
+    let total_low: u64 = considered.iter().map(|app| app.low).sum();
+
+    let mut candidates: Vec<&AppNoiseStats> = considered
+        .into_iter()
+        .filter(|app| {
+            app.low > 0
+                && app.low >= thresholds.min_low_count
+                && share(app.low, app.total) >= thresholds.min_low_share
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.low.cmp(&a.low).then(a.bundle_id.cmp(&b.bundle_id)));
+
+    let mut suggestions = Vec::new();
+    let mut covered = 0;
+    for app in candidates {
+        if suggestions.len() >= thresholds.max_suggestions
+            || share(covered, total_low) >= thresholds.target_coverage
+        {
+            break;
+        }
+        covered += app.low;
+        suggestions.push(NoiseSuggestion {
+            bundle_id: app.bundle_id.clone(),
+            app_name: app.app_name.clone(),
+            low: app.low,
+            total: app.total,
+            low_share: share(app.low, app.total),
+            share_of_noise: share(app.low, total_low),
+            examples: app
+                .examples
+                .iter()
+                .take(EXAMPLES_PER_SUGGESTION)
+                .cloned()
+                .collect(),
+            low_reasons: app.low_reasons.clone(),
+        });
+    }
+
+    NoiseReport {
+        days,
+        total_low,
+        covered_share: share(covered, total_low),
+        suggestions,
+    }
+}
+
+fn share(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64
+    }
+}
+
+/// The report as a Markdown checklist for working through System Settings.
+pub fn noise_report_markdown(report: &NoiseReport) -> String {
+    let mut lines = vec![format!("# 通知ノイズレポート（直近{}日）", report.days)];
+    if report.suggestions.is_empty() {
+        lines.push(String::new());
+        lines.push("消音を提案するアプリはありません。".to_string());
+        return lines.join("\n");
+    }
+    lines.push(String::new());
+    lines.push(format!(
+        "次の{}個のアプリが低優先通知の{:.0}%（{}件中）を占めています。システム設定の集中モードや通知設定で消音を検討してください。",
+        report.suggestions.len(),
+        report.covered_share * 100.0,
+        report.total_low
+    ));
+    for suggestion in &report.suggestions {
+        lines.push(String::new());
+        lines.push(format!(
+            "- [ ] **{}** (`{}`) — 低優先 {}件 / 全{}件、低優先通知全体の{:.0}%",
+            suggestion.app_name,
+            suggestion.bundle_id,
+            suggestion.low,
+            suggestion.total,
+            suggestion.share_of_noise * 100.0
+        ));
+        if !suggestion.low_reasons.is_empty() {
+            let mut reasons: Vec<_> = suggestion.low_reasons.iter().collect();
+            reasons.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+            let reasons: Vec<String> = reasons
+                .into_iter()
+                .map(|(code, count)| format!("{} {count}件", code.as_str()))
+                .collect();
+            lines.push(format!("  - 理由: {}", reasons.join("、")));
+        }
+        for example in &suggestion.examples {
+            lines.push(format!("  - 例: {example}"));
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::{Local, NaiveDate, TimeZone};
+
+    use super::{
+        build_noise_report, noise_report_markdown, AppNoiseStats, AppStatsLedger, NoiseThresholds,
+    };
+    use crate::config_store::ConfigStore;
+    use crate::models::{AnalyzedNotification, ReasonCode, UrgencyLevel};
+
+    fn app(bundle_id: &str, low: u64, total: u64) -> AppNoiseStats {
+        AppNoiseStats {
+            bundle_id: bundle_id.to_string(),
+            app_name: bundle_id.rsplit('.').next().unwrap().to_string(),
+            total,
+            low,
+            examples: vec![format!("{bundle_id} example")],
+            low_reasons: BTreeMap::new(),
+        }
+    }
+
+    fn thresholds() -> NoiseThresholds {
+        NoiseThresholds {
+            min_low_count: 5,
+            min_low_share: 0.5,
+            target_coverage: 0.8,
+            max_suggestions: 5,
+        }
+    }
+
+    #[test]
+    fn smallest_set_covering_target_is_suggested_loudest_first() {
+        let stats = vec![
+            app("com.example.b", 30, 35),
+            app("com.example.a", 50, 55),
+            app("com.example.c", 15, 20),
+            app("com.example.d", 5, 5),
+        ];
+
+        let report = build_noise_report(&stats, &[], &thresholds(), 7);
+
+        let ids: Vec<_> = report
+            .suggestions
+            .iter()
+            .map(|s| s.bundle_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["com.example.a", "com.example.b"]);
+        assert_eq!(report.total_low, 100);
+        assert!((report.covered_share - 0.8).abs() < 1e-9);
+        assert!((report.suggestions[0].share_of_noise - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn thresholds_filter_small_and_mostly_useful_apps() {
+        let stats = vec![
+            // Plenty of Low, but most of its notifications matter.
+            app("com.example.mixed", 40, 100),
+            app("com.example.quiet", 4, 4),
+            app("com.example.noisy", 20, 21),
+        ];
+
+        let report = build_noise_report(&stats, &[], &thresholds(), 7);
+
+        let ids: Vec<_> = report
+            .suggestions
+            .iter()
+            .map(|s| s.bundle_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["com.example.noisy"]);
+        assert_eq!(report.total_low, 64);
+    }
+
+    #[test]
+    fn ignored_apps_are_excluded_from_suggestions_and_totals() {
+        let stats = vec![app("com.example.a", 50, 50), app("com.example.b", 10, 10)];
+
+        let report = build_noise_report(&stats, &["com.example.a".to_string()], &thresholds(), 7);
+
+        assert_eq!(report.total_low, 10);
+        assert_eq!(report.suggestions.len(), 1);
+        assert_eq!(report.suggestions[0].bundle_id, "com.example.b");
+    }
+
+    #[test]
+    fn max_suggestions_caps_the_list() {
+        let stats: Vec<_> = (0..10)
+            .map(|i| app(&format!("com.example.{i}"), 10, 10))
+            .collect();
+        let limits = NoiseThresholds {
+            max_suggestions: 3,
+            ..thresholds()
+        };
+
+        let report = build_noise_report(&stats, &[], &limits, 7);
+
+        assert_eq!(report.suggestions.len(), 3);
+        assert!((report.covered_share - 0.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn markdown_lists_suggestions_with_examples() {
+        let mut stats = app("com.example.a", 10, 10);
+        stats.low_reasons = BTreeMap::from([(ReasonCode::Social, 3), (ReasonCode::Marketing, 7)]);
+        let report = build_noise_report(&[stats], &[], &thresholds(), 7);
+
+        let markdown = noise_report_markdown(&report);
+
+        assert!(markdown.starts_with("# 通知ノイズレポート（直近7日）"));
+        assert!(markdown.contains("- [ ] **a** (`com.example.a`) — 低優先 10件 / 全10件"));
+        assert!(markdown.contains("  - 理由: marketing 7件、social 3件"));
+        assert!(markdown.contains("  - 例: com.example.a example"));
+    }
+
+    #[test]
+    fn ledger_totals_span_the_requested_days() {
+        let dir = std::env::temp_dir().join(format!("notify-noise-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = ConfigStore::new(&dir);
+        let today = Local::now().date_naive();
+        let at = |date: NaiveDate| {
+            Local
+                .from_local_datetime(&date.and_hms_opt(12, 0, 0).unwrap())
+                .unwrap()
+                .timestamp()
+        };
+        let item = |id: i64, urgency: UrgencyLevel, timestamp: i64| AnalyzedNotification {
+            id,
+            bundle_id: "com.example.app".to_string(),
+            app_name: "App".to_string(),
+            urgency,
+            summary_line: format!("line {id}"),
+            timestamp,
+            reason_code: ReasonCode::Marketing,
+            ..Default::default()
+        };
+        let mut ledger = AppStatsLedger::load(&store);
+        let week_ago = today.checked_sub_days(chrono::Days::new(7)).unwrap();
+        ledger
+            .record(&[
+                item(1, UrgencyLevel::Low, at(today)),
+                item(2, UrgencyLevel::High, at(today)),
+                item(3, UrgencyLevel::Low, at(week_ago)),
+            ])
+            .unwrap();
+        // Staged until the poll loop flushes.
+        assert!(AppStatsLedger::load(&store).totals(today, 7).is_empty());
+        store.flush().unwrap();
+
+        let recent = AppStatsLedger::load(&store).totals(today, 7);
+        assert_eq!(recent.len(), 1);
+        assert_eq!((recent[0].low, recent[0].total), (1, 2));
+        assert_eq!(recent[0].examples, vec!["line 1".to_string()]);
+        assert_eq!(recent[0].app_name, "App");
+        assert_eq!(
+            recent[0].low_reasons,
+            BTreeMap::from([(ReasonCode::Marketing, 1)])
+        );
+        let longer = ledger.totals(today, 8);
+        assert_eq!((longer[0].low, longer[0].total), (2, 3));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,169 @@
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+
+/// Checks that System Events UI scripting is allowed. Returns `true` or
+/// `false` without prompting.
+const ACCESSIBILITY_SCRIPT: &str =
+    "tell application \"System Events\" to return UI elements enabled";
+
+/// Closes every alert in Notification Center whose description mentions the
+/// app name given as the first argument, and returns how many were closed.
+/// Relies on the accessibility tree of the current macOS release, so it is
+/// best effort only.
+const DISMISS_SCRIPT: &str = r#"on run argv
+    set appName to item 1 of argv
+    set closed to 0
+    tell application "System Events"
+        if not (exists process "NotificationCenter") then return closed
+        tell process "NotificationCenter"
+            if not (exists window 1) then return closed
+            repeat with elem in (entire contents of window 1)
+                try
+                    if (description of elem as text) contains appName then
+                        repeat with act in (actions of elem)
+                            if (description of act as text) is "Close" then
+                                perform act
+                                set closed to closed + 1
+                                exit repeat
+                            end if
+                        end repeat
+                    end if
+                end try
+            end repeat
+        end tell
+    end tell
+    return closed
+end run"#;
+
+/// Drives the real Notification Center. A trait so clearing can be tested
+/// without UI scripting.
+pub trait NotificationCenterUi {
+    fn accessibility_granted(&self) -> Result<bool>;
+    /// Dismisses the alerts of `app_name` and returns how many went.
+    fn dismiss_app_alerts(&self, app_name: &str) -> Result<usize>;
+}
+
+/// `NotificationCenterUi` through System Events and `osascript`.
+pub struct SystemEventsUi;
+
+impl NotificationCenterUi for SystemEventsUi {
+    fn accessibility_granted(&self) -> Result<bool> {
+        Ok(run_osascript(ACCESSIBILITY_SCRIPT, &[])? == "true")
+    }
+
+    fn dismiss_app_alerts(&self, app_name: &str) -> Result<usize> {
+        let output = run_osascript(DISMISS_SCRIPT, &[app_name])?;
+        output
+            .parse()
+            .with_context(|| format!("unexpected dismiss script output `{output}`"))
+    }
+}
+
+/// Arguments go to the script's `run` handler, so they need no escaping.
+fn run_osascript(script: &str, args: &[&str]) -> Result<String> {
+    let output = Command::new("/usr/bin/osascript")
+        .arg("-e")
+        .arg(script)
+        .args(args)
+        .output()
+        .context("failed to run osascript")?;
+    if !output.status.success() {
+        bail!(
+            "osascript failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Runs `clear_local` and then dismisses the app's alerts in Notification
+/// Center. Accessibility is checked first, so a refusal clears nothing.
+/// Failing to dismiss after the local clear is only logged: the local clear
+/// already happened and the system side is best effort.
+pub fn clear_with_system(
+    ui: &dyn NotificationCenterUi,
+    app_name: &str,
+    clear_local: impl FnOnce() -> Result<usize>,
+) -> Result<usize> {
+    let granted = ui
+        .accessibility_granted()
+        .context("cannot check accessibility permission")?;
+    if !granted {
+        bail!(
+            "accessibility permission is required to clear notifications in Notification Center; \
+allow Notify in System Settings > Privacy & Security > Accessibility"
+        );
+    }
+
+    let cleared = clear_local()?;
+    match ui.dismiss_app_alerts(app_name) {
+        Ok(dismissed) => info!("Dismissed {dismissed} {app_name} alert(s) in Notification Center"),
+        Err(err) => warn!("Cannot dismiss {app_name} alerts in Notification Center: {err:#}"),
+    }
+    Ok(cleared)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use anyhow::{anyhow, Result};
+
+    use super::{clear_with_system, NotificationCenterUi};
+
+    struct FakeUi {
+        granted: Result<bool, &'static str>,
+        dismiss: Result<usize, &'static str>,
+        dismissed_apps: RefCell<Vec<String>>,
+    }
+
+    impl FakeUi {
+        fn new(granted: Result<bool, &'static str>, dismiss: Result<usize, &'static str>) -> Self {
+            Self {
+                granted,
+                dismiss,
+                dismissed_apps: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl NotificationCenterUi for FakeUi {
+        fn accessibility_granted(&self) -> Result<bool> {
+            self.granted.map_err(|err| anyhow!(err))
+        }
+
+        fn dismiss_app_alerts(&self, app_name: &str) -> Result<usize> {
+            self.dismissed_apps.borrow_mut().push(app_name.to_string());
+            self.dismiss.map_err(|err| anyhow!(err))
+        }
+    }
+
+    #[test]
+    fn missing_permission_clears_nothing() {
+        for granted in [Ok(false), Err("osascript failed")] {
+            let ui = FakeUi::new(granted, Ok(0));
+            let mut local_cleared = false;
+            let err = clear_with_system(&ui, "Slack", || {
+                local_cleared = true;
+                Ok(3)
+            })
+            .unwrap_err();
+            assert!(format!("{err:#}").contains("accessibility permission"));
+            assert!(!local_cleared);
+            assert!(ui.dismissed_apps.borrow().is_empty());
+        }
+    }
+
+    #[test]
+    fn granted_permission_clears_locally_then_in_system() {
+        let ui = FakeUi::new(Ok(true), Ok(2));
+        assert_eq!(clear_with_system(&ui, "Slack", || Ok(3)).unwrap(), 3);
+        assert_eq!(*ui.dismissed_apps.borrow(), vec!["Slack".to_string()]);
+
+        let failing = FakeUi::new(Ok(true), Err("UI element not found"));
+        assert_eq!(clear_with_system(&failing, "Slack", || Ok(3)).unwrap(), 3);
+        assert_eq!(failing.dismissed_apps.borrow().len(), 1);
+    }
+}
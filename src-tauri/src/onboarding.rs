@@ -0,0 +1,216 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// First-launch steps, in the order they are asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnboardingStep {
+    /// The notification store is readable, which needs Full Disk Access.
+    FullDiskAccess,
+    /// An Ollama model is chosen; Ollama is the only backend.
+    ChooseModel,
+    /// Ollama answered a test request. Can be skipped and checked later.
+    ConfirmOllama,
+    /// The urgency at which the panel opens by itself, or none.
+    InterruptionThreshold,
+    /// A test banner was posted.
+    TestNotification,
+    Done,
+}
+
+pub const ONBOARDING_STEPS: [OnboardingStep; 5] = [
+    OnboardingStep::FullDiskAccess,
+    OnboardingStep::ChooseModel,
+    OnboardingStep::ConfirmOllama,
+    OnboardingStep::InterruptionThreshold,
+    OnboardingStep::TestNotification,
+];
+
+impl OnboardingStep {
+    pub fn optional(self) -> bool {
+        self == OnboardingStep::ConfirmOllama
+    }
+}
+
+/// Onboarding progress persisted in `settings.json`. The default is
+/// finished: settings written before onboarding existed, or unreadable
+/// ones, belong to existing users. Only a first launch starts from
+/// [`OnboardingProgress::fresh`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct OnboardingProgress {
+    #[serde(default)]
+    pub completed: Vec<OnboardingStep>,
+    #[serde(default)]
+    pub skipped: Vec<OnboardingStep>,
+}
+
+impl Default for OnboardingProgress {
+    fn default() -> Self {
+        Self {
+            completed: ONBOARDING_STEPS.to_vec(),
+            skipped: Vec::new(),
+        }
+    }
+}
+
+impl OnboardingProgress {
+    /// No step done yet.
+    pub fn fresh() -> Self {
+        Self {
+            completed: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+
+    pub fn next_step(&self) -> OnboardingStep {
+        ONBOARDING_STEPS
+            .into_iter()
+            .find(|step| !self.completed.contains(step) && !self.skipped.contains(step))
+            .unwrap_or(OnboardingStep::Done)
+    }
+
+    /// Marks `step` done. Only the current step can be completed, so the
+    /// order is kept even if the frontend sends a stale request.
+    pub fn complete(&mut self, step: OnboardingStep) -> Result<OnboardingStep> {
+        self.expect_current(step)?;
+        self.completed.push(step);
+        Ok(self.next_step())
+    }
+
+    pub fn skip(&mut self, step: OnboardingStep) -> Result<OnboardingStep> {
+        self.expect_current(step)?;
+        if !step.optional() {
+            bail!("onboarding step {step:?} cannot be skipped")
+        }
+        self.skipped.push(step);
+        Ok(self.next_step())
+    }
+
+    fn expect_current(&self, step: OnboardingStep) -> Result<()> {
+        let current = self.next_step();
+        if current == OnboardingStep::Done {
+            bail!("onboarding is already finished")
+        }
+        if step != current {
+            bail!("onboarding is at {current:?}, not {step:?}")
+        }
+        Ok(())
+    }
+}
+
+/// What `get_onboarding_state` returns.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OnboardingState {
+    pub step: OnboardingStep,
+    pub optional: bool,
+    pub completed: Vec<OnboardingStep>,
+    pub skipped: Vec<OnboardingStep>,
+}
+
+impl From<&OnboardingProgress> for OnboardingState {
+    fn from(progress: &OnboardingProgress) -> Self {
+        let step = progress.next_step();
+        Self {
+            step,
+            optional: step.optional(),
+            completed: progress.completed.clone(),
+            skipped: progress.skipped.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{OnboardingProgress, OnboardingStep, ONBOARDING_STEPS};
+    use crate::config_store::{ConfigFile, ConfigStore};
+    use crate::settings::{AppSettings, AppSettingsStore};
+
+    #[test]
+    fn steps_complete_in_order_and_only_the_optional_one_skips() {
+        let mut progress = OnboardingProgress::fresh();
+        assert_eq!(progress.next_step(), OnboardingStep::FullDiskAccess);
+
+        assert!(progress.complete(OnboardingStep::ChooseModel).is_err());
+        assert!(progress.skip(OnboardingStep::FullDiskAccess).is_err());
+        assert_eq!(
+            progress.complete(OnboardingStep::FullDiskAccess).unwrap(),
+            OnboardingStep::ChooseModel
+        );
+        // Completing a step twice is a stale request, not progress.
+        assert!(progress.complete(OnboardingStep::FullDiskAccess).is_err());
+        assert_eq!(
+            progress.complete(OnboardingStep::ChooseModel).unwrap(),
+            OnboardingStep::ConfirmOllama
+        );
+        assert_eq!(
+            progress.skip(OnboardingStep::ConfirmOllama).unwrap(),
+            OnboardingStep::InterruptionThreshold
+        );
+        assert_eq!(
+            progress
+                .complete(OnboardingStep::InterruptionThreshold)
+                .unwrap(),
+            OnboardingStep::TestNotification
+        );
+        assert_eq!(
+            progress.complete(OnboardingStep::TestNotification).unwrap(),
+            OnboardingStep::Done
+        );
+        assert!(progress.complete(OnboardingStep::Done).is_err());
+        assert_eq!(progress.skipped, vec![OnboardingStep::ConfirmOllama]);
+    }
+
+    fn temp_store(name: &str) -> (PathBuf, ConfigStore) {
+        let dir = std::env::temp_dir().join(format!(
+            "notify-onboarding-test-{}-{name}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        (dir.clone(), ConfigStore::new(dir))
+    }
+
+    #[test]
+    fn settings_from_before_onboarding_count_as_finished() {
+        let old: AppSettings = serde_json::from_str("{}").unwrap();
+        assert_eq!(old.onboarding.next_step(), OnboardingStep::Done);
+        assert_eq!(
+            AppSettings::default().onboarding.next_step(),
+            OnboardingStep::Done
+        );
+
+        let fresh = serde_json::to_string(&OnboardingProgress::fresh()).unwrap();
+        let saved: AppSettings =
+            serde_json::from_str(&format!("{{\"onboarding\": {fresh}}}")).unwrap();
+        assert_eq!(saved.onboarding.next_step(), ONBOARDING_STEPS[0]);
+    }
+
+    #[test]
+    fn only_a_first_launch_starts_onboarding() {
+        let step = |store: &ConfigStore| AppSettingsStore::load(store).get().onboarding.next_step();
+
+        // Nothing but the launch counter written yet.
+        let (dir, store) = temp_store("first-launch");
+        store
+            .save(ConfigFile::LaunchState, &serde_json::json!({}))
+            .unwrap();
+        assert_eq!(step(&store), ONBOARDING_STEPS[0]);
+        assert_eq!(step(&ConfigStore::detached(&dir)), OnboardingStep::Done);
+
+        // Files written later in the launch do not change the answer.
+        store
+            .save(ConfigFile::SessionIds, &serde_json::json!({}))
+            .unwrap();
+        assert_eq!(step(&store), ONBOARDING_STEPS[0]);
+
+        // An existing install that never saved settings.
+        let store = ConfigStore::new(&dir);
+        assert_eq!(step(&store), OnboardingStep::Done);
+
+        std::fs::write(store.path(ConfigFile::Settings), "{ not json").unwrap();
+        assert_eq!(step(&store), OnboardingStep::Done);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
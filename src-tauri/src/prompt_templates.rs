@@ -0,0 +1,49 @@
+use serde::Serialize;
+
+/// A built-in app prompt context, for `apply_prompt_templates`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplate {
+    pub bundle_id: &'static str,
+    pub app_name: &'static str,
+    pub context: &'static str,
+}
+
+/// Contexts for common apps, written to be useful as they are and as a
+/// starting point to edit.
+pub const PROMPT_TEMPLATES: [PromptTemplate; 6] = [
+    PromptTemplate {
+        bundle_id: "com.tinyspeck.slackmacgap",
+        app_name: "Slack",
+        context: "仕事用のチャット。自分宛てのメンションや DM、障害・インシデント対応のチャンネルは重要。チャンネルの雑談や bot の定期投稿は低い。",
+    },
+    PromptTemplate {
+        bundle_id: "com.apple.mail",
+        app_name: "Mail",
+        context: "メール。上司や取引先からの返信・依頼、期限のある連絡は重要。ニュースレター、広告、自動送信の通知メールは低い。",
+    },
+    PromptTemplate {
+        bundle_id: "com.apple.iCal",
+        app_name: "Calendar",
+        context: "予定のリマインダー。開始が近い会議や予定の変更・キャンセルは重要。招待への返信状況や先の予定の通知は低い。",
+    },
+    PromptTemplate {
+        bundle_id: "com.apple.reminders",
+        app_name: "Reminders",
+        context: "自分で登録したリマインダー。今日が期限のものや時刻指定のものは重要。期限のない項目は低い。",
+    },
+    PromptTemplate {
+        bundle_id: "com.apple.MobileSMS",
+        app_name: "Messages",
+        context: "家族や知人とのメッセージと SMS。家族からの連絡や急ぎの用件は重要。認証コードは自分で操作中のときだけ必要。キャリアや店舗からの案内は低い。",
+    },
+    PromptTemplate {
+        bundle_id: "com.github.GitHubClient",
+        app_name: "GitHub",
+        context: "開発の通知。自分へのレビュー依頼、メンション、CI の失敗は重要。他人の PR の更新や watch しているリポジトリの動きは低い。",
+    },
+];
+
+pub fn prompt_template(bundle_id: &str) -> Option<&'static PromptTemplate> {
+    PROMPT_TEMPLATES.iter().find(|t| t.bundle_id == bundle_id)
+}
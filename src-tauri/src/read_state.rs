@@ -0,0 +1,224 @@
+use std::collections::{BTreeMap, HashMap};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::config_store::{ConfigFile, ConfigStore};
+use crate::models::AnalyzedNotification;
+
+pub const READ_STATE_SCHEMA_VERSION: u32 = 1;
+/// Marks kept per kind; the oldest are dropped first. Catch-up only re-reads
+/// recent rows, so old marks would never be used.
+const MAX_MARKS: usize = 2000;
+
+/// A notification as the store identifies it. The delivery time is kept
+/// with the rowid because a recreated store hands out the same rowids again.
+type Mark = (i64, i64);
+
+/// Marks in the order they were set, looked up by key.
+#[derive(Debug, Default)]
+struct Marks {
+    by_seq: BTreeMap<u64, Mark>,
+    seq: HashMap<Mark, u64>,
+    next_seq: u64,
+}
+
+impl Marks {
+    fn contains(&self, mark: &Mark) -> bool {
+        self.seq.contains_key(mark)
+    }
+
+    /// Setting a mark again moves it to the newest end.
+    fn set(&mut self, mark: Mark, set: bool) {
+        if let Some(seq) = self.seq.remove(&mark) {
+            self.by_seq.remove(&seq);
+        }
+        if set {
+            self.by_seq.insert(self.next_seq, mark);
+            self.seq.insert(mark, self.next_seq);
+            self.next_seq += 1;
+            if self.by_seq.len() > MAX_MARKS {
+                if let Some((_, oldest)) = self.by_seq.pop_first() {
+                    self.seq.remove(&oldest);
+                }
+            }
+        }
+    }
+
+    /// Oldest first.
+    fn iter(&self) -> impl Iterator<Item = &Mark> {
+        self.by_seq.values()
+    }
+}
+
+impl FromIterator<Mark> for Marks {
+    fn from_iter<I: IntoIterator<Item = Mark>>(marks: I) -> Self {
+        let mut all = Self::default();
+        for mark in marks {
+            all.set(mark, true);
+        }
+        all
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct ReadStateFile {
+    version: u32,
+    read: Vec<Mark>,
+    cleared: Vec<Mark>,
+}
+
+/// Which notifications the user read or cleared, saved in `read_state.json`
+/// so a catch-up after a relaunch does not bring them back as new.
+#[derive(Debug)]
+pub struct ReadState {
+    read: Marks,
+    cleared: Marks,
+    config: ConfigStore,
+}
+
+impl ReadState {
+    pub fn load(config: &ConfigStore) -> Self {
+        let file = config
+            .read(ConfigFile::ReadState)
+            .and_then(|content| {
+                serde_json::from_str::<ReadStateFile>(&content)
+                    .map_err(|err| warn!("Failed to parse read_state.json: {err:#}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self {
+            read: file.read.into_iter().collect(),
+            cleared: file.cleared.into_iter().collect(),
+            config: config.clone(),
+        }
+    }
+
+    pub fn set_read<'a>(
+        &mut self,
+        items: impl IntoIterator<Item = &'a AnalyzedNotification>,
+        read: bool,
+    ) {
+        for item in items {
+            self.read.set(mark(item), read);
+        }
+        self.save();
+    }
+
+    pub fn set_cleared<'a>(
+        &mut self,
+        items: impl IntoIterator<Item = &'a AnalyzedNotification>,
+        cleared: bool,
+    ) {
+        for item in items {
+            self.cleared.set(mark(item), cleared);
+        }
+        self.save();
+    }
+
+    /// Drops notifications cleared before and restores read flags, for rows
+    /// collected again.
+    pub fn apply(&self, items: Vec<AnalyzedNotification>) -> Vec<AnalyzedNotification> {
+        items
+            .into_iter()
+            .filter(|item| !self.cleared.contains(&mark(item)))
+            .map(|mut item| {
+                item.read |= self.read.contains(&mark(&item));
+                item
+            })
+            .collect()
+    }
+
+    fn save(&self) {
+        let file = ReadStateFile {
+            version: READ_STATE_SCHEMA_VERSION,
+            read: self.read.iter().copied().collect(),
+            cleared: self.cleared.iter().copied().collect(),
+        };
+        if let Err(err) = self.config.save(ConfigFile::ReadState, &file) {
+            warn!("failed to save read state: {err:#}");
+        }
+    }
+}
+
+fn mark(item: &AnalyzedNotification) -> Mark {
+    (item.id, item.timestamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{ReadState, MAX_MARKS};
+    use crate::config_store::{ConfigFile, ConfigStore};
+    use crate::models::AnalyzedNotification;
+
+    fn temp_store(name: &str) -> ConfigStore {
+        let dir: PathBuf = std::env::temp_dir().join(format!(
+            "notify-read-state-test-{}-{name}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        ConfigStore::new(dir)
+    }
+
+    fn item(id: i64, timestamp: i64) -> AnalyzedNotification {
+        AnalyzedNotification {
+            id,
+            bundle_id: "com.example.app".to_string(),
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn marks_survive_a_reload_and_match_rowid_and_time() {
+        let store = temp_store("reload");
+        let mut state = ReadState::load(&store);
+        state.set_read([&item(1, 100), &item(2, 100)], true);
+        state.set_read([&item(2, 100)], false);
+        state.set_cleared([&item(3, 100)], true);
+
+        let reloaded = ReadState::load(&store);
+        let items = reloaded.apply(vec![item(1, 100), item(2, 100), item(3, 100), item(1, 900)]);
+        let seen: Vec<_> = items.iter().map(|n| (n.id, n.timestamp, n.read)).collect();
+        // Rowid 1 at another time is a different notification from a
+        // recreated store.
+        assert_eq!(seen, vec![(1, 100, true), (2, 100, false), (1, 900, false)]);
+    }
+
+    #[test]
+    fn files_missing_fields_or_unreadable_load_empty() {
+        let store = temp_store("partial");
+        store
+            .save(
+                ConfigFile::ReadState,
+                &serde_json::json!({ "read": [[1, 100]] }),
+            )
+            .unwrap();
+        let state = ReadState::load(&store);
+        assert!(state.apply(vec![item(1, 100)])[0].read);
+        assert_eq!(state.cleared.iter().count(), 0);
+
+        std::fs::write(store.path(ConfigFile::ReadState), "{").unwrap();
+        assert_eq!(ReadState::load(&store).read.iter().count(), 0);
+    }
+
+    #[test]
+    fn only_the_newest_marks_are_kept() {
+        let store = temp_store("bounded");
+        let mut state = ReadState::load(&store);
+        let items: Vec<_> = (0..MAX_MARKS as i64 + 5).map(|id| item(id, 0)).collect();
+        state.set_read(&items, true);
+        assert_eq!(state.read.iter().count(), MAX_MARKS);
+        assert_eq!(state.read.iter().next(), Some(&(5, 0)));
+
+        // Marking an old item again makes it the newest, so it outlives the
+        // ones after it.
+        state.set_read([&item(5, 0)], true);
+        state.set_read([&item(-1, 0)], true);
+        assert_eq!(state.read.iter().next(), Some(&(7, 0)));
+        assert!(state.read.contains(&(5, 0)) && !state.read.contains(&(6, 0)));
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Poll cycles older than this drop out of the averages.
+pub const CYCLE_WINDOW_SECS: i64 = 60 * 60;
+/// Shortest span the notification rate is spread over, so the first few
+/// cycles after launch don't extrapolate into a huge hourly rate.
+const MIN_RATE_SPAN_SECS: i64 = 10 * 60;
+/// Parameter count (billions) assumed for a local model whose name has none,
+/// such as `qwen3.5:latest`.
+const ASSUMED_MODEL_SIZE_B: f64 = 7.0;
+/// Local models at least this large are heavy whatever the volume.
+const LARGE_MODEL_SIZE_B: f64 = 14.0;
+/// Notifications per hour × model billions from which local analysis counts
+/// as a moderate and as a high load.
+const MODERATE_ANALYSIS_LOAD: f64 = 50.0;
+const HIGH_ANALYSIS_LOAD: f64 = 300.0;
+/// Share of wall time the polling thread spends working (not sleeping).
+const MODERATE_BUSY_PERCENT: f64 = 5.0;
+const HIGH_BUSY_PERCENT: f64 = 20.0;
+
+/// Time one poll cycle spent outside its sleep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CycleSample {
+    /// Unix time the cycle finished.
+    pub at: i64,
+    /// Reading the notification DB and filtering (Phase 1).
+    pub poll: Duration,
+    /// Waiting for the LLM batch (Phase 2).
+    pub analysis: Duration,
+    /// Notifications sent to the LLM.
+    pub analyzed: usize,
+}
+
+/// Averages over the cycles of the last `CYCLE_WINDOW_SECS`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycleAverage {
+    pub cycles: usize,
+    pub poll_ms: u64,
+    pub analysis_ms: u64,
+    pub analyzed: usize,
+    /// Seconds from the oldest cycle kept to now.
+    pub span_secs: i64,
+}
+
+impl CycleAverage {
+    pub fn cycle_ms(&self) -> u64 {
+        self.poll_ms + self.analysis_ms
+    }
+
+    /// Notifications analyzed per hour over the window.
+    pub fn analyzed_per_hour(&self) -> f64 {
+        if self.cycles == 0 {
+            return 0.0;
+        }
+        self.analyzed as f64 * 3600.0 / self.span_secs.max(MIN_RATE_SPAN_SECS) as f64
+    }
+}
+
+/// Rolling record of what recent poll cycles cost.
+#[derive(Debug, Default)]
+pub struct CycleMeter {
+    samples: VecDeque<CycleSample>,
+}
+
+impl CycleMeter {
+    pub fn record(&mut self, sample: CycleSample) {
+        self.samples.push_back(sample);
+        self.prune(sample.at);
+    }
+
+    pub fn average(&self, now: i64) -> CycleAverage {
+        let since = now - CYCLE_WINDOW_SECS;
+        let recent: Vec<_> = self.samples.iter().filter(|s| s.at > since).collect();
+        let Some(oldest) = recent.first() else {
+            return CycleAverage::default();
+        };
+        let cycles = recent.len();
+        let mean_ms = |total: Duration| (total.as_millis() / cycles as u128) as u64;
+        CycleAverage {
+            cycles,
+            poll_ms: mean_ms(recent.iter().map(|s| s.poll).sum()),
+            analysis_ms: mean_ms(recent.iter().map(|s| s.analysis).sum()),
+            analyzed: recent.iter().map(|s| s.analyzed).sum(),
+            span_secs: now - oldest.at,
+        }
+    }
+
+    fn prune(&mut self, now: i64) {
+        while self
+            .samples
+            .front()
+            .is_some_and(|s| s.at <= now - CYCLE_WINDOW_SECS)
+        {
+            self.samples.pop_front();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// The model runs on this Mac.
+    Local,
+    Remote,
+}
+
+impl Backend {
+    pub fn from_base_url(url: &str) -> Self {
+        let host = url
+            .split("://")
+            .nth(1)
+            .unwrap_or(url)
+            .split('/')
+            .next()
+            .unwrap_or_default();
+        let host = match host.strip_prefix('[') {
+            Some(bracketed) => bracketed.split(']').next().unwrap_or_default(),
+            None => host.split(':').next().unwrap_or_default(),
+        };
+        match host {
+            "localhost" | "127.0.0.1" | "::1" => Self::Local,
+            _ => Self::Remote,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImpactRating {
+    Low,
+    Moderate,
+    High,
+}
+
+/// What the estimate is based on.
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceInputs<'a> {
+    pub poll_interval_secs: u64,
+    pub base_url: &'a str,
+    pub model: &'a str,
+    pub analysis_enabled: bool,
+    pub cycles: CycleAverage,
+}
+
+/// Returned by `get_resource_estimate`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceEstimate {
+    pub rating: ImpactRating,
+    pub poll_interval_secs: u64,
+    pub backend: Backend,
+    pub model: String,
+    /// Billions of parameters read from the model tag, when it has one.
+    pub model_size_b: Option<f64>,
+    pub analysis_enabled: bool,
+    pub notifications_per_hour: f64,
+    /// Cycles the averages cover.
+    pub cycles: usize,
+    pub avg_poll_ms: u64,
+    pub avg_analysis_ms: u64,
+    pub avg_cycle_ms: u64,
+    /// Share of wall time the polling thread is working rather than sleeping.
+    pub busy_percent: f64,
+    /// Why the rating is what it is, one line per cause.
+    pub reasons: Vec<String>,
+}
+
+pub fn estimate(inputs: &ResourceInputs) -> ResourceEstimate {
+    let cycles = inputs.cycles;
+    let backend = Backend::from_base_url(inputs.base_url);
+    let model_size_b = model_size_b(inputs.model);
+    let per_hour = cycles.analyzed_per_hour();
+    let interval_ms = inputs.poll_interval_secs * 1000;
+    let busy_percent = if cycles.cycles == 0 {
+        0.0
+    } else {
+        cycles.cycle_ms() as f64 * 100.0 / (interval_ms + cycles.cycle_ms()) as f64
+    };
+
+    let mut rating = ImpactRating::Low;
+    let mut reasons = Vec::new();
+    let mut raise = |level: ImpactRating, reason: String| {
+        rating = rating.max(level);
+        reasons.push(reason);
+    };
+
+    if busy_percent >= MODERATE_BUSY_PERCENT {
+        let level = if busy_percent >= HIGH_BUSY_PERCENT {
+            ImpactRating::High
+        } else {
+            ImpactRating::Moderate
+        };
+        raise(
+            level,
+            format!(
+                "{}秒ごとのポーリングと解析で時間の{busy_percent:.0}%を使用",
+                inputs.poll_interval_secs
+            ),
+        );
+    }
+
+    if !inputs.analysis_enabled {
+        raise(ImpactRating::Low, "LLM解析はオフ".to_string());
+    } else if backend == Backend::Remote {
+        raise(ImpactRating::Low, "解析はリモートで実行".to_string());
+    } else {
+        let size = model_size_b.unwrap_or(ASSUMED_MODEL_SIZE_B);
+        let load = per_hour * size;
+        if size >= LARGE_MODEL_SIZE_B {
+            raise(
+                ImpactRating::Moderate,
+                format!("ローカルモデル {}（{size}B）が大きい", inputs.model),
+            );
+        }
+        if load >= MODERATE_ANALYSIS_LOAD {
+            let level = if load >= HIGH_ANALYSIS_LOAD {
+                ImpactRating::High
+            } else {
+                ImpactRating::Moderate
+            };
+            raise(
+                level,
+                format!(
+                    "ローカルモデル {} で1時間あたり{per_hour:.0}件を解析",
+                    inputs.model
+                ),
+            );
+        }
+    }
+
+    ResourceEstimate {
+        rating,
+        poll_interval_secs: inputs.poll_interval_secs,
+        backend,
+        model: inputs.model.to_string(),
+        model_size_b,
+        analysis_enabled: inputs.analysis_enabled,
+        notifications_per_hour: per_hour,
+        cycles: cycles.cycles,
+        avg_poll_ms: cycles.poll_ms,
+        avg_analysis_ms: cycles.analysis_ms,
+        avg_cycle_ms: cycles.cycle_ms(),
+        busy_percent,
+        reasons,
+    }
+}
+
+/// Reads the parameter count from a tag like `qwen2.5:7b`,
+/// `llama3.1:8b-instruct-q4_K_M` or `mixtral:8x7b`.
+fn model_size_b(model: &str) -> Option<f64> {
+    model
+        .to_lowercase()
+        .split([':', '-', '_', '/'])
+        .find_map(|part| {
+            let digits = part.strip_suffix('b')?;
+            digits
+                .split('x')
+                .map(|n| n.parse::<f64>().ok())
+                .product::<Option<f64>>()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        estimate, model_size_b, Backend, CycleAverage, CycleMeter, CycleSample, ImpactRating,
+        ResourceInputs, CYCLE_WINDOW_SECS,
+    };
+
+    fn sample(at: i64, poll_ms: u64, analysis_ms: u64, analyzed: usize) -> CycleSample {
+        CycleSample {
+            at,
+            poll: Duration::from_millis(poll_ms),
+            analysis: Duration::from_millis(analysis_ms),
+            analyzed,
+        }
+    }
+
+    #[test]
+    fn averages_cover_the_recent_window_only() {
+        let mut meter = CycleMeter::default();
+        assert_eq!(meter.average(0), CycleAverage::default());
+
+        // An expensive cycle that will age out.
+        meter.record(sample(1_000, 500, 90_000, 40));
+        let now = 1_000 + CYCLE_WINDOW_SECS + 100;
+        for i in 0..4 {
+            meter.record(sample(
+                now - 1_800 + i * 600,
+                10 + i as u64 * 2,
+                i as u64 * 1_000,
+                3,
+            ));
+        }
+        let average = meter.average(now);
+        assert_eq!(
+            average,
+            CycleAverage {
+                cycles: 4,
+                poll_ms: 13,
+                analysis_ms: 1_500,
+                analyzed: 12,
+                span_secs: 1_800,
+            }
+        );
+        assert_eq!(average.cycle_ms(), 1_513);
+        assert_eq!(average.analyzed_per_hour(), 24.0);
+
+        // Shortly after launch the rate is spread over at least ten minutes.
+        let mut fresh = CycleMeter::default();
+        fresh.record(sample(50, 5, 0, 2));
+        assert_eq!(fresh.average(60).analyzed_per_hour(), 12.0);
+    }
+
+    #[test]
+    fn rating_follows_model_size_volume_and_backend() {
+        let busy = CycleAverage {
+            cycles: 100,
+            poll_ms: 5,
+            analysis_ms: 400,
+            analyzed: 60,
+            span_secs: 3_600,
+        };
+        let inputs = |model, base_url| ResourceInputs {
+            poll_interval_secs: 5,
+            base_url,
+            model,
+            analysis_enabled: true,
+            cycles: busy,
+        };
+
+        let small = estimate(&inputs("gemma3:1b", "http://localhost:11434"));
+        assert_eq!(small.backend, Backend::Local);
+        assert_eq!(small.rating, ImpactRating::Moderate);
+        assert_eq!(small.avg_cycle_ms, 405);
+        let large = estimate(&inputs("qwen2.5:14b-instruct", "http://127.0.0.1:11434"));
+        assert_eq!(large.rating, ImpactRating::High);
+        let remote = estimate(&inputs("qwen2.5:14b-instruct", "https://llm.example.com"));
+        assert_eq!(remote.backend, Backend::Remote);
+        assert_eq!(remote.rating, ImpactRating::Moderate);
+
+        let idle = ResourceInputs {
+            cycles: CycleAverage::default(),
+            ..inputs("qwen3.5:latest", "http://localhost:11434")
+        };
+        assert_eq!(estimate(&idle).rating, ImpactRating::Low);
+
+        assert_eq!(model_size_b("mixtral:8x7b"), Some(56.0));
+        assert_eq!(model_size_b("llama3.2:3.2B-q4"), Some(3.2));
+        assert_eq!(model_size_b("qwen3.5:latest"), None);
+    }
+}
@@ -0,0 +1,281 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::language::{language_instruction, OutputLanguage};
+use crate::models::{AnalyzedNotification, SessionSummary, SummaryKind};
+use crate::summary::SummarySource;
+
+/// `set_daily_rollup_time` takes times like `18:00`.
+const ROLLUP_TIME_FORMAT: &str = "%H:%M";
+
+pub fn parse_rollup_time(value: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(value.trim(), ROLLUP_TIME_FORMAT)
+        .map_err(|_| anyhow!("rollup time must be HH:MM, got `{value}`"))
+}
+
+/// Whether the rollup for `now`'s day should run. Any tick after `at` fires
+/// it, so a Mac that slept through the scheduled time catches up on wake the
+/// same day; a day that was slept through entirely is not rolled up later.
+pub fn rollup_due(now: NaiveDateTime, at: NaiveTime, last_rollup: Option<NaiveDate>) -> bool {
+    now.time() >= at && last_rollup.is_none_or(|day| day < now.date())
+}
+
+/// Material for one day's rollup: the session summaries written that day and
+/// the collected notifications of that day.
+#[derive(Debug, Clone)]
+pub struct DailyRollupSource {
+    pub sessions: Vec<SessionSummary>,
+    /// Notifications of the day not collected in one of `sessions`.
+    pub loose: Vec<AnalyzedNotification>,
+    /// Every notification of the day, so no urgent one is left out.
+    pub items: SummarySource,
+    pub notification_count: usize,
+}
+
+impl DailyRollupSource {
+    pub fn new(
+        day: NaiveDate,
+        history: &[SessionSummary],
+        collected: &[AnalyzedNotification],
+    ) -> Self {
+        let sessions: Vec<SessionSummary> = history
+            .iter()
+            .filter(|s| s.kind == SummaryKind::Session && local_day(s.created_at) == day)
+            .cloned()
+            .collect();
+        let today: Vec<AnalyzedNotification> = collected
+            .iter()
+            .filter(|n| local_day(n.timestamp) == day)
+            .cloned()
+            .collect();
+        let loose = today
+            .iter()
+            .filter(|n| {
+                n.session_id
+                    .is_none_or(|id| !sessions.iter().any(|s| s.session_id == Some(id)))
+            })
+            .cloned()
+            .collect();
+        Self {
+            items: SummarySource::from_items(&today),
+            notification_count: today.len(),
+            sessions,
+            loose,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty() && self.notification_count == 0
+    }
+}
+
+/// The reduce step over the day's sessions: each session was already
+/// summarized when it ended, so their summaries are combined with the loose
+/// notifications in one request. Answers use the session summary sections, so
+/// `enforce_summary_tiers` applies to the result.
+pub fn build_daily_rollup_prompt(source: &DailyRollupSource, language: OutputLanguage) -> String {
+    let sessions = if source.sessions.is_empty() {
+        "なし".to_string()
+    } else {
+        source
+            .sessions
+            .iter()
+            .zip(1..)
+            .map(|(s, n)| {
+                let span = match s.started_at {
+                    Some(start) => format!("{}〜{}", clock(start), clock(s.created_at)),
+                    None => format!("〜{}", clock(s.created_at)),
+                };
+                format!(
+                    "セッション{n} ({span}、{}件):\n{}",
+                    s.notification_count, s.text
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    };
+    let loose = if source.loose.is_empty() {
+        "なし".to_string()
+    } else {
+        source
+            .loose
+            .iter()
+            .map(|n| {
+                format!(
+                    "- [#{}][{}][{}] {}",
+                    n.id,
+                    n.app_name,
+                    n.urgency.label(),
+                    n.summary_line
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    format!(
+        "今日1日分の集中セッションの要約と、セッション外に届いた通知です。\
+全体を1つにまとめ、次の形式の{}で書いてください。\n\
+前置きや追加説明は不要です。\n\
+{}\n\n\
+【今すぐ】\n\
+まだ対応が必要な緊急・高の通知を1行ずつ。[#番号] があれば残す。\n\
+【中】\n\
+1日の流れと中優先の通知を2〜3行で要約。\n\
+【低】\n\
+件数のみ1行。\n\n\
+{sessions}\n\nセッション外の通知:\n{loose}",
+        language.prompt_name(),
+        language_instruction(language),
+    )
+}
+
+/// Used when the model is paused or unavailable.
+pub fn fallback_rollup_text(source: &DailyRollupSource) -> String {
+    format!(
+        "今日の通知 {}件、集中セッション {}回",
+        source.notification_count,
+        source.sessions.len()
+    )
+}
+
+/// The local calendar day of a Unix timestamp.
+pub(crate) fn local_day(timestamp: i64) -> NaiveDate {
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .with_timezone(&Local)
+        .date_naive()
+}
+
+fn clock(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .with_timezone(&Local)
+        .format("%H:%M")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, NaiveDate, NaiveTime, TimeZone};
+
+    use super::{build_daily_rollup_prompt, parse_rollup_time, rollup_due, DailyRollupSource};
+    use crate::language::OutputLanguage;
+    use crate::models::{AnalyzedNotification, SessionSummary, SummaryKind, UrgencyLevel};
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, d).unwrap()
+    }
+
+    fn at(d: u32, h: u32, m: u32) -> i64 {
+        Local
+            .with_ymd_and_hms(2025, 3, d, h, m, 0)
+            .earliest()
+            .unwrap()
+            .timestamp()
+    }
+
+    fn item(
+        id: i64,
+        timestamp: i64,
+        urgency: UrgencyLevel,
+        session_id: Option<u64>,
+    ) -> AnalyzedNotification {
+        AnalyzedNotification {
+            id,
+            bundle_id: "com.example.app".to_string(),
+            app_name: "Slack".to_string(),
+            urgency,
+            summary_line: format!("通知{id}"),
+            timestamp,
+            session_id,
+            ..Default::default()
+        }
+    }
+
+    fn session(id: u64, started_at: i64, created_at: i64, text: &str) -> SessionSummary {
+        SessionSummary {
+            text: text.to_string(),
+            created_at,
+            notification_count: 1,
+            protected_secs: None,
+            slept_secs: 0,
+            kind: SummaryKind::Session,
+            started_at: Some(started_at),
+            session_id: Some(id),
+        }
+    }
+
+    #[test]
+    fn rollup_fires_once_a_day_after_the_scheduled_time() {
+        let six = parse_rollup_time("18:00").unwrap();
+        let time = |h: u32, m: u32| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+
+        assert!(!rollup_due(day(12).and_time(time(17, 59)), six, None));
+        assert!(rollup_due(day(12).and_time(time(18, 0)), six, None));
+        assert!(rollup_due(
+            day(12).and_time(time(18, 0)),
+            six,
+            Some(day(11))
+        ));
+        assert!(!rollup_due(
+            day(12).and_time(time(18, 5)),
+            six,
+            Some(day(12))
+        ));
+        // Asleep at 18:00, woken at 21:30 the same day.
+        assert!(rollup_due(
+            day(12).and_time(time(21, 30)),
+            six,
+            Some(day(11))
+        ));
+        // Asleep until the next morning: nothing until that day's 18:00.
+        assert!(!rollup_due(
+            day(13).and_time(time(8, 0)),
+            six,
+            Some(day(11))
+        ));
+        // The clock went back past a day already rolled up.
+        assert!(!rollup_due(
+            day(11).and_time(time(19, 0)),
+            six,
+            Some(day(12))
+        ));
+
+        assert!(parse_rollup_time(" 07:30 ").is_ok());
+        assert!(parse_rollup_time("25:00").is_err());
+        assert!(parse_rollup_time("6pm").is_err());
+    }
+
+    #[test]
+    fn source_separates_loose_notifications_from_sessions() {
+        let history = vec![
+            session(1, at(11, 9, 0), at(11, 11, 0), "昨日"),
+            session(2, at(12, 9, 0), at(12, 11, 0), "午前の集中"),
+            session(3, at(12, 14, 0), at(12, 16, 0), "午後の集中"),
+        ];
+        let collected = vec![
+            item(1, at(11, 10, 0), UrgencyLevel::Critical, Some(1)),
+            item(2, at(12, 10, 0), UrgencyLevel::High, Some(2)),
+            item(3, at(12, 12, 30), UrgencyLevel::Medium, None),
+            item(4, at(12, 15, 0), UrgencyLevel::Low, Some(3)),
+            item(5, at(12, 17, 0), UrgencyLevel::Critical, None),
+        ];
+
+        let source = DailyRollupSource::new(day(12), &history, &collected);
+
+        let texts: Vec<_> = source.sessions.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, vec!["午前の集中", "午後の集中"]);
+        let loose: Vec<_> = source.loose.iter().map(|n| n.id).collect();
+        assert_eq!(loose, vec![3, 5]);
+        assert_eq!(source.notification_count, 4);
+        let urgent: Vec<_> = source.items.urgent.iter().map(|i| i.id).collect();
+        assert_eq!(urgent, vec![5, 2]);
+
+        let prompt = build_daily_rollup_prompt(&source, OutputLanguage::Japanese);
+        assert!(prompt.contains("セッション1 (09:00〜11:00、1件):\n午前の集中"));
+        assert!(prompt.contains("- [#5][Slack][URGENT] 通知5"));
+        assert!(!prompt.contains("昨日"));
+
+        assert!(DailyRollupSource::new(day(13), &history, &collected).is_empty());
+    }
+}
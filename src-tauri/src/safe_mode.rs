@@ -0,0 +1,163 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use chrono::Local;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::config_store::{ConfigFile, ConfigStore};
+
+/// Launches in a row that crashed or hung before safe mode is used.
+pub const SAFE_MODE_AFTER_CRASHES: u32 = 3;
+/// How long a launch has to stay up before it counts as a clean run.
+pub const CLEAN_RUN_SECS: u64 = 60;
+
+/// Launches that started but never reached a clean run or a quit from the
+/// tray. Anything else (a crash, a freeze the user force-quit) leaves the
+/// count raised.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct LaunchState {
+    unfinished_launches: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LaunchMode {
+    Normal,
+    /// Config files are not loaded and analysis stays off.
+    Safe {
+        crashes: u32,
+    },
+}
+
+/// Counts launches in `launch_state.json`, which lives in the real config
+/// directory even while safe mode runs on a detached store.
+#[derive(Debug, Clone)]
+pub struct LaunchCounter {
+    config: ConfigStore,
+}
+
+impl LaunchCounter {
+    pub fn new(config: ConfigStore) -> Self {
+        Self { config }
+    }
+
+    /// Records this launch as unfinished and picks its mode from the ones
+    /// before it.
+    pub fn begin(&self) -> LaunchMode {
+        let crashes = self.load().unfinished_launches;
+        self.save(crashes.saturating_add(1));
+        if crashes >= SAFE_MODE_AFTER_CRASHES {
+            LaunchMode::Safe { crashes }
+        } else {
+            LaunchMode::Normal
+        }
+    }
+
+    /// The launch ran cleanly; the next one starts normally.
+    pub fn mark_clean(&self) {
+        self.save(0);
+    }
+
+    fn load(&self) -> LaunchState {
+        // An unreadable counter counts as no crashes: safe mode is a way out,
+        // not something a broken file should force.
+        self.config
+            .read(ConfigFile::LaunchState)
+            .and_then(|content| {
+                serde_json::from_str(&content)
+                    .map_err(|err| warn!("Failed to parse launch_state.json: {err:#}"))
+                    .ok()
+            })
+            .unwrap_or_default()
+    }
+
+    fn save(&self, unfinished_launches: u32) {
+        let state = LaunchState {
+            unfinished_launches,
+        };
+        if let Err(err) = self.config.save(ConfigFile::LaunchState, &state) {
+            warn!("failed to save launch state: {err:#}");
+        }
+    }
+}
+
+/// Moves every file in the config directory, except the launch counter, into
+/// a dated `reset-*` folder so the next launch starts from defaults. Returns
+/// the folder, which keeps the old files for the user to inspect or restore.
+pub fn reset_config(config: &ConfigStore) -> Result<PathBuf> {
+    let keep = ConfigFile::LaunchState.file_name();
+    let backup = config
+        .dir()
+        .join(format!("reset-{}", Local::now().format("%Y%m%d-%H%M%S")));
+    fs::create_dir_all(&backup)?;
+    for entry in fs::read_dir(config.dir())? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() || entry.file_name() == keep {
+            continue;
+        }
+        fs::rename(entry.path(), backup.join(entry.file_name()))?;
+    }
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::{reset_config, LaunchCounter, LaunchMode, SAFE_MODE_AFTER_CRASHES};
+    use crate::config_store::{ConfigFile, ConfigStore};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "notify-safe-mode-test-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn repeated_unfinished_launches_start_in_safe_mode() {
+        let counter = LaunchCounter::new(ConfigStore::new(temp_dir("counter")));
+
+        for _ in 0..SAFE_MODE_AFTER_CRASHES {
+            assert_eq!(counter.begin(), LaunchMode::Normal);
+        }
+        assert_eq!(
+            counter.begin(),
+            LaunchMode::Safe {
+                crashes: SAFE_MODE_AFTER_CRASHES
+            }
+        );
+
+        counter.mark_clean();
+        assert_eq!(counter.begin(), LaunchMode::Normal);
+    }
+
+    #[test]
+    fn a_corrupt_counter_starts_normally() {
+        let config = ConfigStore::new(temp_dir("corrupt"));
+        fs::create_dir_all(config.dir()).unwrap();
+        fs::write(config.path(ConfigFile::LaunchState), "{").unwrap();
+
+        assert_eq!(LaunchCounter::new(config).begin(), LaunchMode::Normal);
+    }
+
+    #[test]
+    fn reset_moves_config_files_aside_but_keeps_the_counter() {
+        let config = ConfigStore::new(temp_dir("reset"));
+        config
+            .save(ConfigFile::Settings, &serde_json::json!({ "broken": true }))
+            .unwrap();
+        LaunchCounter::new(config.clone()).begin();
+
+        let backup = reset_config(&config).unwrap();
+
+        assert_eq!(config.read(ConfigFile::Settings), None);
+        assert!(config.read(ConfigFile::LaunchState).is_some());
+        assert!(backup.join("settings.json").is_file());
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::away::AwaySettings;
+use crate::config_store::{ConfigFile, ConfigStore};
+use crate::dialog::MessageTemplates;
+use crate::http::NetworkSettings;
+use crate::models::{SummaryField, UrgencyLevel};
+use crate::noise::NoiseThresholds;
+use crate::onboarding::OnboardingProgress;
+use crate::orchestrator::RevisionCheckSettings;
+use crate::sources::SourceConfig;
+use crate::storage::StorageSettings;
+use crate::webhook::WebhookConfig;
+
+/// App-wide behavior switches persisted in `settings.json`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AppSettings {
+    /// Write `state_snapshot.json` for external dashboards.
+    pub state_snapshot: bool,
+    /// Bundle ids pinned to the top of the grouped view, in this order.
+    pub app_order: Vec<String>,
+    /// Keep collected notifications in timestamp order as they arrive, so
+    /// building the grouped view does not sort them on every read.
+    pub incremental_sort: bool,
+    /// Bucket an app's notifications by their category identifier in the
+    /// notification tree and session summaries.
+    pub group_by_category: bool,
+    /// Split an app's group by the notification database (macOS account) the
+    /// notifications came from. See `SourceConfig::Database`.
+    pub group_by_account: bool,
+    /// Case-insensitive words (e.g. a VIP's name) that let a notification
+    /// through ignore and mute rules and make it critical. See `RULE_PRECEDENCE`.
+    pub escalation_keywords: Vec<String>,
+    /// Longest app prompt context `set_app_prompt` accepts, in characters.
+    /// Unset uses `DEFAULT_APP_CONTEXT_MAX_CHARS`.
+    pub app_context_max_chars: Option<usize>,
+    /// Keep notification text and user-written prompts in diagnostic bundles
+    /// instead of redacting them.
+    pub diagnostics_include_content: bool,
+    /// When `get_noise_report` suggests silencing an app in System Settings.
+    pub noise_thresholds: NoiseThresholds,
+    /// Open the panel when a notification at or above `auto_show_min_urgency`
+    /// is collected.
+    pub auto_show_on_critical: bool,
+    /// Unset means Critical only.
+    pub auto_show_min_urgency: Option<UrgencyLevel>,
+    /// Criticals the model was less confident about than this (0.0–1.0) get
+    /// a banner instead of a dialog. Unset never downgrades.
+    pub critical_min_confidence: Option<f64>,
+    /// How long cleared notifications stay restorable with `undo_clear`.
+    /// Unset uses `DEFAULT_UNDO_CLEAR_WINDOW_SECS`; 0 drops them right away.
+    pub undo_clear_window_secs: Option<i64>,
+    /// The same for a notification cleared on its own, which
+    /// `restore_notification` brings back. Unset uses
+    /// `DEFAULT_RESTORE_WINDOW_SECS`.
+    pub restore_window_secs: Option<i64>,
+    /// On the focus-start edge, also analyze notifications delivered this many
+    /// seconds before focus began. Unset or 0 turns the look-back off.
+    pub focus_start_lookback_secs: Option<i64>,
+    /// Skip raising time-sensitive and critical notifications to their
+    /// urgency floor (see `InterruptionLevel::urgency_floor`).
+    pub ignore_interruption_level: bool,
+    /// Field priority for summary lines built without the LLM. Unset uses
+    /// `DEFAULT_SUMMARY_FIELDS`; per-app values in `app_prompts.json` win.
+    pub summary_fields: Option<Vec<SummaryField>>,
+    /// Show title and subtitle as one `title — subtitle` line when neither
+    /// is longer than this many characters, in the headline and in summary
+    /// lines built without the LLM. Unset keeps them apart.
+    pub title_merge_max_chars: Option<usize>,
+    /// Let critical alerts go through the normal rules and analysis instead
+    /// of collecting them as critical at once. Only set by editing
+    /// `settings.json`.
+    pub disable_critical_alert_passthrough: bool,
+    /// Enables maintainer-only diagnostic commands such as `get_blob_stats`.
+    /// Only set by editing `settings.json`.
+    pub debug_commands: bool,
+    /// Age after which a focus assertion is reported as possibly left over
+    /// from a crashed app. Unset uses `DEFAULT_STALE_ASSERTION_SECS`.
+    pub stale_assertion_secs: Option<i64>,
+    /// Treat focus held up only by stale assertions as inactive.
+    pub ignore_stale_assertions: bool,
+    /// During focus, hold back analysis of probably-not-critical notifications
+    /// until the user has been idle this many seconds or focus ends, to keep
+    /// the model from running while the machine is busy. Unset analyzes at once.
+    pub defer_analysis_idle_secs: Option<u64>,
+    /// Most notifications analyzed in one poll; the rest wait for later
+    /// polls. Unset uses `DEFAULT_MAX_ANALYSIS_PER_POLL`.
+    pub max_analysis_per_poll: Option<usize>,
+    /// Reads of a notification whose data fails to parse before it is
+    /// skipped for good. Unset uses `DEFAULT_MAX_PARSE_ATTEMPTS`.
+    pub max_parse_attempts: Option<u32>,
+    /// Local time (`HH:MM`) of the end-of-day rollup over the day's sessions
+    /// and loose notifications. Unset turns the rollup off.
+    pub daily_rollup_time: Option<String>,
+    /// How many recently collected rows are re-read for in-place edits, and
+    /// how often.
+    pub revision_check: RevisionCheckSettings,
+    /// Size and age limits for the growing files in the config dir.
+    pub storage: StorageSettings,
+    /// Proxy and extra root certificate for outgoing HTTPS.
+    pub network: NetworkSettings,
+    /// Text of the critical dialog and the focus-end banner.
+    pub message_templates: MessageTemplates,
+    /// Catch-up on return from a long idle or screen lock.
+    pub away: AwaySettings,
+    /// Sources read alongside the Apple store (always read). Only set by
+    /// editing `settings.json`; read at launch.
+    pub notification_sources: Vec<SourceConfig>,
+    /// URLs collected notifications are posted to, through the outbox in
+    /// `webhook_outbox.json`. Only set by editing `settings.json`.
+    pub webhooks: Vec<WebhookConfig>,
+    /// First-launch setup steps done so far. Missing from files written
+    /// before onboarding existed, which count as finished.
+    pub onboarding: OnboardingProgress,
+}
+
+#[derive(Debug)]
+pub struct AppSettingsStore {
+    settings: AppSettings,
+    store: ConfigStore,
+}
+
+impl AppSettingsStore {
+    pub fn load(store: &ConfigStore) -> Self {
+        let settings = match store.read(ConfigFile::Settings) {
+            Some(content) => match serde_json::from_str::<AppSettings>(&content) {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    warn!("Failed to parse settings.json: {err:#}");
+                    AppSettings::default()
+                }
+            },
+            None if store.is_first_launch() => AppSettings {
+                onboarding: OnboardingProgress::fresh(),
+                ..AppSettings::default()
+            },
+            None => AppSettings::default(),
+        };
+        Self {
+            settings,
+            store: store.clone(),
+        }
+    }
+
+    pub fn get(&self) -> &AppSettings {
+        &self.settings
+    }
+
+    pub fn update(&mut self, f: impl FnOnce(&mut AppSettings)) -> Result<()> {
+        f(&mut self.settings);
+        self.store.save(ConfigFile::Settings, &self.settings)
+    }
+}
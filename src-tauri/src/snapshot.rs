@@ -0,0 +1,230 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::Result;
+use log::warn;
+use serde::Serialize;
+
+use crate::config_store::write_atomic;
+use crate::models::{AnalyzedNotification, FocusStatus};
+use crate::orchestrator::NotifyOrchestrator;
+
+pub const STATE_SNAPSHOT_SCHEMA_VERSION: u32 = 1;
+const MAX_SNAPSHOT_TOP_ITEMS: usize = 5;
+const MAX_SNAPSHOT_SUMMARY_CHARS: usize = 80;
+
+/// Compact state written to `state_snapshot.json` for external dashboards.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StateSnapshot {
+    pub schema_version: u32,
+    pub updated_at: i64,
+    pub focus_active: bool,
+    pub focus_ends_at: Option<i64>,
+    pub counts: SnapshotCounts,
+    pub top: Vec<SnapshotItem>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SnapshotCounts {
+    pub critical: usize,
+    pub high: usize,
+    pub medium: usize,
+    pub low: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotItem {
+    pub app_name: String,
+    pub urgency: &'static str,
+    pub summary_line: String,
+    pub timestamp: i64,
+}
+
+impl StateSnapshot {
+    /// Builds a snapshot from collected notifications; the most urgent, then newest, come first.
+    pub fn build(collected: &[AnalyzedNotification], focus: FocusStatus, now: i64) -> Self {
+        let mut counts = [0usize; 4];
+        for n in collected {
+            counts[n.urgency.rank()] += 1;
+        }
+
+        let mut ordered: Vec<&AnalyzedNotification> = collected.iter().collect();
+        ordered.sort_by(|a, b| {
+            a.urgency
+                .rank()
+                .cmp(&b.urgency.rank())
+                .then(b.timestamp.cmp(&a.timestamp))
+        });
+
+        let top = ordered
+            .into_iter()
+            .take(MAX_SNAPSHOT_TOP_ITEMS)
+            .map(|n| SnapshotItem {
+                app_name: n.app_name.clone(),
+                urgency: n.urgency.label(),
+                summary_line: n
+                    .summary_line
+                    .chars()
+                    .take(MAX_SNAPSHOT_SUMMARY_CHARS)
+                    .collect(),
+                timestamp: n.timestamp,
+            })
+            .collect();
+
+        Self {
+            schema_version: STATE_SNAPSHOT_SCHEMA_VERSION,
+            updated_at: now,
+            focus_active: focus.active,
+            focus_ends_at: focus.focus_ends_at,
+            counts: SnapshotCounts {
+                critical: counts[0],
+                high: counts[1],
+                medium: counts[2],
+                low: counts[3],
+                total: collected.len(),
+            },
+            top,
+        }
+    }
+}
+
+/// Handle for waking the snapshot writer thread.
+pub struct SnapshotWriter(Sender<()>);
+
+impl SnapshotWriter {
+    /// Requests a snapshot write. Never blocks; bursts are coalesced by the writer.
+    pub fn request(&self) {
+        let _ = self.0.send(());
+    }
+}
+
+/// `~/.config/mac-notify/state_snapshot.json`. Kept apart from the app's own
+/// config directory so dashboards read a stable, product-named path.
+pub fn state_snapshot_path() -> PathBuf {
+    env::var("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".config/mac-notify/state_snapshot.json")
+}
+
+/// Spawns the writer thread. The orchestrator is only locked once the caller
+/// that requested the write has released it.
+pub fn start_snapshot_writer(orchestrator: Arc<Mutex<NotifyOrchestrator>>) -> SnapshotWriter {
+    let (tx, rx) = mpsc::channel::<()>();
+    let path = state_snapshot_path();
+
+    thread::spawn(move || {
+        while rx.recv().is_ok() {
+            // Coalesce requests that piled up while we were writing.
+            while rx.try_recv().is_ok() {}
+
+            let snapshot = match orchestrator.lock() {
+                Ok(guard) => guard.state_snapshot(),
+                Err(err) => {
+                    warn!("Orchestrator lock poisoned: {err}");
+                    continue;
+                }
+            };
+            if let Some(snapshot) = snapshot {
+                if let Err(err) = write_snapshot_atomic(&path, &snapshot) {
+                    warn!("failed to write state snapshot: {err:#}");
+                }
+            }
+        }
+    });
+
+    SnapshotWriter(tx)
+}
+
+fn write_snapshot_atomic(path: &Path, snapshot: &StateSnapshot) -> Result<()> {
+    write_atomic(path, &serde_json::to_vec(snapshot)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StateSnapshot, MAX_SNAPSHOT_SUMMARY_CHARS, STATE_SNAPSHOT_SCHEMA_VERSION};
+    use crate::models::{AnalyzedNotification, FocusStatus, UrgencyLevel};
+
+    const IDLE: FocusStatus = FocusStatus {
+        active: false,
+        focus_ends_at: None,
+    };
+
+    fn sample(id: i64, urgency: UrgencyLevel, timestamp: i64) -> AnalyzedNotification {
+        AnalyzedNotification {
+            id,
+            bundle_id: "com.example.app".to_string(),
+            app_name: "App".to_string(),
+            urgency,
+            summary_line: format!("summary {id}"),
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn counts_and_versions_snapshot() {
+        let collected = vec![
+            sample(1, UrgencyLevel::Low, 10),
+            sample(2, UrgencyLevel::Critical, 20),
+            sample(3, UrgencyLevel::Low, 30),
+        ];
+
+        let focus = FocusStatus {
+            active: true,
+            focus_ends_at: Some(500),
+        };
+        let snapshot = StateSnapshot::build(&collected, focus, 100);
+
+        assert_eq!(snapshot.schema_version, STATE_SNAPSHOT_SCHEMA_VERSION);
+        assert_eq!(snapshot.updated_at, 100);
+        assert!(snapshot.focus_active);
+        assert_eq!(snapshot.focus_ends_at, Some(500));
+        assert_eq!(snapshot.counts.critical, 1);
+        assert_eq!(snapshot.counts.low, 2);
+        assert_eq!(snapshot.counts.total, 3);
+    }
+
+    #[test]
+    fn top_items_are_capped_and_ordered_by_urgency_then_recency() {
+        let mut collected: Vec<_> = (0..8).map(|i| sample(i, UrgencyLevel::Low, i)).collect();
+        collected.push(sample(100, UrgencyLevel::High, 0));
+
+        let snapshot = StateSnapshot::build(&collected, IDLE, 0);
+
+        let ids: Vec<_> = snapshot
+            .top
+            .iter()
+            .map(|n| n.summary_line.clone())
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                "summary 100",
+                "summary 7",
+                "summary 6",
+                "summary 5",
+                "summary 4"
+            ]
+        );
+    }
+
+    #[test]
+    fn long_summary_lines_are_truncated() {
+        let mut item = sample(1, UrgencyLevel::Medium, 0);
+        item.summary_line = "あ".repeat(MAX_SNAPSHOT_SUMMARY_CHARS + 20);
+
+        let snapshot = StateSnapshot::build(&[item], IDLE, 0);
+
+        assert_eq!(
+            snapshot.top[0].summary_line.chars().count(),
+            MAX_SNAPSHOT_SUMMARY_CHARS
+        );
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use chrono::Utc;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::config_store::ConfigStore;
+use crate::db::NotificationDb;
+use crate::models::{InterruptionLevel, Notification};
+
+/// Ids of notifications from sources other than the Apple store start here,
+/// one block of this size per configured source, far above any store rowid
+/// and still exact as a JavaScript number.
+pub const SOURCE_ID_BASE: i64 = 1 << 40;
+
+/// Id blocks that end at or below 2^53, the largest exact JavaScript integer.
+const SOURCE_ID_BLOCKS: u64 = (1 << 13) - 1;
+
+/// Where notifications come from. Each poll returns the notifications that
+/// arrived since the previous one; they then go through the same ignore
+/// lists, rules and analysis whatever their source.
+pub trait NotificationSource: Send {
+    fn name(&self) -> &str;
+    fn poll(&mut self) -> Result<Vec<Notification>>;
+
+    /// Whether to poll outside focus too, discarding what it returns, so a
+    /// cursor-based source starts focus from where focus began rather than
+    /// from everything delivered since the last session.
+    fn read_outside_focus(&self) -> bool {
+        false
+    }
+
+    /// Notifications delivered at or after `since` (Unix seconds), whether or
+    /// not `poll` returned them, for the focus-start look-back. Sources that
+    /// cannot be re-read return none.
+    fn read_recent(&mut self, _since: i64, _max_rows: i64) -> Result<Vec<Notification>> {
+        Ok(Vec::new())
+    }
+
+    /// The given notifications as they are now, for the revision check. Ids
+    /// from other sources are left out.
+    fn read_rows(&mut self, _ids: &[i64]) -> Result<Vec<Notification>> {
+        Ok(Vec::new())
+    }
+}
+
+/// A source besides the Apple store, listed in `settings.json` under
+/// `notification_sources`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SourceConfig {
+    /// JSONL files dropped into `dir`, `inbox/` in the config dir if unset.
+    Inbox {
+        #[serde(default)]
+        dir: Option<PathBuf>,
+    },
+    /// Another notification store, e.g. a second macOS user's
+    /// `.../com.apple.notificationcenter/db2/db` readable by this one. Its
+    /// notifications carry `label`, or the path if unset, as their account.
+    /// Also added and removed from Settings.
+    Database {
+        path: PathBuf,
+        #[serde(default)]
+        label: Option<String>,
+    },
+}
+
+impl SourceConfig {
+    /// The folder or file the source reads, which names it for its ids.
+    fn location(&self, config: &ConfigStore) -> PathBuf {
+        match self {
+            SourceConfig::Inbox { dir } => {
+                dir.clone().unwrap_or_else(|| config.dir().join("inbox"))
+            }
+            SourceConfig::Database { path, .. } => path.clone(),
+        }
+    }
+}
+
+/// The first id of the block a source reading `location` owns. It comes
+/// from a hash of the location, not the source's place in the list, so
+/// adding, removing or reordering sources leaves the others' ids alone.
+pub fn source_id_base(location: &Path) -> i64 {
+    // FNV-1a, which unlike `DefaultHasher` is the same in every build.
+    let hash = location
+        .as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    SOURCE_ID_BASE * (1 + (hash % SOURCE_ID_BLOCKS) as i64)
+}
+
+/// Builds the configured sources. Two locations hashing to the same block
+/// are rare; the later one then takes the next free block.
+pub fn build_sources(
+    configs: &[SourceConfig],
+    config: &ConfigStore,
+) -> Vec<Box<dyn NotificationSource>> {
+    let mut taken = HashSet::new();
+    configs
+        .iter()
+        .map(|source| {
+            let location = source.location(config);
+            let mut id_base = source_id_base(&location);
+            while !taken.insert(id_base) {
+                let block = id_base / SOURCE_ID_BASE;
+                id_base = SOURCE_ID_BASE * (block % SOURCE_ID_BLOCKS as i64 + 1);
+            }
+            match source {
+                SourceConfig::Inbox { .. } => {
+                    Box::new(InboxSource::new(location, id_base)) as Box<dyn NotificationSource>
+                }
+                SourceConfig::Database { label, .. } => {
+                    let label = label
+                        .clone()
+                        .unwrap_or_else(|| location.display().to_string());
+                    Box::new(DatabaseSource::new(location, label, id_base))
+                }
+            }
+        })
+        .collect()
+}
+
+/// One line of an inbox file.
+#[derive(Debug, Deserialize)]
+struct InboxItem {
+    bundle_id: String,
+    title: String,
+    #[serde(default)]
+    subtitle: String,
+    #[serde(default)]
+    body: String,
+    /// Unix seconds; unset means the time it was read.
+    timestamp: Option<i64>,
+}
+
+/// Reads `*.jsonl` files from a folder, one notification per line, and
+/// deletes each file once read. Writers should create the file under another
+/// name and rename it into place so a half-written file is never picked up.
+pub struct InboxSource {
+    dir: PathBuf,
+    next_id: i64,
+}
+
+impl InboxSource {
+    pub fn new(dir: PathBuf, id_base: i64) -> Self {
+        Self {
+            dir,
+            next_id: id_base,
+        }
+    }
+
+    fn notification(&mut self, item: InboxItem) -> Notification {
+        self.next_id += 1;
+        Notification {
+            rowid: self.next_id,
+            title: item.title,
+            body: item.body,
+            subtitle: item.subtitle,
+            bundle_id: item.bundle_id,
+            timestamp: item.timestamp.unwrap_or_else(|| Utc::now().timestamp()),
+            thread_id: None,
+            request_id: None,
+            badge: None,
+            interruption_level: InterruptionLevel::Active,
+            system_critical: false,
+            category: None,
+            account: None,
+            backfill: false,
+        }
+    }
+}
+
+impl NotificationSource for InboxSource {
+    fn name(&self) -> &str {
+        "inbox"
+    }
+
+    fn poll(&mut self) -> Result<Vec<Notification>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "jsonl"))
+            .collect();
+        files.sort();
+
+        let mut notifications = Vec::new();
+        for path in files {
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(err) => {
+                    warn!("failed to read inbox file {}: {err}", path.display());
+                    continue;
+                }
+            };
+            // A file that cannot be deleted would be ingested on every poll.
+            if let Err(err) = fs::remove_file(&path) {
+                warn!("failed to remove inbox file {}: {err}", path.display());
+                continue;
+            }
+            let mut skipped = 0;
+            for line in content.lines().filter(|line| !line.trim().is_empty()) {
+                match serde_json::from_str::<InboxItem>(line) {
+                    Ok(item) => notifications.push(self.notification(item)),
+                    Err(_) => skipped += 1,
+                }
+            }
+            if skipped > 0 {
+                warn!(
+                    "Skipped {skipped} malformed line(s) in inbox file {}",
+                    path.display()
+                );
+            }
+        }
+        Ok(notifications)
+    }
+}
+
+/// A notification store other than this user's, read like the primary one.
+/// Rowids are offset by `id_base` so they never collide with the primary's.
+pub struct DatabaseSource {
+    db: NotificationDb,
+    label: String,
+    id_base: i64,
+}
+
+impl DatabaseSource {
+    pub fn new(path: PathBuf, label: String, id_base: i64) -> Self {
+        Self {
+            // No schema cache: that file belongs to the primary store.
+            db: NotificationDb::new(path),
+            label,
+            id_base,
+        }
+    }
+
+    /// Moves rows into this source's id block and marks them with its account.
+    fn tag(&self, mut notifications: Vec<Notification>) -> Vec<Notification> {
+        for notification in &mut notifications {
+            notification.rowid += self.id_base;
+            notification.account = Some(self.label.clone());
+        }
+        notifications
+    }
+}
+
+impl NotificationSource for DatabaseSource {
+    fn name(&self) -> &str {
+        &self.label
+    }
+
+    fn poll(&mut self) -> Result<Vec<Notification>> {
+        let notifications = self.db.poll()?;
+        Ok(self.tag(notifications))
+    }
+
+    fn read_outside_focus(&self) -> bool {
+        true
+    }
+
+    fn read_recent(&mut self, since: i64, max_rows: i64) -> Result<Vec<Notification>> {
+        let notifications = self.db.read_recent(since, max_rows)?;
+        Ok(self.tag(notifications))
+    }
+
+    fn read_rows(&mut self, ids: &[i64]) -> Result<Vec<Notification>> {
+        let owned = self.id_base..self.id_base + SOURCE_ID_BASE;
+        let rowids: Vec<i64> = ids
+            .iter()
+            .filter(|id| owned.contains(*id))
+            .map(|id| id - self.id_base)
+            .collect();
+        let notifications = self.db.read_rows(&rowids)?;
+        Ok(self.tag(notifications))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::{
+        build_sources, source_id_base, InboxSource, NotificationSource, SourceConfig,
+        SOURCE_ID_BASE, SOURCE_ID_BLOCKS,
+    };
+    use crate::config_store::ConfigStore;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("notify-sources-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn inbox_files_are_ingested_once_and_bad_lines_skipped() {
+        let dir = temp_dir("inbox");
+        let mut source = InboxSource::new(dir.clone(), SOURCE_ID_BASE);
+        assert!(source.poll().unwrap().is_empty());
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("a.jsonl"),
+            "{\"bundle_id\":\"mail.filter\",\"title\":\"Invoice\",\"body\":\"Due today\",\"timestamp\":100}\n\
+             not json\n\
+             \n\
+             {\"title\":\"no bundle\"}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("b.jsonl"),
+            "{\"bundle_id\":\"script\",\"title\":\"Build done\"}\n",
+        )
+        .unwrap();
+        fs::write(dir.join("c.tmp"), "{\"bundle_id\":\"x\",\"title\":\"x\"}\n").unwrap();
+
+        let items = source.poll().unwrap();
+        let summary: Vec<_> = items
+            .iter()
+            .map(|n| (n.rowid, n.bundle_id.as_str(), n.title.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (SOURCE_ID_BASE + 1, "mail.filter", "Invoice"),
+                (SOURCE_ID_BASE + 2, "script", "Build done"),
+            ]
+        );
+        assert_eq!(items[0].timestamp, 100);
+        assert_eq!(items[0].body, "Due today");
+        assert!(items[1].timestamp > 100);
+
+        assert!(!dir.join("a.jsonl").exists());
+        assert!(dir.join("c.tmp").exists());
+        assert!(source.poll().unwrap().is_empty());
+    }
+
+    #[test]
+    fn sources_are_built_from_settings() {
+        let config = ConfigStore::new(temp_dir("build"));
+        let configs: Vec<SourceConfig> = serde_json::from_str(
+            r#"[
+                { "kind": "inbox" },
+                { "kind": "inbox", "dir": "/tmp/other-inbox" },
+                { "kind": "database", "path": "/tmp/work/db", "label": "仕事用" },
+                { "kind": "database", "path": "/tmp/home/db" }
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            configs[1],
+            SourceConfig::Inbox {
+                dir: Some(PathBuf::from("/tmp/other-inbox"))
+            }
+        );
+
+        assert_eq!(
+            configs[2],
+            SourceConfig::Database {
+                path: PathBuf::from("/tmp/work/db"),
+                label: Some("仕事用".to_string())
+            }
+        );
+
+        let mut sources = build_sources(&configs, &config);
+        let names: Vec<_> = sources.iter().map(|s| s.name()).collect();
+        assert_eq!(names, vec!["inbox", "inbox", "仕事用", "/tmp/home/db"]);
+        assert!(!sources[0].read_outside_focus() && sources[2].read_outside_focus());
+        fs::create_dir_all(config.dir().join("inbox")).unwrap();
+        fs::write(
+            config.dir().join("inbox/x.jsonl"),
+            "{\"bundle_id\":\"a\",\"title\":\"t\"}\n",
+        )
+        .unwrap();
+        let inbox_base = source_id_base(&config.dir().join("inbox"));
+        assert_eq!(sources[0].poll().unwrap()[0].rowid, inbox_base + 1);
+    }
+
+    #[test]
+    fn source_ids_do_not_depend_on_list_position() {
+        let config = ConfigStore::new(temp_dir("ids"));
+        let inbox = |name: &str| SourceConfig::Inbox {
+            dir: Some(config.dir().join(name)),
+        };
+        let last_id = |configs: &[SourceConfig]| {
+            let dir = config.dir().join("b");
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(
+                dir.join("x.jsonl"),
+                "{\"bundle_id\":\"a\",\"title\":\"t\"}\n",
+            )
+            .unwrap();
+            let mut sources = build_sources(configs, &config);
+            sources.last_mut().unwrap().poll().unwrap()[0].rowid
+        };
+
+        let base = source_id_base(&config.dir().join("b"));
+        assert_eq!(last_id(&[inbox("a"), inbox("b")]), base + 1);
+        assert_eq!(last_id(&[inbox("b")]), base + 1);
+
+        // A clash moves the later source to the next block.
+        let next = base % (SOURCE_ID_BASE * SOURCE_ID_BLOCKS as i64) + SOURCE_ID_BASE;
+        assert_eq!(last_id(&[inbox("b"), inbox("b")]), next + 1);
+    }
+}
@@ -0,0 +1,279 @@
+use std::sync::LazyLock;
+
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use regex::Regex;
+
+use crate::models::AnalyzedNotification;
+
+/// `15:00`, `3:30pm`, `9:05 a.m.`
+static CLOCK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(\d{1,2}):(\d{2})(?:\s*([ap])\.?m\b\.?)?").expect("invalid regex")
+});
+/// `3pm`, `11 a.m.`
+static MERIDIEM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(\d{1,2})\s*([ap])\.?m\b").expect("invalid regex"));
+/// `15時`, `午後3時半`, `10時30分`
+static JA_CLOCK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(午前|午後)?\s*(\d{1,2})時(?:(半)|(\d{1,2})分)?").expect("invalid regex")
+});
+/// Deadlines at the end of the delivery day.
+static TODAY_DEADLINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)今日中|本日中|今日まで|本日まで|今夜|今晩|\b(?:by today|tonight|eod|end of (?:the )?day)\b")
+        .expect("invalid regex")
+});
+/// Deadlines at the end of the next day.
+static TOMORROW_DEADLINE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)明日中|明日まで|\bby tomorrow\b").expect("invalid regex"));
+/// Moves clock times in the same text to the next day.
+static TOMORROW: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)明日|あした|\btomorrow\b").expect("invalid regex"));
+/// Words for deadlines and scheduled events, with or without a time.
+static SCHEDULE_WORDS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(concat!(
+        r"(?i)締切|締め切り|〆切|期限|会議|打ち合わせ|ミーティング|面談|予約|開始時刻",
+        r"|\b(?:deadline|meeting|appointment|(?:is|are) due|due (?:today|tomorrow|date|by|on|at|in)|starts? (?:at|in))\b",
+    ))
+    .expect("invalid regex")
+});
+
+/// The latest explicit time or deadline in `text` (`15:00`, `3pm`,
+/// `午後3時`, `今日中`, `EOD`, ...). Times are read on the local day of
+/// `delivered`, or the next day with `明日`/`tomorrow`. Once it has passed,
+/// the notification is possibly stale; text without such a reference never
+/// is.
+pub fn due_time(text: &str, delivered: NaiveDateTime) -> Option<NaiveDateTime> {
+    time_references(text, delivered).into_iter().max()
+}
+
+/// Whether `text` names a time, a deadline or a scheduled event such as a
+/// meeting. Used when the model did not say whether a notification is time
+/// sensitive.
+pub fn mentions_schedule(text: &str) -> bool {
+    SCHEDULE_WORDS.is_match(text) || !time_references(text, NaiveDateTime::default()).is_empty()
+}
+
+/// [`due_time`] of a collected notification's title, summary and body, as
+/// Unix seconds.
+pub fn notification_due_time(item: &AnalyzedNotification) -> Option<i64> {
+    let due = due_time(&item_text(item), local_time(item.timestamp))?;
+    due.and_local_timezone(Local)
+        .earliest()
+        .map(|due| due.timestamp())
+}
+
+fn item_text(item: &AnalyzedNotification) -> String {
+    format!("{}\n{}\n{}", item.title, item.summary_line, item.body)
+}
+
+fn local_time(timestamp: i64) -> NaiveDateTime {
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .with_timezone(&Local)
+        .naive_local()
+}
+
+fn time_references(text: &str, delivered: NaiveDateTime) -> Vec<NaiveDateTime> {
+    let text = normalize_digits(text);
+    let today = delivered.date();
+    let day = if TOMORROW.is_match(&text) {
+        today + Days::new(1)
+    } else {
+        today
+    };
+
+    let mut references = Vec::new();
+    if TODAY_DEADLINE.is_match(&text) {
+        references.push(end_of(today));
+    }
+    if TOMORROW_DEADLINE.is_match(&text) {
+        references.push(end_of(today + Days::new(1)));
+    }
+    for caps in CLOCK.captures_iter(&text) {
+        let whole = caps.get(0).expect("match");
+        // `2024:10:05` or `12:345` are not clock times.
+        if neighbours(&text, whole.start(), whole.end())
+            .iter()
+            .any(|c| c.is_some_and(|c| c.is_ascii_digit() || c == ':'))
+        {
+            continue;
+        }
+        let (Ok(hour), Ok(minute)) = (caps[1].parse::<u32>(), caps[2].parse::<u32>()) else {
+            continue;
+        };
+        let pm = caps.get(3).map(|m| m.as_str().eq_ignore_ascii_case("p"));
+        references.extend(clock_time(day, hour, minute, pm, delivered));
+    }
+    for caps in MERIDIEM.captures_iter(&text) {
+        let whole = caps.get(0).expect("match");
+        // The minutes of `3:05pm`, already read above.
+        if neighbours(&text, whole.start(), whole.end())[0] == Some(':') {
+            continue;
+        }
+        let Ok(hour) = caps[1].parse::<u32>() else {
+            continue;
+        };
+        let pm = caps[2].eq_ignore_ascii_case("p");
+        references.extend(clock_time(day, hour, 0, Some(pm), delivered));
+    }
+    for caps in JA_CLOCK.captures_iter(&text) {
+        let whole = caps.get(0).expect("match");
+        let [before, after] = neighbours(&text, whole.start(), whole.end());
+        // `3時間` is a duration, and `13時` is not `3時`.
+        if before.is_some_and(|c| c.is_ascii_digit())
+            || (caps.get(3).is_none() && caps.get(4).is_none() && after == Some('間'))
+        {
+            continue;
+        }
+        let Ok(hour) = caps[2].parse::<u32>() else {
+            continue;
+        };
+        let minute = match (caps.get(3), caps.get(4)) {
+            (Some(_), _) => 30,
+            (None, Some(m)) => m.as_str().parse().unwrap_or(60),
+            (None, None) => 0,
+        };
+        let pm = caps.get(1).map(|m| m.as_str() == "午後");
+        references.extend(clock_time(day, hour, minute, pm, delivered));
+    }
+    references
+}
+
+/// A clock time on `day`. Without am/pm, an hour under 12 can be either
+/// half of the day: the first reading not before delivery is taken, so
+/// `3:00` sent at 13:00 means 15:00.
+fn clock_time(
+    day: NaiveDate,
+    hour: u32,
+    minute: u32,
+    pm: Option<bool>,
+    delivered: NaiveDateTime,
+) -> Option<NaiveDateTime> {
+    let at = |hour| NaiveTime::from_hms_opt(hour, minute, 0).map(|time| day.and_time(time));
+    match pm {
+        Some(pm) => {
+            if !(1..=12).contains(&hour) {
+                return None;
+            }
+            at(hour % 12 + if pm { 12 } else { 0 })
+        }
+        None if (1..12).contains(&hour) => {
+            let morning = at(hour)?;
+            let evening = at(hour + 12)?;
+            Some(if morning >= delivered {
+                morning
+            } else {
+                evening
+            })
+        }
+        None => at(hour),
+    }
+}
+
+fn end_of(day: NaiveDate) -> NaiveDateTime {
+    (day + Days::new(1)).and_time(NaiveTime::MIN)
+}
+
+fn neighbours(text: &str, start: usize, end: usize) -> [Option<char>; 2] {
+    [
+        text[..start].chars().next_back(),
+        text[end..].chars().next(),
+    ]
+}
+
+/// Full-width digits and colons as ASCII, as Japanese text often has them.
+fn normalize_digits(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '０'..='９' => char::from(b'0' + (c as u32 - '０' as u32) as u8),
+            '：' => ':',
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    use super::{due_time, mentions_schedule};
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn possibly_stale(text: &str, delivered: NaiveDateTime, now: NaiveDateTime) -> bool {
+        due_time(text, delivered).is_some_and(|due| due < now)
+    }
+
+    #[test]
+    fn passed_times_and_deadlines_are_stale() {
+        let delivered = at(16, 13, 0);
+        let stale = |text: &str, now| possibly_stale(text, delivered, now);
+
+        assert!(stale("15:00会議のURL変更", at(16, 17, 0)));
+        assert!(!stale("15:00会議のURL変更", at(16, 14, 59)));
+        assert!(stale("１５：３０からレビュー", at(16, 16, 0)));
+        assert!(stale("午後3時半に集合", at(16, 15, 31)));
+        assert!(stale("16時30分締切", at(16, 16, 31)));
+        assert!(stale("Standup moved to 3:30pm", at(16, 15, 31)));
+        assert!(stale("Call at 4 p.m.", at(16, 16, 1)));
+        // Sent after lunch, `3:00` is the afternoon.
+        assert!(!stale("3:00 sync", at(16, 14, 0)));
+        assert!(stale("3:00 sync", at(16, 15, 1)));
+
+        assert!(!stale("今日中に返信ください", at(16, 23, 59)));
+        assert!(stale("今日中に返信ください", at(17, 0, 1)));
+        assert!(stale("Please review by EOD", at(17, 9, 0)));
+        assert!(!stale("明日10時から打ち合わせ", at(16, 23, 0)));
+        assert!(stale("明日10時から打ち合わせ", at(17, 10, 1)));
+        assert!(!stale("Due tomorrow at 9am", at(17, 8, 0)));
+        // Only the latest reference counts.
+        assert!(!stale("15:00の会議は16:00に変更", at(16, 15, 30)));
+        assert_eq!(
+            due_time("15:00の会議は16:00に変更", delivered),
+            Some(at(16, 16, 0))
+        );
+        assert_eq!(due_time("明日中に提出", delivered), Some(at(18, 0, 0)));
+    }
+
+    #[test]
+    fn schedule_words_count_without_a_time() {
+        for text in [
+            "見積もりの提出期限が近づいています",
+            "Invoice #123 is due",
+            "Meeting starts in 5 minutes",
+            "15時から",
+        ] {
+            assert!(mentions_schedule(text), "{text}");
+        }
+        for text in [
+            "デプロイが完了しました",
+            "PR #1530 was merged",
+            "Delayed due to traffic",
+            "3時間前のビルドが失敗",
+        ] {
+            assert!(!mentions_schedule(text), "{text}");
+        }
+    }
+
+    #[test]
+    fn text_without_explicit_times_is_never_stale() {
+        let later = at(20, 0, 0);
+        for text in [
+            "デプロイが完了しました",
+            "3時間前のビルドが失敗",
+            "比率は 1:2 です",
+            "Version 2.10.3 released",
+            "PR #1530 was merged",
+            "Today's standup notes",
+            "2026-10-16T10:15:30 のログ",
+            "3 amazing deals",
+            "",
+        ] {
+            assert!(!possibly_stale(text, at(16, 9, 0), later), "{text}");
+        }
+    }
+}
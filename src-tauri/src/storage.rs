@@ -0,0 +1,327 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{Days, NaiveDate};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_MAINTENANCE_INTERVAL_SECS: i64 = 6 * 60 * 60;
+
+/// Size and age limits for one on-disk store. Unset fields do not limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StoreLimit {
+    pub max_age_days: Option<u64>,
+    /// Pretty-printed JSON size, which is what lands on disk.
+    pub max_bytes: Option<u64>,
+}
+
+/// Stored in `settings.json` under `storage`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageSettings {
+    /// Seconds between maintenance passes on idle polls. Unset uses
+    /// `DEFAULT_MAINTENANCE_INTERVAL_SECS`.
+    pub maintenance_interval_secs: Option<i64>,
+    /// Limits keyed by `Maintainable::store_name`. Stores not listed keep
+    /// their `default_limit`.
+    pub limits: BTreeMap<String, StoreLimit>,
+}
+
+impl StorageSettings {
+    pub fn limit(&self, store: &dyn Maintainable) -> StoreLimit {
+        self.limits
+            .get(store.store_name())
+            .copied()
+            .unwrap_or_else(|| store.default_limit())
+    }
+
+    fn interval_secs(&self) -> i64 {
+        self.maintenance_interval_secs
+            .unwrap_or(DEFAULT_MAINTENANCE_INTERVAL_SECS)
+    }
+}
+
+/// An on-disk store the maintenance pass keeps within its limits.
+pub trait Maintainable {
+    fn store_name(&self) -> &'static str;
+    fn default_limit(&self) -> StoreLimit;
+    /// Bytes the store currently takes on disk.
+    fn size_bytes(&self) -> u64;
+    /// Drops the oldest data beyond `limit` and returns how many entries went.
+    fn prune(&self, limit: StoreLimit, today: NaiveDate) -> Result<usize>;
+}
+
+/// One store as reported by `get_storage_usage` and `run_maintenance_now`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreUsage {
+    pub store: &'static str,
+    pub bytes: u64,
+    pub limit: StoreLimit,
+    /// Entries dropped by this pass; always 0 outside maintenance.
+    pub pruned: usize,
+}
+
+pub fn storage_usage(stores: &[&dyn Maintainable], settings: &StorageSettings) -> Vec<StoreUsage> {
+    stores
+        .iter()
+        .map(|store| StoreUsage {
+            store: store.store_name(),
+            bytes: store.size_bytes(),
+            limit: settings.limit(*store),
+            pruned: 0,
+        })
+        .collect()
+}
+
+/// Prunes every store to its limit. A failing store is logged and skipped so
+/// the others are still maintained.
+pub fn run_maintenance(
+    stores: &[&dyn Maintainable],
+    settings: &StorageSettings,
+    today: NaiveDate,
+) -> Vec<StoreUsage> {
+    stores
+        .iter()
+        .map(|store| {
+            let limit = settings.limit(*store);
+            let pruned = match store.prune(limit, today) {
+                Ok(pruned) => pruned,
+                Err(err) => {
+                    warn!("failed to prune {}: {err:#}", store.store_name());
+                    0
+                }
+            };
+            if pruned > 0 {
+                info!("pruned {pruned} entries from {}", store.store_name());
+            }
+            StoreUsage {
+                store: store.store_name(),
+                bytes: store.size_bytes(),
+                limit,
+                pruned,
+            }
+        })
+        .collect()
+}
+
+/// Tracks when the last maintenance pass ran. The first idle poll runs one.
+#[derive(Debug, Default)]
+pub struct MaintenanceSchedule {
+    last_run: Option<i64>,
+}
+
+impl MaintenanceSchedule {
+    pub fn due(&self, now: i64, settings: &StorageSettings) -> bool {
+        self.last_run
+            .is_none_or(|last| now - last >= settings.interval_secs())
+    }
+
+    pub fn mark_run(&mut self, now: i64) {
+        self.last_run = Some(now);
+    }
+}
+
+/// Drops `YYYY-MM-DD`-keyed entries older than `limit.max_age_days`, then the
+/// oldest remaining ones until the pretty JSON fits in `limit.max_bytes`.
+pub fn prune_dated<V: Serialize>(
+    days: &mut BTreeMap<String, V>,
+    limit: StoreLimit,
+    today: NaiveDate,
+) -> Result<usize> {
+    let before = days.len();
+    if let Some(cutoff) = limit
+        .max_age_days
+        .and_then(|age| today.checked_sub_days(Days::new(age)))
+    {
+        let cutoff = cutoff.format("%Y-%m-%d").to_string();
+        days.retain(|date, _| *date >= cutoff);
+    }
+    if let Some(max_bytes) = limit.max_bytes {
+        // The size only shrinks as more entries go, so search for the fewest
+        // to drop rather than serializing the map again after each one.
+        let fits = |drop: usize| -> Result<bool> {
+            let kept: BTreeMap<&String, &V> = days.iter().skip(drop).collect();
+            Ok(serde_json::to_vec_pretty(&kept)?.len() as u64 <= max_bytes)
+        };
+        let (mut low, mut high) = (0, days.len());
+        while low < high {
+            let mid = (low + high) / 2;
+            if fits(mid)? {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        for _ in 0..low {
+            days.pop_first();
+        }
+    }
+    Ok(before - days.len())
+}
+
+pub fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|meta| meta.len()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    use anyhow::{bail, Result};
+    use chrono::NaiveDate;
+
+    use super::{
+        prune_dated, run_maintenance, storage_usage, Maintainable, MaintenanceSchedule,
+        StorageSettings, StoreLimit,
+    };
+    use crate::config_store::ConfigStore;
+    use crate::models::{AnalyzedNotification, UrgencyLevel};
+    use crate::noise::AppStatsLedger;
+    use crate::usage::{TokenUsage, UsageLedger};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("notify-storage-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, month, day).unwrap()
+    }
+
+    fn days(dates: &[&str]) -> BTreeMap<String, u32> {
+        dates.iter().map(|date| (date.to_string(), 1)).collect()
+    }
+
+    #[test]
+    fn dated_entries_past_the_age_limit_are_dropped() {
+        let mut map = days(&["2026-02-28", "2026-03-01", "2026-03-10"]);
+        let limit = StoreLimit {
+            max_age_days: Some(9),
+            max_bytes: None,
+        };
+
+        assert_eq!(prune_dated(&mut map, limit, date(3, 10)).unwrap(), 1);
+        assert_eq!(map.keys().collect::<Vec<_>>(), ["2026-03-01", "2026-03-10"]);
+        assert_eq!(prune_dated(&mut map, limit, date(3, 10)).unwrap(), 0);
+    }
+
+    #[test]
+    fn oldest_entries_go_first_to_fit_the_size_limit() {
+        let mut map = days(&["2026-03-01", "2026-03-02", "2026-03-03"]);
+        let one_entry = serde_json::to_vec_pretty(&days(&["2026-03-03"]))
+            .unwrap()
+            .len() as u64;
+        let limit = StoreLimit {
+            max_age_days: None,
+            max_bytes: Some(one_entry),
+        };
+
+        assert_eq!(prune_dated(&mut map, limit, date(3, 3)).unwrap(), 2);
+        assert_eq!(map.keys().collect::<Vec<_>>(), ["2026-03-03"]);
+    }
+
+    #[test]
+    fn schedule_runs_first_then_waits_for_the_interval() {
+        let settings = StorageSettings {
+            maintenance_interval_secs: Some(600),
+            ..StorageSettings::default()
+        };
+        let mut schedule = MaintenanceSchedule::default();
+        assert!(schedule.due(1_000, &settings));
+
+        schedule.mark_run(1_000);
+        assert!(!schedule.due(1_599, &settings));
+        assert!(schedule.due(1_600, &settings));
+        assert!(!schedule.due(1_600, &StorageSettings::default()));
+    }
+
+    #[test]
+    fn maintenance_prunes_the_ledgers_on_disk() {
+        let store = ConfigStore::new(temp_dir("ledgers"));
+        let usage = UsageLedger::load(&store);
+        for day in [1, 20] {
+            usage
+                .record("ollama", date(3, day), TokenUsage::estimate("ab", "cd"))
+                .unwrap();
+        }
+        let mut stats = AppStatsLedger::load(&store);
+        stats
+            .record(&[AnalyzedNotification {
+                id: 1,
+                bundle_id: "com.example.app".to_string(),
+                app_name: "App".to_string(),
+                urgency: UrgencyLevel::Low,
+                timestamp: 1_700_000_000,
+                ..Default::default()
+            }])
+            .unwrap();
+        store.flush().unwrap();
+        let settings = StorageSettings {
+            limits: BTreeMap::from([(
+                "llm_usage".to_string(),
+                StoreLimit {
+                    max_age_days: Some(10),
+                    max_bytes: None,
+                },
+            )]),
+            ..StorageSettings::default()
+        };
+        let stores: [&dyn Maintainable; 2] = [&usage, &stats];
+        let before = storage_usage(&stores, &settings);
+
+        let report = run_maintenance(&stores, &settings, date(3, 20));
+
+        assert_eq!(report[0].store, "llm_usage");
+        assert_eq!(report[0].pruned, 1);
+        assert!(report[0].bytes < before[0].bytes);
+        // 2023-11 is far past the 90-day default for app stats.
+        assert_eq!(report[1].store, "app_stats");
+        assert_eq!(report[1].limit.max_age_days, Some(90));
+        assert_eq!(report[1].pruned, 1);
+        assert_eq!(
+            UsageLedger::load(&store)
+                .costs(date(3, 20), 30, &BTreeMap::new())
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    struct Broken;
+
+    impl Maintainable for Broken {
+        fn store_name(&self) -> &'static str {
+            "broken"
+        }
+
+        fn default_limit(&self) -> StoreLimit {
+            StoreLimit::default()
+        }
+
+        fn size_bytes(&self) -> u64 {
+            7
+        }
+
+        fn prune(&self, _limit: StoreLimit, _today: NaiveDate) -> Result<usize> {
+            bail!("disk full")
+        }
+    }
+
+    #[test]
+    fn a_failing_store_does_not_stop_the_pass() {
+        let usage = UsageLedger::load(&ConfigStore::new(temp_dir("broken")));
+        let stores: [&dyn Maintainable; 2] = [&Broken, &usage];
+
+        let report = run_maintenance(&stores, &StorageSettings::default(), date(3, 20));
+
+        assert_eq!(report[0].bytes, 7);
+        assert_eq!(report[0].pruned, 0);
+        assert_eq!(report[1].store, "llm_usage");
+    }
+}
@@ -0,0 +1,462 @@
+use chrono::{DateTime, Local};
+
+use crate::language::{language_instruction, OutputLanguage};
+use crate::llm::summary_line_from_fields;
+use crate::models::{AnalyzedNotification, UiNotificationInput, UrgencyLevel};
+
+const NOW_HEADER: &str = "【今すぐ】";
+const MEDIUM_HEADER: &str = "【中】";
+const LOW_HEADER: &str = "【低】";
+/// Medium items listed when the model wrote no Medium digest.
+const MEDIUM_FALLBACK_ITEMS: usize = 3;
+
+/// One notification as the session summary refers to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SummaryItem {
+    pub id: i64,
+    pub app_name: String,
+    pub summary_line: String,
+    pub category: Option<String>,
+    /// Delivery time; `None` for hand-made lists.
+    pub timestamp: Option<i64>,
+    /// See [`AnalyzedNotification::due_at`].
+    pub due_at: Option<i64>,
+}
+
+impl SummaryItem {
+    fn line(&self) -> String {
+        format!("- [#{}][{}] {}", self.id, self.app_name, self.summary_line)
+    }
+
+    /// The prompt line, with the category after the app name when asked for
+    /// and the time since delivery at the end.
+    fn prompt_line(&self, by_category: bool, now: DateTime<Local>) -> String {
+        let line = match self.category.as_deref().filter(|_| by_category) {
+            Some(category) => format!(
+                "- [#{}][{}/{category}] {}",
+                self.id, self.app_name, self.summary_line
+            ),
+            None => self.line(),
+        };
+        let Some(timestamp) = self.timestamp else {
+            return line;
+        };
+        let age = format_age(now.timestamp() - timestamp);
+        if self.due_at.is_some_and(|due| due < now.timestamp()) {
+            format!("{line}（{age}・時刻経過）")
+        } else {
+            format!("{line}（{age}）")
+        }
+    }
+
+    fn mentioned_in(&self, lines: &[String]) -> bool {
+        let tag = format!("#{}", self.id);
+        lines.iter().any(|line| {
+            mentions_tag(line, &tag)
+                || (!self.summary_line.is_empty() && line.contains(&self.summary_line))
+        })
+    }
+}
+
+fn format_age(secs: i64) -> String {
+    match secs.max(0) {
+        secs if secs < 60 => "たった今".to_string(),
+        secs if secs < 3600 => format!("{}分前", secs / 60),
+        secs if secs < 86_400 => format!("{}時間前", secs / 3600),
+        secs => format!("{}日前", secs / 86_400),
+    }
+}
+
+/// `#12` must not match inside `#123`.
+fn mentions_tag(line: &str, tag: &str) -> bool {
+    line.match_indices(tag).any(|(at, _)| {
+        !line[at + tag.len()..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_digit())
+    })
+}
+
+/// The collected notifications split into summary tiers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SummarySource {
+    /// Critical then High, oldest first within a tier.
+    pub urgent: Vec<SummaryItem>,
+    pub medium: Vec<SummaryItem>,
+    pub low_count: usize,
+    /// Ask the model to group each app's items by category.
+    pub by_category: bool,
+}
+
+impl SummarySource {
+    pub fn from_items(items: &[AnalyzedNotification]) -> Self {
+        let mut ordered: Vec<&AnalyzedNotification> = items.iter().collect();
+        ordered.sort_by_key(|n| (n.urgency.rank(), n.timestamp, n.id));
+
+        let mut source = Self::default();
+        for n in ordered {
+            let item = SummaryItem {
+                id: n.id,
+                app_name: n.app_name.clone(),
+                summary_line: n.summary_line.clone(),
+                category: n.category.clone(),
+                timestamp: Some(n.timestamp),
+                due_at: n.due_at,
+            };
+            match n.urgency {
+                UrgencyLevel::Critical | UrgencyLevel::High => source.urgent.push(item),
+                UrgencyLevel::Medium => source.medium.push(item),
+                UrgencyLevel::Low => source.low_count += 1,
+            }
+        }
+        source
+    }
+
+    /// Source for a hand-made list, numbered from 1 in input order.
+    pub fn from_inputs(inputs: &[UiNotificationInput]) -> Self {
+        let items: Vec<AnalyzedNotification> = inputs
+            .iter()
+            .zip(1..)
+            .map(|(input, id)| AnalyzedNotification {
+                id,
+                body: input.body.clone(),
+                app_name: input.app_name.clone(),
+                urgency: input.urgency,
+                // Like a notification the model did not summarize.
+                summary_line: if input.summary_line.trim().is_empty() {
+                    summary_line_from_fields(["", "", &input.body], &[], None)
+                } else {
+                    input.summary_line.clone()
+                },
+                // Equal timestamps keep the input order within a tier.
+                timestamp: 0,
+                ..Default::default()
+            })
+            .collect();
+        let mut source = Self::from_items(&items);
+        for item in source.urgent.iter_mut().chain(&mut source.medium) {
+            item.timestamp = None;
+        }
+        source
+    }
+}
+
+/// `now` is when the summary is written; each item carries its age from
+/// then, so the model can say what may already be over.
+pub fn build_session_summary_prompt(
+    source: &SummarySource,
+    language: OutputLanguage,
+    now: DateTime<Local>,
+) -> String {
+    let list = |items: &[SummaryItem]| {
+        if items.is_empty() {
+            "なし".to_string()
+        } else {
+            items
+                .iter()
+                .map(|item| item.prompt_line(source.by_category, now))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    };
+    let category_rule = if source.by_category {
+        "[アプリ/カテゴリ] の付いた通知は、同じアプリ内でカテゴリごとにまとめる。\n"
+    } else {
+        ""
+    };
+    format!(
+        "集中モード中に届いた通知の一覧です。次の形式の{}でまとめてください。\n\
+前置きや追加説明は不要です。\n\
+{}\n\n\
+{NOW_HEADER}\n\
+緊急・高の通知を1件も省略せず1行ずつ。行頭の [#番号] はそのまま残す。\n\
+{MEDIUM_HEADER}\n\
+中優先の通知を1〜2行で要約。\n\
+{LOW_HEADER}\n\
+件数のみ1行。\n\
+{category_rule}\
+現在時刻は {}。行末の（〜前）は通知が届いてからの時間。\
+「時刻経過」の付いた通知や、時刻・期限が既に過ぎていそうな通知には\
+「既に開始済みの可能性」「期限を過ぎている可能性」のように添える。\n\n\
+緊急・高:\n{}\n\n中:\n{}\n\n低: {}件",
+        language.prompt_name(),
+        language_instruction(language),
+        now.format("%m/%d %H:%M"),
+        list(&source.urgent),
+        list(&source.medium),
+        source.low_count
+    )
+}
+
+/// Rebuilds the model's summary in the fixed tier order. Every Critical/High
+/// item missing from the 今すぐ section is appended to it, in source order,
+/// and the Low line is always computed from the counts.
+pub fn enforce_summary_tiers(text: &str, source: &SummarySource) -> String {
+    let mut now = Vec::new();
+    let mut medium = Vec::new();
+    let mut unplaced = Vec::new();
+    let mut section: Option<&str> = None;
+    for line in text.lines().map(str::trim_end) {
+        let trimmed = line.trim_start();
+        if let Some(header) = [NOW_HEADER, MEDIUM_HEADER, LOW_HEADER]
+            .into_iter()
+            .find(|header| trimmed.starts_with(header))
+        {
+            section = Some(header);
+            let rest = trimmed[header.len()..].trim();
+            if rest.is_empty() {
+                continue;
+            }
+            push_line(section, rest, &mut now, &mut medium, &mut unplaced);
+            continue;
+        }
+        if !trimmed.is_empty() {
+            push_line(section, line, &mut now, &mut medium, &mut unplaced);
+        }
+    }
+    // Text outside the requested sections is kept as part of the digest.
+    medium.extend(unplaced);
+
+    for item in &source.urgent {
+        if !item.mentioned_in(&now) {
+            now.push(item.line());
+        }
+    }
+    if now.is_empty() {
+        now.push("- なし".to_string());
+    }
+    if medium.is_empty() {
+        medium = medium_fallback(&source.medium);
+    }
+
+    format!(
+        "{NOW_HEADER}\n{}\n{MEDIUM_HEADER}\n{}\n{LOW_HEADER}\n低優先 {}件",
+        now.join("\n"),
+        medium.join("\n"),
+        source.low_count
+    )
+}
+
+fn push_line(
+    section: Option<&str>,
+    line: &str,
+    now: &mut Vec<String>,
+    medium: &mut Vec<String>,
+    unplaced: &mut Vec<String>,
+) {
+    match section {
+        Some(NOW_HEADER) => now.push(line.to_string()),
+        Some(MEDIUM_HEADER) => medium.push(line.to_string()),
+        // The Low line is replaced by the real count.
+        Some(_) => {}
+        None => unplaced.push(line.to_string()),
+    }
+}
+
+fn medium_fallback(items: &[SummaryItem]) -> Vec<String> {
+    if items.is_empty() {
+        return vec!["- なし".to_string()];
+    }
+    let mut lines: Vec<String> = items
+        .iter()
+        .take(MEDIUM_FALLBACK_ITEMS)
+        .map(|item| format!("- [{}] {}", item.app_name, item.summary_line))
+        .collect();
+    if items.len() > MEDIUM_FALLBACK_ITEMS {
+        lines.push(format!("- ほか{}件", items.len() - MEDIUM_FALLBACK_ITEMS));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Local, TimeZone};
+
+    use super::{build_session_summary_prompt, enforce_summary_tiers, SummarySource};
+    use crate::language::OutputLanguage;
+    use crate::models::{AnalyzedNotification, UiNotificationInput, UrgencyLevel};
+
+    fn item(id: i64, urgency: UrgencyLevel, summary: &str) -> AnalyzedNotification {
+        AnalyzedNotification {
+            id,
+            bundle_id: "com.example.app".to_string(),
+            app_name: "Slack".to_string(),
+            urgency,
+            summary_line: summary.to_string(),
+            timestamp: 1_700_000_000 + id,
+            ..Default::default()
+        }
+    }
+
+    /// Two hours after the items of [`source`] arrived.
+    fn two_hours_later() -> DateTime<Local> {
+        Local.timestamp_opt(1_700_000_000 + 7500, 0).unwrap()
+    }
+
+    fn source() -> SummarySource {
+        SummarySource::from_items(&[
+            item(3, UrgencyLevel::Medium, "雑談スレッド"),
+            item(12, UrgencyLevel::High, "レビュー依頼"),
+            item(7, UrgencyLevel::Critical, "本番障害"),
+            item(123, UrgencyLevel::Critical, "決済エラー"),
+            item(4, UrgencyLevel::Low, "ニュースレター"),
+            item(5, UrgencyLevel::Low, "広告"),
+        ])
+    }
+
+    /// Stands in for the LLM: answers in the requested format but drops
+    /// every urgent item it is told to forget.
+    fn dropping_provider(drop: &'static [i64]) -> impl Fn(&str) -> String {
+        move |prompt| {
+            let urgent: Vec<&str> = prompt
+                .lines()
+                .filter(|line| line.starts_with("- [#"))
+                .filter(|line| {
+                    !drop
+                        .iter()
+                        .any(|id| line.starts_with(&format!("- [#{id}]")))
+                })
+                .filter(|line| !line.contains("雑談"))
+                .collect();
+            format!(
+                "【今すぐ】\n{}\n【中】\nSlackで雑談が続いています。\n【低】\n3件",
+                urgent.join("\n")
+            )
+        }
+    }
+
+    #[test]
+    fn urgent_items_come_first_in_source_order() {
+        let source = source();
+        let ids: Vec<_> = source.urgent.iter().map(|item| item.id).collect();
+        assert_eq!(ids, vec![7, 123, 12]);
+        assert_eq!(source.medium.len(), 1);
+        assert_eq!(source.low_count, 2);
+    }
+
+    #[test]
+    fn omitted_urgent_items_are_appended_to_the_now_section() {
+        let source = source();
+        let text = dropping_provider(&[123, 12])(&build_session_summary_prompt(
+            &source,
+            OutputLanguage::Japanese,
+            two_hours_later(),
+        ));
+
+        let summary = enforce_summary_tiers(&text, &source);
+
+        assert_eq!(
+            summary,
+            "【今すぐ】\n- [#7][Slack] 本番障害（2時間前）\n- [#123][Slack] 決済エラー\n- [#12][Slack] レビュー依頼\n\
+             【中】\nSlackで雑談が続いています。\n【低】\n低優先 2件"
+        );
+    }
+
+    #[test]
+    fn complete_answers_are_left_alone_apart_from_the_low_count() {
+        let source = source();
+        let text = dropping_provider(&[])(&build_session_summary_prompt(
+            &source,
+            OutputLanguage::Japanese,
+            two_hours_later(),
+        ));
+
+        let summary = enforce_summary_tiers(&text, &source);
+
+        assert_eq!(summary.matches("決済エラー").count(), 1);
+        assert!(summary.ends_with("【低】\n低優先 2件"));
+    }
+
+    #[test]
+    fn prompt_lines_carry_their_age_and_passed_times() {
+        let at = |hour| Local.with_ymd_and_hms(2026, 10, 16, hour, 0, 0).unwrap();
+        let mut meeting = item(1, UrgencyLevel::High, "15:00会議のURL変更");
+        meeting.timestamp = at(13).timestamp();
+        meeting.refresh_due_at();
+        let mut review = item(2, UrgencyLevel::Medium, "レビュー依頼");
+        review.timestamp = at(16).timestamp();
+        review.refresh_due_at();
+        // The body counts as it does in the view.
+        let mut call = item(3, UrgencyLevel::Medium, "折り返しの依頼");
+        call.body = "16:30までに電話ください".to_string();
+        call.timestamp = at(16).timestamp();
+        call.refresh_due_at();
+        let source = SummarySource::from_items(&[meeting, review, call]);
+
+        let prompt = build_session_summary_prompt(&source, OutputLanguage::Japanese, at(17));
+
+        assert!(prompt.contains("現在時刻は 10/16 17:00。"));
+        assert!(prompt.contains("- [#1][Slack] 15:00会議のURL変更（4時間前・時刻経過）\n"));
+        assert!(prompt.contains("- [#2][Slack] レビュー依頼（1時間前）\n"));
+        assert!(prompt.contains("- [#3][Slack] 折り返しの依頼（1時間前・時刻経過）\n"));
+
+        let inputs: Vec<UiNotificationInput> = serde_json::from_value(serde_json::json!([
+            { "appName": "Mail", "urgency": "high", "summaryLine": "15:00締切" }
+        ]))
+        .unwrap();
+        let prompt = build_session_summary_prompt(
+            &SummarySource::from_inputs(&inputs),
+            OutputLanguage::Japanese,
+            at(17),
+        );
+        assert!(prompt.contains("- [#1][Mail] 15:00締切\n"));
+    }
+
+    #[test]
+    fn id_prefixes_do_not_count_as_mentions() {
+        let source = SummarySource::from_items(&[
+            item(12, UrgencyLevel::Critical, ""),
+            item(123, UrgencyLevel::Critical, ""),
+        ]);
+        let summary = enforce_summary_tiers("【今すぐ】\n- [#123] 決済", &source);
+        assert!(summary.contains("- [#12][Slack] \n"));
+    }
+
+    #[test]
+    fn free_form_answers_are_restructured() {
+        let source = source();
+        let text = "Slackで雑談が続いています。\n【低】\n5件\n【今すぐ】\n本番障害 (#7) に対応してください。";
+
+        let summary = enforce_summary_tiers(text, &source);
+
+        assert!(summary.starts_with("【今すぐ】\n本番障害 (#7) に対応してください。\n- [#123]"));
+        assert!(summary.contains("【中】\nSlackで雑談が続いています。\n【低】"));
+        assert!(summary.ends_with("低優先 2件"));
+    }
+
+    #[test]
+    fn imported_inputs_are_numbered_in_order() {
+        let inputs: Vec<UiNotificationInput> = serde_json::from_value(serde_json::json!([
+            { "appName": "Mail", "urgency": "medium", "body": " 請求書が届きました " },
+            { "appName": "Slack", "urgency": "high", "summaryLine": "レビュー依頼", "body": "…" },
+            { "appName": "PagerDuty", "urgency": "high", "summaryLine": "障害" },
+            { "appName": "News", "urgency": "low", "summaryLine": "記事" }
+        ]))
+        .unwrap();
+
+        let source = SummarySource::from_inputs(&inputs);
+
+        let urgent: Vec<_> = source
+            .urgent
+            .iter()
+            .map(|item| (item.id, item.app_name.as_str()))
+            .collect();
+        assert_eq!(urgent, vec![(2, "Slack"), (3, "PagerDuty")]);
+        assert_eq!(source.urgent[0].summary_line, "レビュー依頼");
+        assert_eq!(source.medium[0].id, 1);
+        assert_eq!(source.medium[0].summary_line, "請求書が届きました");
+        assert_eq!(source.low_count, 1);
+    }
+
+    #[test]
+    fn empty_answers_fall_back_to_listing_items() {
+        let source = SummarySource::from_items(&[item(3, UrgencyLevel::Medium, "雑談スレッド")]);
+
+        let summary = enforce_summary_tiers("", &source);
+
+        assert_eq!(
+            summary,
+            "【今すぐ】\n- なし\n【中】\n- [Slack] 雑談スレッド\n【低】\n低優先 0件"
+        );
+    }
+}
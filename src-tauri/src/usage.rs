@@ -0,0 +1,304 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use chrono::{Days, NaiveDate};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config_store::{ConfigFile, ConfigStore};
+use crate::storage::{file_size, prune_dated, Maintainable, StoreLimit};
+
+/// Rough characters per token for responses without usage fields. Notification
+/// text is mostly Japanese, which tokenizes denser than English.
+const ESTIMATED_CHARS_PER_TOKEN: u64 = 2;
+/// Default age limit for `llm_usage.json`.
+const USAGE_RETENTION_DAYS: u64 = 365;
+
+/// Tokens used by one LLM request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Counted from characters because the provider reported no usage.
+    pub estimated: bool,
+}
+
+impl TokenUsage {
+    /// Usage reported in an Ollama response (`prompt_eval_count` / `eval_count`).
+    pub fn from_response(response: &Value) -> Option<Self> {
+        let prompt = response.get("prompt_eval_count").and_then(Value::as_u64);
+        let completion = response.get("eval_count").and_then(Value::as_u64);
+        if prompt.is_none() && completion.is_none() {
+            return None;
+        }
+        Some(Self {
+            prompt_tokens: prompt.unwrap_or(0),
+            completion_tokens: completion.unwrap_or(0),
+            estimated: false,
+        })
+    }
+
+    pub fn estimate(prompt: &str, completion: &str) -> Self {
+        let tokens = |text: &str| (text.chars().count() as u64).div_ceil(ESTIMATED_CHARS_PER_TOKEN);
+        Self {
+            prompt_tokens: tokens(prompt),
+            completion_tokens: tokens(completion),
+            estimated: true,
+        }
+    }
+}
+
+/// Per-1k-token prices for one provider, in whatever currency the user enters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenPrice {
+    pub prompt_per_1k: f64,
+    pub completion_per_1k: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct DailyUsage {
+    requests: u64,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    /// Some of the day's requests were estimated.
+    estimated: bool,
+}
+
+/// One provider's usage on one day, as returned by `get_llm_costs`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LlmDailyCost {
+    /// Local date, `YYYY-MM-DD`.
+    pub date: String,
+    pub provider: String,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub estimated: bool,
+    /// 0 when no price is configured for the provider.
+    pub cost: f64,
+}
+
+type DailyTotals = BTreeMap<String, BTreeMap<String, DailyUsage>>;
+
+/// Token totals per local date and provider, persisted in `llm_usage.json`.
+/// Like `AppStatsLedger`, recording only stages the file for the poll loop
+/// to flush, and the maintenance pass prunes old days.
+#[derive(Debug)]
+pub struct UsageLedger {
+    days: Mutex<DailyTotals>,
+    store: ConfigStore,
+}
+
+impl UsageLedger {
+    pub fn load(store: &ConfigStore) -> Self {
+        let days = match store.read(ConfigFile::LlmUsage) {
+            Some(content) => serde_json::from_str(&content).unwrap_or_else(|err| {
+                warn!("Failed to parse llm_usage.json: {err:#}");
+                DailyTotals::new()
+            }),
+            None => DailyTotals::new(),
+        };
+        Self {
+            days: Mutex::new(days),
+            store: store.clone(),
+        }
+    }
+
+    pub fn record(&self, provider: &str, date: NaiveDate, usage: TokenUsage) -> Result<()> {
+        let mut days = self
+            .days
+            .lock()
+            .map_err(|err| anyhow!("usage lock error: {err}"))?;
+        let day = days
+            .entry(date.format("%Y-%m-%d").to_string())
+            .or_default()
+            .entry(provider.to_string())
+            .or_default();
+        day.requests += 1;
+        day.prompt_tokens += usage.prompt_tokens;
+        day.completion_tokens += usage.completion_tokens;
+        day.estimated |= usage.estimated;
+        self.store.stage(ConfigFile::LlmUsage, &*days)
+    }
+
+    /// Usage for the `days` days up to and including `today`, oldest first.
+    pub fn costs(
+        &self,
+        today: NaiveDate,
+        days: u32,
+        prices: &BTreeMap<String, TokenPrice>,
+    ) -> Result<Vec<LlmDailyCost>> {
+        let totals = self
+            .days
+            .lock()
+            .map_err(|err| anyhow!("usage lock error: {err}"))?;
+        let mut costs = Vec::new();
+        for back in (0..days).rev() {
+            let Some(date) = today.checked_sub_days(Days::new(back.into())) else {
+                continue;
+            };
+            let date = date.format("%Y-%m-%d").to_string();
+            let Some(providers) = totals.get(&date) else {
+                continue;
+            };
+            for (provider, usage) in providers {
+                let price = prices.get(provider).copied().unwrap_or_default();
+                costs.push(LlmDailyCost {
+                    date: date.clone(),
+                    provider: provider.clone(),
+                    requests: usage.requests,
+                    prompt_tokens: usage.prompt_tokens,
+                    completion_tokens: usage.completion_tokens,
+                    estimated: usage.estimated,
+                    cost: usage.prompt_tokens as f64 / 1000.0 * price.prompt_per_1k
+                        + usage.completion_tokens as f64 / 1000.0 * price.completion_per_1k,
+                });
+            }
+        }
+        Ok(costs)
+    }
+}
+
+impl Maintainable for UsageLedger {
+    fn store_name(&self) -> &'static str {
+        "llm_usage"
+    }
+
+    fn default_limit(&self) -> StoreLimit {
+        StoreLimit {
+            max_age_days: Some(USAGE_RETENTION_DAYS),
+            max_bytes: None,
+        }
+    }
+
+    fn size_bytes(&self) -> u64 {
+        file_size(&self.store.path(ConfigFile::LlmUsage))
+    }
+
+    fn prune(&self, limit: StoreLimit, today: NaiveDate) -> Result<usize> {
+        let pruned = {
+            let mut days = self
+                .days
+                .lock()
+                .map_err(|err| anyhow!("usage lock error: {err}"))?;
+            let pruned = prune_dated(&mut days, limit, today)?;
+            if pruned > 0 {
+                self.store.stage(ConfigFile::LlmUsage, &*days)?;
+            }
+            pruned
+        };
+        if pruned > 0 {
+            self.store.flush()?;
+        }
+        Ok(pruned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::NaiveDate;
+    use serde_json::json;
+
+    use super::{TokenPrice, TokenUsage, UsageLedger};
+    use crate::config_store::ConfigStore;
+
+    fn ledger(name: &str) -> (UsageLedger, ConfigStore) {
+        let dir =
+            std::env::temp_dir().join(format!("notify-usage-test-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = ConfigStore::new(dir);
+        (UsageLedger::load(&store), store)
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 3, day).unwrap()
+    }
+
+    #[test]
+    fn usage_is_read_from_response_fields() {
+        let response = json!({
+            "response": "{}",
+            "done": true,
+            "prompt_eval_count": 412,
+            "eval_count": 57
+        });
+        assert_eq!(
+            TokenUsage::from_response(&response),
+            Some(TokenUsage {
+                prompt_tokens: 412,
+                completion_tokens: 57,
+                estimated: false,
+            })
+        );
+        assert_eq!(
+            TokenUsage::from_response(&json!({ "response": "{}" })),
+            None
+        );
+    }
+
+    #[test]
+    fn missing_usage_is_estimated_from_characters() {
+        let usage = TokenUsage::estimate("緊急度を判定して", "abc");
+        assert_eq!(usage.prompt_tokens, 4);
+        assert_eq!(usage.completion_tokens, 2);
+        assert!(usage.estimated);
+    }
+
+    #[test]
+    fn usage_accumulates_per_day_and_provider_with_prices() {
+        let (ledger, store) = ledger("accumulate");
+        let reported = |prompt_eval_count: u64, eval_count: u64| {
+            TokenUsage::from_response(&json!({
+                "prompt_eval_count": prompt_eval_count,
+                "eval_count": eval_count
+            }))
+            .unwrap()
+        };
+        ledger
+            .record("ollama", date(1), reported(1000, 200))
+            .unwrap();
+        ledger
+            .record("ollama", date(1), reported(500, 100))
+            .unwrap();
+        ledger
+            .record("ollama", date(2), TokenUsage::estimate("ab", "cd"))
+            .unwrap();
+        ledger.record("other", date(2), reported(2000, 0)).unwrap();
+
+        let prices = BTreeMap::from([(
+            "ollama".to_string(),
+            TokenPrice {
+                prompt_per_1k: 0.5,
+                completion_per_1k: 2.0,
+            },
+        )]);
+        let costs = ledger.costs(date(2), 7, &prices).unwrap();
+
+        assert_eq!(costs.len(), 3);
+        assert_eq!(costs[0].date, "2026-03-01");
+        assert_eq!(costs[0].requests, 2);
+        assert_eq!(costs[0].prompt_tokens, 1500);
+        assert_eq!(costs[0].completion_tokens, 300);
+        assert!(!costs[0].estimated);
+        assert!((costs[0].cost - 1.35).abs() < 1e-9);
+        assert!(costs[1].estimated);
+        assert_eq!(costs[2].provider, "other");
+        assert_eq!(costs[2].cost, 0.0);
+
+        assert_eq!(ledger.costs(date(2), 1, &prices).unwrap().len(), 2);
+        // Four records, one write once the poll loop flushes.
+        assert!(UsageLedger::load(&store)
+            .costs(date(2), 7, &prices)
+            .unwrap()
+            .is_empty());
+        store.flush().unwrap();
+        let reloaded = UsageLedger::load(&store);
+        assert_eq!(reloaded.costs(date(2), 7, &prices).unwrap(), costs);
+    }
+}
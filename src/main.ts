@@ -1,18 +1,34 @@
+type AppDelivery = "default" | "batch_only" | "always_dialog";
+
 type AppPromptEntry = {
   bundleId: string;
   context: string;
+  delivery: AppDelivery;
+};
+
+// Another user's notification store, read alongside this one.
+type NotificationDatabase = {
+  kind: "database";
+  path: string;
+  label: string | null;
 };
 
 type LlmSettings = {
   selectedModel: string;
   models: string[];
+  analysisEnabled: boolean;
+  outputLanguage: "ja" | "en";
+  modelLifecycle: boolean;
+  sessionKeepAliveMinutes: number;
 };
 
 type UrgencyLevel = "critical" | "high" | "medium" | "low";
+type InterruptionLevel = "passive" | "active" | "timeSensitive" | "critical";
 
 type UiNotification = {
   id: number;
   title: string;
+  headline: string;
   body: string;
   subtitle: string;
   bundleId: string;
@@ -23,13 +39,104 @@ type UiNotification = {
   summaryLine: string;
   reason: string;
   timestamp: number;
+  read: boolean;
+  pinned: boolean;
+  analyzedBy: "llm" | "fallback" | "disabled" | "rule";
+  lowConfidence: boolean;
+  confidence: number;
+  contextApplied: boolean;
+  appContext: string | null;
+  sessionContext: string | null;
+  threadId: string | null;
+  interruptionLevel: InterruptionLevel;
+  systemCritical: boolean;
+  translatedSummary: string | null;
+  sessionId: number | null;
+  category: string | null;
+  reasonCode:
+    | "deadline"
+    | "direct_mention"
+    | "incident"
+    | "security"
+    | "social"
+    | "marketing"
+    | "transactional"
+    | "unknown";
+  dueAt: number | null;
+  isTimeSensitive: boolean;
+  account: string | null;
+  provenance: Provenance;
+};
+
+type Rule =
+  | "system_critical"
+  | "escalation_keyword"
+  | "ignored_app"
+  | "muted_app"
+  | "interruption_level"
+  | "analysis_paused"
+  | "llm_analysis"
+  | "fallback";
+
+// Why a notification is shown the way it is.
+type Provenance = {
+  source: "live" | "backfill" | "simulated";
+  matchedRules: Rule[];
+  clamped: boolean;
+  brokeThrough: boolean;
+};
+
+// What a triage command did, plus the next item of a triage pass.
+type TriageStep<T> = {
+  result: T;
+  next: UiNotification | null;
 };
 
+// Which account's notifications a group action covers.
+type AccountScope =
+  | { kind: "all" }
+  | { kind: "own" }
+  | { kind: "named"; name: string };
+
 type UiNotificationGroup = {
   bundleId: string;
+  account: string | null;
+  scope: AccountScope;
   appName: string;
   iconBase64: string | null;
   notifications: UiNotification[];
+  note: string | null;
+};
+
+type UiNotificationGroups = {
+  revision: number;
+  groups: UiNotificationGroup[];
+};
+
+type SessionSummary = {
+  text: string;
+  createdAt: number;
+  notificationCount: number;
+  protectedSecs: number | null;
+  sleptSecs: number;
+  kind: "session" | "daily" | "away";
+  startedAt: number | null;
+  sessionId: number | null;
+};
+
+type OnboardingStep =
+  | "full_disk_access"
+  | "choose_model"
+  | "confirm_ollama"
+  | "interruption_threshold"
+  | "test_notification"
+  | "done";
+
+type OnboardingState = {
+  step: OnboardingStep;
+  optional: boolean;
+  completed: OnboardingStep[];
+  skipped: OnboardingStep[];
 };
 
 type TauriEvent<T = unknown> = {
@@ -70,6 +177,7 @@ const root = requireRoot();
 
 const state: {
   groups: UiNotificationGroup[];
+  revision: number;
   selected: UiNotification | null;
   error: string;
   loading: boolean;
@@ -77,11 +185,19 @@ const state: {
   prompts: AppPromptEntry[];
   editingPrompt: { bundleId: string; context: string; isNew: boolean } | null;
   ignoredApps: string[];
+  notificationDatabases: NotificationDatabase[];
+  newDatabase: { path: string; label: string };
   llmModels: string[];
   selectedLlmModel: string;
+  analysisEnabled: boolean;
   confirm: { message: string; okLabel?: string; onOk: () => void } | null;
+  summary: SessionSummary | null;
+  onboarding: OnboardingState | null;
+  onboardingThreshold: UrgencyLevel | "none";
+  lastClearedId: number | null;
 } = {
   groups: [],
+  revision: 0,
   selected: null,
   error: "",
   loading: false,
@@ -89,11 +205,22 @@ const state: {
   prompts: [],
   editingPrompt: null,
   ignoredApps: [],
+  notificationDatabases: [],
+  newDatabase: { path: "", label: "" },
   llmModels: [],
   selectedLlmModel: "",
+  analysisEnabled: true,
   confirm: null,
+  summary: null,
+  onboarding: null,
+  onboardingThreshold: "critical",
+  lastClearedId: null,
 };
 
+// Matches the backend's default restore window for a single clear.
+const RESTORE_WINDOW_MS = 60_000;
+let restoreTimer: number | undefined;
+
 const dom: {
   panel: HTMLElement | null;
   title: HTMLElement | null;
@@ -121,6 +248,7 @@ function resetUiToMainView(): void {
   state.selected = null;
   state.editingPrompt = null;
   state.confirm = null;
+  state.summary = null;
   state.error = "";
 }
 
@@ -220,6 +348,9 @@ function initView(): void {
       if (dismissType === "confirm") {
         state.confirm = null;
       }
+      if (dismissType === "summary") {
+        state.summary = null;
+      }
       render();
       return;
     }
@@ -236,6 +367,9 @@ function initView(): void {
 
     const id = Number(actionElement.dataset.id);
     const bundleId = actionElement.dataset.bundleId;
+    const scope: AccountScope = actionElement.dataset.scope
+      ? JSON.parse(actionElement.dataset.scope)
+      : { kind: "all" };
 
     switch (action) {
       case "refresh":
@@ -247,6 +381,24 @@ function initView(): void {
       case "clear-all":
         void clearAll();
         break;
+      case "undo-clear":
+        void undoClear();
+        break;
+      case "restore-one":
+        if (state.lastClearedId !== null) {
+          void restoreOne(state.lastClearedId);
+        }
+        break;
+      case "show-summary":
+        void showLastSummary(false);
+        break;
+      case "regenerate-summary":
+        void showLastSummary(true);
+        break;
+      case "close-summary":
+        state.summary = null;
+        render();
+        break;
       case "clear-all-close":
         void (async () => {
           const cleared = await clearAll();
@@ -273,6 +425,12 @@ function initView(): void {
         }
         state.selected = selected;
         render();
+        if (!selected.read) {
+          void invokeCommand<TriageStep<number>>("mark_notifications_read", {
+            ids: [id],
+            read: true,
+          });
+        }
         break;
       }
       case "open-app":
@@ -314,7 +472,8 @@ function initView(): void {
             okLabel: "無視する",
             onOk: async () => {
               await addIgnoredApp(bundleId);
-              await clearApp(bundleId);
+              // Ignoring is per app, so every account's group goes.
+              await clearApp(bundleId, { kind: "all" });
             },
           };
           render();
@@ -322,7 +481,12 @@ function initView(): void {
         break;
       case "clear-app":
         if (bundleId) {
-          void clearApp(bundleId);
+          void clearApp(bundleId, scope);
+        }
+        break;
+      case "snooze-app":
+        if (bundleId) {
+          void snoozeApp(bundleId, scope);
         }
         break;
       case "close-dialog":
@@ -370,6 +534,24 @@ function initView(): void {
           void removeIgnoredApp(bundleId);
         }
         break;
+      case "settings-add-database":
+        if (!state.newDatabase.path.trim()) {
+          state.error = "DB のパスを入力してください。";
+          render();
+          break;
+        }
+        void addNotificationDatabase(
+          state.newDatabase.path,
+          state.newDatabase.label,
+        );
+        break;
+      case "settings-remove-database": {
+        const path = actionElement.dataset.path;
+        if (path) {
+          void removeNotificationDatabase(path);
+        }
+        break;
+      }
       case "settings-refresh-models":
         void loadSettings();
         break;
@@ -402,6 +584,12 @@ function initView(): void {
         state.editingPrompt = null;
         render();
         break;
+      case "onboarding-next":
+        void runOnboardingStep();
+        break;
+      case "onboarding-skip":
+        void skipOnboardingStep();
+        break;
       default:
         break;
     }
@@ -411,6 +599,12 @@ function initView(): void {
     if (!(target instanceof HTMLInputElement || target instanceof HTMLTextAreaElement)) {
       return;
     }
+    if (target.dataset.field === "database-path") {
+      state.newDatabase.path = target.value;
+    }
+    if (target.dataset.field === "database-label") {
+      state.newDatabase.label = target.value;
+    }
     if (!state.editingPrompt) {
       return;
     }
@@ -429,6 +623,9 @@ function initView(): void {
     if (target.dataset.field === "llm-model") {
       state.selectedLlmModel = target.value;
     }
+    if (target.dataset.field === "onboarding-threshold") {
+      state.onboardingThreshold = target.value as UrgencyLevel | "none";
+    }
   });
 }
 
@@ -468,6 +665,18 @@ function renderHeaderActions(view: "notifications" | "settings", _loading: boole
     dummyBtn.innerHTML =
       '<svg width="15" height="15" viewBox="0 0 16 16" fill="none" stroke="currentColor" stroke-width="1.5" stroke-linecap="round" stroke-linejoin="round"><path d="M8 1v14"/><path d="M1 8h14"/></svg>';
 
+    const summaryBtn = create("button", "icon-btn");
+    summaryBtn.title = "前回の要約";
+    summaryBtn.dataset.action = "show-summary";
+    summaryBtn.innerHTML =
+      '<svg width="15" height="15" viewBox="0 0 16 16" fill="none" stroke="currentColor" stroke-width="1.5" stroke-linecap="round" stroke-linejoin="round"><path d="M3 3h10M3 6.5h10M3 10h6M3 13.5h4"/></svg>';
+
+    const undoBtn = create("button", "icon-btn");
+    undoBtn.title = "クリアを元に戻す";
+    undoBtn.dataset.action = "undo-clear";
+    undoBtn.innerHTML =
+      '<svg width="15" height="15" viewBox="0 0 16 16" fill="none" stroke="currentColor" stroke-width="1.5" stroke-linecap="round" stroke-linejoin="round"><path d="M2 6h8a4 4 0 0 1 0 8H6"/><path d="M5 3L2 6l3 3"/></svg>';
+
     const clearAllBtn = create("button", "icon-btn warn");
     clearAllBtn.title = "全通知をクリア";
     clearAllBtn.dataset.action = "clear-all";
@@ -480,7 +689,15 @@ function renderHeaderActions(view: "notifications" | "settings", _loading: boole
     clearAndCloseBtn.innerHTML =
       '<svg width="15" height="15" viewBox="0 0 16 16" fill="none" stroke="currentColor" stroke-width="1.5" stroke-linecap="round" stroke-linejoin="round"><path d="M6 2H2v12h4"/><path d="M9 5l3 3-3 3"/><path d="M12 8H4"/></svg>';
 
-    actions.push(refreshBtn, dummyBtn, clearAllBtn, clearAndCloseBtn);
+    if (state.lastClearedId !== null) {
+      const restoreBtn = create("button", "icon-btn");
+      restoreBtn.title = "クリアした通知を戻す";
+      restoreBtn.dataset.action = "restore-one";
+      restoreBtn.innerHTML =
+        '<svg width="15" height="15" viewBox="0 0 16 16" fill="none" stroke="currentColor" stroke-width="1.5" stroke-linecap="round" stroke-linejoin="round"><path d="M2 8a6 6 0 1 0 1.76-4.24"/><path d="M2 2v4h4"/></svg>';
+      actions.push(restoreBtn);
+    }
+    actions.push(refreshBtn, dummyBtn, summaryBtn, undoBtn, clearAllBtn, clearAndCloseBtn);
   }
 
   const settingsBtn = create("button", "icon-btn");
@@ -499,19 +716,31 @@ function renderHeaderActions(view: "notifications" | "settings", _loading: boole
   dom.actions.replaceChildren(...actions);
 }
 
+function appLabel(appName: string, account: string | null): string {
+  return account ? `${appName}（${account}）` : appName;
+}
+
+// With group_by_account one app can have a group per account.
+function groupKey(group: UiNotificationGroup): string {
+  return `${group.account ?? ""}|${group.bundleId}`;
+}
+
 function groupSignature(group: UiNotificationGroup): string {
   return JSON.stringify({
     appName: group.appName,
     iconBase64: group.iconBase64,
+    note: group.note,
     notifications: group.notifications.map((notification) => ({
       id: notification.id,
       title: notification.title,
+      headline: notification.headline,
       body: notification.body,
       subtitle: notification.subtitle,
       urgencyColor: notification.urgencyColor,
       urgencyLabel: notification.urgencyLabel,
       summaryLine: notification.summaryLine,
       timestamp: notification.timestamp,
+      account: notification.account,
     })),
   });
 }
@@ -530,11 +759,11 @@ function renderGroups(groups: UiNotificationGroup[]): void {
   }
 
   dom.notificationById.clear();
-  const activeBundleIds = new Set(groups.map((group) => group.bundleId));
-  for (const [bundleId, entry] of dom.groupElements.entries()) {
-    if (!activeBundleIds.has(bundleId)) {
+  const activeKeys = new Set(groups.map(groupKey));
+  for (const [key, entry] of dom.groupElements.entries()) {
+    if (!activeKeys.has(key)) {
       entry.element.remove();
-      dom.groupElements.delete(bundleId);
+      dom.groupElements.delete(key);
     }
   }
 
@@ -545,12 +774,13 @@ function renderGroups(groups: UiNotificationGroup[]): void {
       dom.notificationById.set(notification.id, notification);
     }
 
+    const key = groupKey(group);
     const signature = groupSignature(group);
-    const existing = dom.groupElements.get(group.bundleId);
+    const existing = dom.groupElements.get(key);
     let section: HTMLElement;
     if (!existing) {
       section = create("section", "group");
-      dom.groupElements.set(group.bundleId, { signature: "", element: section });
+      dom.groupElements.set(key, { signature: "", element: section });
     } else {
       section = existing.element;
     }
@@ -558,7 +788,7 @@ function renderGroups(groups: UiNotificationGroup[]): void {
     if (!existing || existing.signature !== signature) {
       section.style.animationDelay = `${groupIdx * 0.06}s`;
       section.replaceChildren(...renderGroupContent(group, groupIdx));
-      dom.groupElements.set(group.bundleId, { signature, element: section });
+      dom.groupElements.set(key, { signature, element: section });
     }
 
     orderedElements.push(section);
@@ -579,7 +809,7 @@ function render(): void {
   initView();
   renderHeaderActions(state.view, state.loading);
   renderGroups(state.groups);
-  renderDialogs(state.selected, state.confirm);
+  renderDialogs(state.selected, state.confirm, state.summary, state.onboarding);
 
   if (dom.error) {
     dom.error.textContent = state.error;
@@ -600,9 +830,12 @@ function renderGroupContent(group: UiNotificationGroup, groupIdx: number): HTMLE
   const groupTitle = create(
     "h2",
     "group-title",
-    `${group.appName} (${group.notifications.length})`,
+    `${appLabel(group.appName, group.account)} (${group.notifications.length})`,
   );
   groupTitleWrap.append(groupTitle);
+  if (group.note) {
+    groupTitleWrap.append(create("span", "group-note", group.note));
+  }
 
   const groupActions = create("div", "group-actions");
 
@@ -620,14 +853,23 @@ function renderGroupContent(group: UiNotificationGroup, groupIdx: number): HTMLE
   ignoreBtn.innerHTML =
     '<svg width="14" height="14" viewBox="0 0 16 16" fill="none" stroke="currentColor" stroke-width="1.5" stroke-linecap="round" stroke-linejoin="round"><path d="M1 1l14 14"/><path d="M6.5 6.5a2 2 0 0 0 3 3"/><path d="M2.5 2.5C1.4 3.7.5 5.2.5 8c0 4.5 7.5 7 7.5 7s2.3-.8 4.5-2.5"/><path d="M14 11.2C15 9.8 15.5 8.5 15.5 8c0-4.5-7.5-7-7.5-7-.8.4-1.7 1-2.5 1.6"/></svg>';
 
+  const snoozeBtn = create("button", "group-clear-btn");
+  snoozeBtn.title = "集中モード終了まで非表示";
+  snoozeBtn.dataset.action = "snooze-app";
+  snoozeBtn.dataset.bundleId = group.bundleId;
+  snoozeBtn.dataset.scope = JSON.stringify(group.scope);
+  snoozeBtn.innerHTML =
+    '<svg width="14" height="14" viewBox="0 0 16 16" fill="none" stroke="currentColor" stroke-width="1.5" stroke-linecap="round" stroke-linejoin="round"><circle cx="8" cy="8" r="6.5"/><path d="M8 4.5V8l2.5 1.5"/></svg>';
+
   const clearAppBtn = create("button", "group-clear-btn");
   clearAppBtn.title = "このアプリをクリア";
   clearAppBtn.dataset.action = "clear-app";
   clearAppBtn.dataset.bundleId = group.bundleId;
+  clearAppBtn.dataset.scope = JSON.stringify(group.scope);
   clearAppBtn.innerHTML =
     '<svg width="14" height="14" viewBox="0 0 16 16" fill="none" stroke="currentColor" stroke-width="1.5" stroke-linecap="round" stroke-linejoin="round"><path d="M2 4h12M5.33 4V2.67a1.33 1.33 0 0 1 1.34-1.34h2.66a1.33 1.33 0 0 1 1.34 1.34V4M6.67 7.33v4M9.33 7.33v4"/><path d="M3.33 4h9.34l-.67 9.33a1.33 1.33 0 0 1-1.33 1.34H5.33A1.33 1.33 0 0 1 4 13.33L3.33 4z"/></svg>';
 
-  groupActions.append(promptBtn, ignoreBtn, clearAppBtn);
+  groupActions.append(promptBtn, ignoreBtn, snoozeBtn, clearAppBtn);
   groupHeader.append(groupTitleWrap, groupActions);
 
   const cards = create("div", "cards");
@@ -645,6 +887,8 @@ function renderGroupContent(group: UiNotificationGroup, groupIdx: number): HTMLE
 function renderDialogs(
   selected: UiNotification | null,
   confirm: { message: string; okLabel?: string; onOk: () => void } | null,
+  summary: SessionSummary | null,
+  onboarding: OnboardingState | null,
 ): void {
   if (!dom.overlays) {
     return;
@@ -654,12 +898,113 @@ function renderDialogs(
   if (selected) {
     overlays.push(renderDialog(selected));
   }
+  if (summary) {
+    overlays.push(renderSummaryDialog(summary));
+  }
   if (confirm) {
     overlays.push(renderConfirmDialog(confirm));
   }
+  if (onboarding && onboarding.step !== "done") {
+    overlays.push(renderOnboardingDialog(onboarding));
+  }
   dom.overlays.replaceChildren(...overlays);
 }
 
+const ONBOARDING_TEXT: Record<
+  Exclude<OnboardingStep, "done">,
+  { title: string; body: string; next: string }
+> = {
+  full_disk_access: {
+    title: "フルディスクアクセス",
+    body: "通知の読み取りにはフルディスクアクセスが必要です。システム設定 > プライバシーとセキュリティ で許可してから確認してください。",
+    next: "確認する",
+  },
+  choose_model: {
+    title: "LLM モデル",
+    body: "通知の分析に使う Ollama のモデルを選択します。",
+    next: "このモデルを使う",
+  },
+  confirm_ollama: {
+    title: "Ollama の接続確認",
+    body: "選択したモデルで Ollama に接続できるか確認します。後で設定から確認することもできます。",
+    next: "接続を確認",
+  },
+  interruption_threshold: {
+    title: "パネルを自動で開く緊急度",
+    body: "この緊急度以上の通知が届いたとき、パネルを自動で開きます。",
+    next: "決定",
+  },
+  test_notification: {
+    title: "テスト通知",
+    body: "テスト通知を送って、バナーが表示されることを確認します。",
+    next: "テスト通知を送る",
+  },
+};
+
+const ONBOARDING_THRESHOLDS: [UrgencyLevel | "none", string][] = [
+  ["critical", "緊急のみ"],
+  ["high", "高以上"],
+  ["medium", "中以上"],
+  ["none", "自動で開かない"],
+];
+
+function renderOnboardingDialog(onboarding: OnboardingState): HTMLElement {
+  const overlay = create("div", "overlay");
+
+  const dialog = create("article", "dialog");
+  dialog.style.width = "min(360px, 88vw)";
+
+  const step = onboarding.step as Exclude<OnboardingStep, "done">;
+  const text = ONBOARDING_TEXT[step];
+  const title = create("h3", "dialog-title", text.title);
+  const body = create("p", "dialog-section", text.body);
+  dialog.append(title, body);
+
+  if (step === "choose_model") {
+    const select = document.createElement("select");
+    select.className = "prompt-input prompt-select";
+    select.dataset.field = "llm-model";
+    for (const model of state.llmModels) {
+      const option = document.createElement("option");
+      option.value = model;
+      option.textContent = model;
+      option.selected = model === state.selectedLlmModel;
+      select.append(option);
+    }
+    select.disabled = state.llmModels.length === 0;
+    dialog.append(select);
+  }
+  if (step === "interruption_threshold") {
+    const select = document.createElement("select");
+    select.className = "prompt-input prompt-select";
+    select.dataset.field = "onboarding-threshold";
+    for (const [value, label] of ONBOARDING_THRESHOLDS) {
+      const option = document.createElement("option");
+      option.value = value;
+      option.textContent = label;
+      option.selected = value === state.onboardingThreshold;
+      select.append(option);
+    }
+    dialog.append(select);
+  }
+
+  const actions = create("div", "panel-actions");
+  actions.style.justifyContent = "flex-end";
+  actions.style.marginTop = "16px";
+  if (onboarding.optional) {
+    const skipBtn = create("button", "btn secondary", "スキップ");
+    skipBtn.dataset.action = "onboarding-skip";
+    actions.append(skipBtn);
+  }
+  const nextBtn = create("button", "btn", text.next);
+  nextBtn.dataset.action = "onboarding-next";
+  actions.append(nextBtn);
+
+  dialog.append(actions);
+  overlay.append(dialog);
+  return overlay;
+}
+
 function renderConfirmDialog(confirm: {
   message: string;
   okLabel?: string;
@@ -689,6 +1034,60 @@ function renderConfirmDialog(confirm: {
   return overlay;
 }
 
+function renderSummaryDialog(summary: SessionSummary): HTMLElement {
+  const overlay = create("div", "overlay");
+  overlay.dataset.dismissOnBackdrop = "summary";
+
+  const dialog = create("article", "dialog");
+  dialog.style.width = "min(360px, 88vw)";
+
+  const title = create(
+    "h3",
+    "dialog-title",
+    summary.kind === "daily"
+      ? "今日のまとめ"
+      : summary.kind === "away"
+        ? "離席中の通知"
+        : "前回の要約",
+  );
+  const metaParts = [
+    formatRelativeTime(summary.createdAt),
+    `${summary.notificationCount}件`,
+  ];
+  if (summary.protectedSecs !== null) {
+    metaParts.push(`集中 ${Math.round(summary.protectedSecs / 60)}分`);
+  }
+  if (summary.sleptSecs > 0) {
+    metaParts.push(`スリープ中 ${Math.round(summary.sleptSecs / 60)}分を除く`);
+  }
+  if (summary.kind === "away" && summary.startedAt !== null) {
+    metaParts.push(
+      `離席 ${Math.round((summary.createdAt - summary.startedAt) / 60)}分`,
+    );
+  }
+  const meta = create("p", "card-sub", metaParts.join("・"));
+  const text = create("p", "dialog-section", summary.text);
+
+  const actions = create("div", "panel-actions");
+  actions.style.justifyContent = "flex-end";
+
+  const regenerateBtn = create("button", "btn secondary", "再生成");
+  regenerateBtn.dataset.action = "regenerate-summary";
+
+  const closeBtn = create("button", "btn", "閉じる");
+  closeBtn.dataset.action = "close-summary";
+
+  // The away catch-up is not a stored summary and cannot be regenerated.
+  if (summary.kind === "away") {
+    actions.append(closeBtn);
+  } else {
+    actions.append(regenerateBtn, closeBtn);
+  }
+  dialog.append(title, meta, text, actions);
+  overlay.append(dialog);
+  return overlay;
+}
+
 function renderCard(notification: UiNotification): HTMLElement {
   const card = create("article", "card");
 
@@ -705,15 +1104,40 @@ function renderCard(notification: UiNotification): HTMLElement {
   label.setAttribute("style", urgencyBadgeStyle(notification.urgencyColor));
 
   const summary = create("p", "card-summary", notification.summaryLine);
-  const sub = create(
-    "p",
-    "card-sub",
-    `${notification.title || "タイトルなし"} / ${notification.appName}`,
-  );
+  const appName = appLabel(notification.appName, notification.account);
+  const sub = create("p", "card-sub", `${notification.headline || "タイトルなし"} / ${appName}`);
   const time = create("span", "card-time", formatRelativeTime(notification.timestamp));
   time.dataset.timestamp = String(notification.timestamp);
 
-  openBtn.append(label, time, summary, sub);
+  openBtn.append(label);
+  if (notification.lowConfidence) {
+    const check = create("span", "card-label card-check", "要確認");
+    check.title = "分類の確信度が低いため、内容を確認してください";
+    openBtn.append(check);
+  }
+  if (notification.dueAt !== null && notification.dueAt < Date.now() / 1000) {
+    const stale = create("span", "card-label card-check", "時刻経過");
+    stale.title = "通知にある時刻や期限は既に過ぎている可能性があります";
+    openBtn.append(stale);
+  }
+  if (
+    notification.interruptionLevel === "timeSensitive" ||
+    notification.interruptionLevel === "critical"
+  ) {
+    const level = create(
+      "span",
+      "card-label card-check",
+      notification.interruptionLevel === "critical" ? "重大" : "Time Sensitive",
+    );
+    level.title = "送信元アプリが指定した割り込みレベル";
+    openBtn.append(level);
+  }
+  if (notification.systemCritical) {
+    const alert = create("span", "card-label card-check", "クリティカルアラート");
+    alert.title = "macOS のクリティカルアラートとして届いたため、分析せずに緊急として扱いました";
+    openBtn.append(alert);
+  }
+  openBtn.append(time, summary, sub);
 
   const openAppBtn = create("button", "card-clear");
   openAppBtn.type = "button";
@@ -749,10 +1173,32 @@ function renderDialog(notification: UiNotification): HTMLElement {
   const app = create("span", "dialog-pill", notification.appName);
   app.style.background = "#334155";
   meta.append(urgency, app);
+  if (notification.confidence < 1) {
+    const confidence = create(
+      "span",
+      "dialog-pill",
+      `確信度 ${Math.round(notification.confidence * 100)}%`,
+    );
+    confidence.style.background = "#334155";
+    meta.append(confidence);
+  }
 
   const reasonTitle = create("p", "card-sub", "AI判定理由");
   const reason = create("p", "dialog-section", notification.reason);
 
+  const contextTitle = create("p", "card-sub", "適用したコンテキスト");
+  const context = create(
+    "p",
+    "dialog-section",
+    notification.contextApplied ? (notification.appContext ?? "") : "なし",
+  );
+
+  const sessionContextTitle = create("p", "card-sub", "今回のセッション限定のコンテキスト");
+  const sessionContext = create("p", "dialog-section", notification.sessionContext ?? "");
+
+  const translationTitle = create("p", "card-sub", "翻訳");
+  const translation = create("p", "dialog-section", notification.translatedSummary ?? "");
+
   const originalTitle = create("p", "card-sub", "元通知");
   const original = create(
     "p",
@@ -782,7 +1228,19 @@ function renderDialog(notification: UiNotification): HTMLElement {
   clearBtn.dataset.id = String(notification.id);
 
   actions.append(closeBtn, openAppBtn, clearBtn);
-  dialog.append(title, meta, reasonTitle, reason, originalTitle, original, actions);
+  dialog.append(
+    title,
+    meta,
+    reasonTitle,
+    reason,
+    contextTitle,
+    context,
+    ...(notification.sessionContext ? [sessionContextTitle, sessionContext] : []),
+    ...(notification.translatedSummary ? [translationTitle, translation] : []),
+    originalTitle,
+    original,
+    actions,
+  );
   overlay.append(dialog);
   return overlay;
 }
@@ -873,9 +1331,69 @@ function renderSettingsView(container: HTMLElement): void {
   }
 
   elements.push(ignoredSection);
+  elements.push(renderDatabaseSettingsSection());
   container.replaceChildren(...elements);
 }
 
+function renderDatabaseSettingsSection(): HTMLElement {
+  const section = create("section", "group");
+  section.style.marginTop = "16px";
+  const title = create("h2", "group-title", "他のユーザーの通知 DB");
+  const hint = create(
+    "p",
+    "card-sub",
+    "読み取れる別の macOS ユーザーの通知 DB を、このユーザーの通知と一緒に扱います。",
+  );
+  hint.style.margin = "4px 0 4px";
+  section.append(title, hint);
+
+  if (state.notificationDatabases.length === 0) {
+    section.append(create("div", "empty", "追加の DB はありません。"));
+  }
+
+  for (const database of state.notificationDatabases) {
+    const row = create("div", "group-header");
+    const label = create(
+      "span",
+      "card-sub",
+      database.label ? `${database.label}（${database.path}）` : database.path,
+    );
+    const removeBtn = create("button", "group-clear-btn");
+    removeBtn.title = "削除";
+    removeBtn.dataset.action = "settings-remove-database";
+    removeBtn.dataset.path = database.path;
+    removeBtn.innerHTML =
+      '<svg width="14" height="14" viewBox="0 0 16 16" fill="none" stroke="currentColor" stroke-width="1.5" stroke-linecap="round" stroke-linejoin="round"><path d="M3 3l10 10"/><path d="M13 3L3 13"/></svg>';
+    row.append(label, removeBtn);
+    section.append(row);
+  }
+
+  const pathInput = document.createElement("input");
+  pathInput.type = "text";
+  pathInput.className = "prompt-input";
+  pathInput.value = state.newDatabase.path;
+  pathInput.placeholder =
+    "/Users/other/Library/Group Containers/group.com.apple.usernoted/db2/db";
+  pathInput.dataset.field = "database-path";
+
+  const labelInput = document.createElement("input");
+  labelInput.type = "text";
+  labelInput.className = "prompt-input";
+  labelInput.value = state.newDatabase.label;
+  labelInput.placeholder = "表示名（省略時はパス）";
+  labelInput.dataset.field = "database-label";
+  labelInput.style.marginTop = "6px";
+
+  const actions = create("div", "panel-actions");
+  actions.style.marginTop = "8px";
+  const addBtn = create("button", "btn", "追加");
+  addBtn.dataset.action = "settings-add-database";
+  actions.append(addBtn);
+
+  section.append(pathInput, labelInput, actions);
+  return section;
+}
+
 function renderLlmSettingsSection(): HTMLElement {
   const section = create("section", "group");
 
@@ -940,6 +1458,10 @@ function renderLlmSettingsSection(): HTMLElement {
       : "現在の設定はまだありません。",
   );
   current.style.margin = "8px 0 0";
+  if (!state.analysisEnabled) {
+    current.textContent +=
+      "（AI分析は一時停止中です。トレイメニューから再開できます）";
+  }
 
   const actions = create("div", "panel-actions");
   actions.style.marginTop = "10px";
@@ -1017,15 +1539,18 @@ function renderPromptForm(editing: {
 async function loadSettings(): Promise<void> {
   try {
     state.error = "";
-    const [prompts, ignoredApps, llmSettings] = await Promise.all([
+    const [prompts, ignoredApps, llmSettings, databases] = await Promise.all([
       invokeCommand<AppPromptEntry[]>("get_app_prompts"),
       invokeCommand<string[]>("get_ignored_apps"),
       invokeCommand<LlmSettings>("get_llm_settings"),
+      invokeCommand<NotificationDatabase[]>("get_notification_databases"),
     ]);
     state.prompts = prompts;
     state.ignoredApps = ignoredApps;
+    state.notificationDatabases = databases;
     state.llmModels = llmSettings.models;
     state.selectedLlmModel = llmSettings.selectedModel;
+    state.analysisEnabled = llmSettings.analysisEnabled;
   } catch (error) {
     state.error = (error as Error).message;
   }
@@ -1079,6 +1604,35 @@ async function removeIgnoredApp(bundleId: string): Promise<void> {
   }
 }
 
+async function addNotificationDatabase(
+  path: string,
+  label: string,
+): Promise<void> {
+  try {
+    state.error = "";
+    await invokeCommand("add_notification_database", {
+      path,
+      label: label.trim() || null,
+    });
+    state.newDatabase = { path: "", label: "" };
+    await loadSettings();
+  } catch (error) {
+    state.error = (error as Error).message;
+    render();
+  }
+}
+
+async function removeNotificationDatabase(path: string): Promise<void> {
+  try {
+    state.error = "";
+    await invokeCommand("remove_notification_database", { path });
+    await loadSettings();
+  } catch (error) {
+    state.error = (error as Error).message;
+    render();
+  }
+}
+
 async function saveLlmModel(model: string): Promise<void> {
   try {
     state.error = "";
@@ -1090,14 +1644,71 @@ async function saveLlmModel(model: string): Promise<void> {
   }
 }
 
+async function loadOnboarding(): Promise<void> {
+  try {
+    const onboarding = await invokeCommand<OnboardingState>("get_onboarding_state");
+    if (onboarding.step === "choose_model") {
+      const llmSettings = await invokeCommand<LlmSettings>("get_llm_settings");
+      state.llmModels = llmSettings.models;
+      state.selectedLlmModel = llmSettings.selectedModel || llmSettings.models[0] || "";
+    }
+    state.onboarding = onboarding;
+  } catch (error) {
+    state.error = (error as Error).message;
+  }
+  render();
+}
+
+async function runOnboardingStep(): Promise<void> {
+  const step = state.onboarding?.step;
+  try {
+    state.error = "";
+    switch (step) {
+      case "full_disk_access":
+        await invokeCommand("verify_full_disk_access");
+        break;
+      case "choose_model":
+        await invokeCommand("choose_onboarding_model", { model: state.selectedLlmModel });
+        break;
+      case "confirm_ollama":
+        await invokeCommand("confirm_ollama");
+        break;
+      case "interruption_threshold":
+        await invokeCommand("choose_interruption_threshold", {
+          min: state.onboardingThreshold === "none" ? null : state.onboardingThreshold,
+        });
+        break;
+      case "test_notification":
+        await invokeCommand("send_test_notification");
+        break;
+      default:
+        return;
+    }
+  } catch (error) {
+    state.error = (error as Error).message;
+  }
+  await loadOnboarding();
+}
+
+async function skipOnboardingStep(): Promise<void> {
+  try {
+    state.error = "";
+    await invokeCommand("skip_onboarding_step", { step: state.onboarding?.step });
+  } catch (error) {
+    state.error = (error as Error).message;
+  }
+  await loadOnboarding();
+}
+
 async function loadGroups(): Promise<void> {
   state.loading = true;
   state.error = "";
   render();
 
   try {
-    const groups = await invokeCommand<UiNotificationGroup[]>("get_notification_groups");
-    state.groups = groups;
+    const page = await invokeCommand<UiNotificationGroups>("get_notification_groups");
+    state.groups = page.groups;
+    state.revision = page.revision;
   } catch (error) {
     state.error = (error as Error).message;
   } finally {
@@ -1109,7 +1720,33 @@ async function loadGroups(): Promise<void> {
 async function clearOne(id: number): Promise<void> {
   try {
     state.error = "";
-    await invokeCommand<boolean>("clear_notification", { id });
+    const step = await invokeCommand<TriageStep<boolean>>(
+      "clear_notification",
+      { id },
+    );
+    if (step.result) {
+      state.lastClearedId = id;
+      window.clearTimeout(restoreTimer);
+      restoreTimer = window.setTimeout(() => {
+        state.lastClearedId = null;
+        render();
+      }, RESTORE_WINDOW_MS);
+    }
+    await loadGroups();
+    assertRootFrameStable();
+  } catch (error) {
+    state.error = (error as Error).message;
+    render();
+  }
+}
+
+async function clearApp(
+  bundleId: string,
+  scope: AccountScope,
+): Promise<void> {
+  try {
+    state.error = "";
+    await invokeCommand<number>("clear_app_notifications", { bundleId, scope });
     await loadGroups();
     assertRootFrameStable();
   } catch (error) {
@@ -1118,10 +1755,16 @@ async function clearOne(id: number): Promise<void> {
   }
 }
 
-async function clearApp(bundleId: string): Promise<void> {
+async function snoozeApp(
+  bundleId: string,
+  scope: AccountScope,
+): Promise<void> {
   try {
     state.error = "";
-    await invokeCommand<number>("clear_app_notifications", { bundleId });
+    await invokeCommand<TriageStep<unknown>>("snooze_app_until_focus_end", {
+      bundleId,
+      scope,
+    });
     await loadGroups();
     assertRootFrameStable();
   } catch (error) {
@@ -1144,6 +1787,56 @@ async function clearAll(): Promise<boolean> {
   }
 }
 
+async function restoreOne(id: number): Promise<void> {
+  state.lastClearedId = null;
+  window.clearTimeout(restoreTimer);
+  try {
+    state.error = "";
+    const restored = await invokeCommand<boolean>("restore_notification", { id });
+    if (!restored) {
+      state.error = "この通知はもう戻せません";
+      render();
+      return;
+    }
+    await loadGroups();
+  } catch (error) {
+    state.error = (error as Error).message;
+    render();
+  }
+}
+
+async function undoClear(): Promise<void> {
+  try {
+    state.error = "";
+    const restored = await invokeCommand<number>("undo_clear");
+    if (restored === 0) {
+      state.error = "元に戻せるクリアはありません";
+      render();
+      return;
+    }
+    await loadGroups();
+  } catch (error) {
+    state.error = (error as Error).message;
+    render();
+  }
+}
+
+async function showLastSummary(regenerate: boolean): Promise<void> {
+  try {
+    state.error = "";
+    const summary = regenerate
+      ? await invokeCommand<SessionSummary>("regenerate_summary")
+      : await invokeCommand<SessionSummary | null>("get_last_summary");
+    if (!summary) {
+      state.error = "まだ要約はありません";
+    }
+    state.summary = summary;
+  } catch (error) {
+    state.error = (error as Error).message;
+  }
+  render();
+}
+
 async function hideMainWindow(): Promise<void> {
   try {
     await invokeCommand<void>("hide_main_window");
@@ -1184,10 +1877,32 @@ async function setupEventListener(): Promise<void> {
   await listen("notifications-updated", () => {
     void loadGroups();
   });
+
+  await listen<string>("summary-chunk", (event) => {
+    if (!state.summary) {
+      return;
+    }
+    state.summary = { ...state.summary, text: event.payload };
+    render();
+  });
+
+  await listen<SessionSummary>("summary-ready", (event) => {
+    if (!state.summary) {
+      return;
+    }
+    state.summary = event.payload;
+    render();
+  });
+
+  await listen<SessionSummary>("away-summary", (event) => {
+    state.summary = event.payload;
+    render();
+  });
 }
 
 void setupEventListener();
 void loadGroups();
+void loadOnboarding();
 
 setInterval(() => {
   for (const el of document.querySelectorAll<HTMLElement>(".card-time")) {